@@ -0,0 +1,46 @@
+//! Throughput of allocation lookups by id, run with and without the `ahash` feature:
+//!
+//! `cargo bench --bench alloc` and `cargo bench --bench alloc --features ahash`
+
+use std::{hint::black_box, time::Instant};
+
+use rether::{
+    alloc::{BufferAlloc, BufferDynamicAlloc, BufferDynamicAllocator},
+    vertex::Vertex,
+};
+
+const ALLOCATIONS: usize = 10_000;
+const ROUNDS: usize = 100;
+
+fn main() {
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+
+    let ids = (0..ALLOCATIONS)
+        .map(|index| format!("allocation-{index}"))
+        .collect::<Vec<String>>();
+
+    for id in ids.iter() {
+        allocator.allocate(id, 3);
+    }
+
+    let start = Instant::now();
+
+    for _ in 0..ROUNDS {
+        for id in ids.iter() {
+            black_box(allocator.get(black_box(id)));
+        }
+    }
+
+    let elapsed = start.elapsed();
+    let lookups = ALLOCATIONS * ROUNDS;
+
+    println!(
+        "get: {lookups} lookups in {elapsed:?}, {:.1} ns/lookup, hasher: {}",
+        elapsed.as_nanos() as f64 / lookups as f64,
+        if cfg!(feature = "ahash") {
+            "ahash"
+        } else {
+            "SipHash"
+        }
+    );
+}
@@ -0,0 +1,68 @@
+use glam::{Mat4, Vec3};
+
+/// An axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// The smallest box containing all points, None if there are no points.
+    pub fn from_points(points: &[Vec3]) -> Option<Self> {
+        let (first, rest) = points.split_first()?;
+
+        Some(rest.iter().fold(Self::new(*first, *first), |aabb, point| {
+            Self::new(aabb.min.min(*point), aabb.max.max(*point))
+        }))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// The size of the box along each axis.
+    pub fn extents(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+
+    /// The smallest box containing both boxes.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// The box enclosing all corners of this box transformed by `matrix`.
+    pub fn transformed(&self, matrix: &Mat4) -> Aabb {
+        let corners = self.corners().map(|corner| matrix.transform_point3(corner));
+
+        Self::from_points(&corners).expect("A box always has corners")
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+}
@@ -0,0 +1,62 @@
+// Reduces the positions of a vertex buffer to one min/max pair per workgroup,
+// the remaining pairs are reduced on the cpu.
+
+struct Params {
+    count: u32,
+    // size of a vertex in floats, the position is expected at its start
+    stride: u32,
+    _padding: vec2<u32>,
+}
+
+const WORKGROUP_SIZE: u32 = 256u;
+
+@group(0) @binding(0) var<storage, read> vertices: array<f32>;
+@group(0) @binding(1) var<storage, read_write> bounds: array<f32>;
+@group(0) @binding(2) var<uniform> params: Params;
+
+var<workgroup> local_min: array<vec3<f32>, WORKGROUP_SIZE>;
+var<workgroup> local_max: array<vec3<f32>, WORKGROUP_SIZE>;
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn main(
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(workgroup_id) group_id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    var min_position = vec3<f32>(3.40282347e+38);
+    var max_position = vec3<f32>(-3.40282347e+38);
+
+    // every invocation reduces a strided part of the buffer
+    for (var i = group_id.x * WORKGROUP_SIZE + local_id.x; i < params.count; i += WORKGROUP_SIZE * groups.x) {
+        let base = i * params.stride;
+        let position = vec3<f32>(vertices[base], vertices[base + 1u], vertices[base + 2u]);
+
+        min_position = min(min_position, position);
+        max_position = max(max_position, position);
+    }
+
+    local_min[local_id.x] = min_position;
+    local_max[local_id.x] = max_position;
+
+    workgroupBarrier();
+
+    for (var offset = WORKGROUP_SIZE / 2u; offset > 0u; offset /= 2u) {
+        if local_id.x < offset {
+            local_min[local_id.x] = min(local_min[local_id.x], local_min[local_id.x + offset]);
+            local_max[local_id.x] = max(local_max[local_id.x], local_max[local_id.x + offset]);
+        }
+
+        workgroupBarrier();
+    }
+
+    if local_id.x == 0u {
+        let base = group_id.x * 6u;
+
+        bounds[base] = local_min[0].x;
+        bounds[base + 1u] = local_min[0].y;
+        bounds[base + 2u] = local_min[0].z;
+        bounds[base + 3u] = local_max[0].x;
+        bounds[base + 4u] = local_max[0].y;
+        bounds[base + 5u] = local_max[0].z;
+    }
+}
@@ -1,17 +1,47 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap, HashSet},
     sync::{
-        atomic::{AtomicBool, AtomicUsize},
-        mpsc::{SendError, Sender},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize},
+        mpsc::Sender,
         Arc,
     },
 };
 
-pub type FnModifyData<T> = Box<dyn FnMut(&mut [T])>;
+use parking_lot::RwLock;
+
+use glam::Mat4;
+
+use crate::{Affine, RetherError};
+
+// actions are sent to the thread owning the buffer, so they have to be Send themselves.
+// This also makes the handles holding their senders Send and Sync without unsafe impls.
+pub type FnModifyData<T> = Box<dyn FnMut(&mut [T]) + Send>;
+
+// lookups by id happen on every write and draw of an allocation, the `ahash` feature
+// swaps the default SipHash for a faster hasher, see `benches/alloc.rs`
+#[cfg(feature = "ahash")]
+type IdMap<V> = HashMap<BufferAllocationID, V, ahash::RandomState>;
+#[cfg(not(feature = "ahash"))]
+type IdMap<V> = HashMap<BufferAllocationID, V>;
+
+#[cfg(feature = "ahash")]
+type IdSet = HashSet<BufferAllocationID, ahash::RandomState>;
+#[cfg(not(feature = "ahash"))]
+type IdSet = HashSet<BufferAllocationID>;
+
+// the matrix of an action and how to apply it, boxed so actions stay small when queued
+type ActionMatrix<T> = Box<(Mat4, fn(&mut [T], &Mat4))>;
 
 pub struct ModifyAction<T> {
     pub offset: usize,
     pub size: usize,
+    id: Option<BufferAllocationID>,
+    // generation of the handle when the action was sent, see `DynamicAllocHandle::cancel_pending`
+    generation: u64,
+    // the closure overwrites the whole range, so the current data doesn't have to be read back
+    write_only: bool,
+    // the matrix and how to apply it for actions of `Self::transform`, merged by multiplying
+    matrix: Option<ActionMatrix<T>>,
     mod_action: FnModifyData<T>,
 }
 
@@ -20,26 +50,129 @@ impl<T> ModifyAction<T> {
         Self {
             offset,
             size,
+            id: None,
+            generation: 0,
+            write_only: false,
+            matrix: None,
             mod_action,
         }
     }
 
+    /// Creates an action that doesn't depend on the current data of the range.
+    ///
+    /// The closure gets a zeroed slice instead of the data read back from the buffer, so it
+    /// has to overwrite every element it wants to keep. Saves the readback, e.g. when
+    /// recoloring a whole allocation.
+    pub fn write_only(offset: usize, size: usize, mod_action: FnModifyData<T>) -> Self {
+        Self {
+            write_only: true,
+            ..Self::new(offset, size, mod_action)
+        }
+    }
+
+    pub fn is_write_only(&self) -> bool {
+        self.write_only
+    }
+
+    /// The matrix of an action created by [`Self::transform`], None once it's chained with
+    /// a closure.
+    pub fn matrix(&self) -> Option<Mat4> {
+        self.matrix.as_ref().map(|matrix| matrix.0)
+    }
+
+    /// The allocation this action was sent through, if it was sent by a handle.
+    pub fn id(&self) -> Option<&BufferAllocationID> {
+        self.id.as_ref()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
     pub fn act(&mut self, data: &mut [T]) {
         (self.mod_action)(data);
     }
 }
 
+impl<T: Affine + 'static> ModifyAction<T> {
+    /// Creates an action applying the affine `matrix` to the range.
+    ///
+    /// Merging two of them multiplies their matrices, so a burst of transforms is applied
+    /// as a single matrix instead of one closure after another.
+    pub fn transform(offset: usize, size: usize, matrix: Mat4) -> Self {
+        Self {
+            matrix: Some(Box::new((matrix, T::apply_matrix as fn(&mut [T], &Mat4)))),
+            ..Self::new(
+                offset,
+                size,
+                Box::new(move |data: &mut [T]| T::apply_matrix(data, &matrix)),
+            )
+        }
+    }
+}
+
+impl<T: 'static> ModifyAction<T> {
+    /// Chains `other` behind this action, so both are applied with a single readback.
+    ///
+    /// Only actions targeting the exact same range can be merged, otherwise `other` is
+    /// handed back. The merged action is write only if this action is. Two actions of
+    /// [`Self::transform`] are merged into one matrix.
+    pub fn merge(&mut self, other: ModifyAction<T>) -> Result<(), ModifyAction<T>> {
+        if self.offset != other.offset
+            || self.size != other.size
+            || self.id != other.id
+            || self.generation != other.generation
+        {
+            return Err(other);
+        }
+
+        if let (Some((first, apply)), Some((second, _))) = (
+            self.matrix.as_deref().copied(),
+            other.matrix.as_deref().copied(),
+        ) {
+            let matrix = second * first;
+
+            self.matrix = Some(Box::new((matrix, apply)));
+            self.mod_action = Box::new(move |data: &mut [T]| apply(data, &matrix));
+
+            return Ok(());
+        }
+
+        self.matrix = None;
+
+        let mut first = std::mem::replace(&mut self.mod_action, Box::new(|_: &mut [T]| {}));
+        let mut second = other.mod_action;
+
+        self.mod_action = Box::new(move |data: &mut [T]| {
+            first(data);
+            second(data);
+        });
+
+        Ok(())
+    }
+}
+
 pub trait AllocHandle<T> {
     fn id(&self) -> &BufferAllocationID;
     fn offset(&self) -> usize;
     fn size(&self) -> usize;
 
-    fn get_action_sender(&self) -> &Sender<ModifyAction<T>>;
+    fn get_action_sender(&self) -> Sender<ModifyAction<T>>;
+
+    /// Actions sent before the current generation are skipped, handles that can't cancel
+    /// their actions stay at zero.
+    fn generation(&self) -> u64 {
+        0
+    }
 
-    fn send_action(&self, mut action: ModifyAction<T>) -> Result<(), SendError<ModifyAction<T>>> {
+    fn send_action(&self, mut action: ModifyAction<T>) -> Result<(), RetherError> {
         action.offset += self.offset();
+        action.id = Some(self.id().clone());
+        action.generation = self.generation();
 
-        self.get_action_sender().send(action)
+        self.get_action_sender()
+            .send(action)
+            .map_err(|_| RetherError::SendFailed)
     }
 }
 
@@ -52,9 +185,6 @@ pub struct StaticAllocHandle<T> {
     action_sender: Sender<ModifyAction<T>>,
 }
 
-unsafe impl<T> Send for StaticAllocHandle<T> {}
-unsafe impl<T> Sync for StaticAllocHandle<T> {}
-
 impl<T> StaticAllocHandle<T> {
     pub fn new(
         id: &str,
@@ -103,8 +233,8 @@ impl<T> AllocHandle<T> for StaticAllocHandle<T> {
         self.size
     }
 
-    fn get_action_sender(&self) -> &Sender<ModifyAction<T>> {
-        &self.action_sender
+    fn get_action_sender(&self) -> Sender<ModifyAction<T>> {
+        self.action_sender.clone()
     }
 }
 
@@ -112,16 +242,15 @@ impl<T> AllocHandle<T> for StaticAllocHandle<T> {
 pub struct DynamicAllocHandle<T> {
     id: String,
     destroyed: AtomicBool,
-    destroy_sender: std::sync::mpsc::Sender<BufferAllocationID>,
+    // the senders are swappable so the handle can be attached to another allocator
+    destroy_sender: RwLock<Sender<BufferAllocationID>>,
     offset: AtomicUsize,
     size: AtomicUsize,
+    generation: AtomicU64,
 
-    action_sender: Sender<ModifyAction<T>>,
+    action_sender: RwLock<Sender<ModifyAction<T>>>,
 }
 
-unsafe impl<T> Send for DynamicAllocHandle<T> {}
-unsafe impl<T> Sync for DynamicAllocHandle<T> {}
-
 impl<T> std::hash::Hash for DynamicAllocHandle<T> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.id.hash(state);
@@ -141,8 +270,12 @@ impl<T> AllocHandle<T> for DynamicAllocHandle<T> {
         self.size.load(std::sync::atomic::Ordering::Relaxed)
     }
 
-    fn get_action_sender(&self) -> &Sender<ModifyAction<T>> {
-        &self.action_sender
+    fn get_action_sender(&self) -> Sender<ModifyAction<T>> {
+        self.action_sender.read().clone()
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(std::sync::atomic::Ordering::Relaxed)
     }
 }
 
@@ -156,21 +289,44 @@ impl<T> DynamicAllocHandle<T> {
         Self {
             id,
             destroyed: AtomicBool::new(false),
-            destroy_sender,
+            destroy_sender: RwLock::new(destroy_sender),
             offset: AtomicUsize::new(allocation.offset),
             size: AtomicUsize::new(allocation.size),
+            generation: AtomicU64::new(0),
 
-            action_sender,
+            action_sender: RwLock::new(action_sender),
         }
     }
 
-    pub fn destroy(&self) {
+    /// Attaches the handle to another allocator, actions and destroy requests sent
+    /// afterwards go to the new senders.
+    ///
+    /// Actions that were already sent stay with the old allocator.
+    pub fn rebind(
+        &self,
+        action_sender: Sender<ModifyAction<T>>,
+        destroy_sender: Sender<BufferAllocationID>,
+    ) {
+        *self.action_sender.write() = action_sender;
+        *self.destroy_sender.write() = destroy_sender;
+    }
+
+    pub fn destroy(&self) -> Result<(), RetherError> {
         self.destroyed
             .store(true, std::sync::atomic::Ordering::Relaxed);
 
         self.destroy_sender
+            .read()
             .send(self.id.clone())
-            .expect("Failed to send destroy request");
+            .map_err(|_| RetherError::SendFailed)
+    }
+
+    /// Cancels all actions sent through this handle that weren't applied yet.
+    ///
+    /// The actions stay queued, but the next update of the buffer skips them.
+    pub fn cancel_pending(&self) {
+        self.generation
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
     }
 
     pub fn is_destroyed(&self) -> bool {
@@ -188,33 +344,113 @@ impl<T> DynamicAllocHandle<T> {
         self.offset
             .fetch_sub(pos, std::sync::atomic::Ordering::Relaxed);
     }
+
+    // destroyed without a request, for allocations the allocator already removed itself
+    fn mark_destroyed(&self) {
+        self.destroyed
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn set_offset(&self, offset: usize) {
+        self.offset
+            .store(offset, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn set_size(&self, size: usize) {
+        self.size.store(size, std::sync::atomic::Ordering::Relaxed);
+    }
 }
 
 pub trait BufferAlloc<T> {
     type Handle: AllocHandle<T>;
 
     fn get(&self, id: &str) -> Option<&Arc<Self::Handle>>;
+    /// A copy of the range of `id`, for reads that don't need to hold the handle.
+    fn allocation(&self, id: &str) -> Option<BufferAllocation> {
+        self.get(id).map(|handle| BufferAllocation {
+            offset: handle.offset(),
+            size: handle.size(),
+        })
+    }
+    /// All handles, ordered by their offset.
+    fn handles<'a>(&'a self) -> impl Iterator<Item = &'a Arc<Self::Handle>>
+    where
+        Self::Handle: 'a;
     fn size(&self) -> usize;
-    fn update(&self, modify: impl Fn(ModifyAction<T>));
+    fn update(&self, modify: impl FnMut(ModifyAction<T>));
+
+    /// Announces that `additional` elements are about to be allocated.
+    ///
+    /// Only a hint for allocators that track it, [`super::Buffer::reserve`] grows the
+    /// device buffer itself.
+    fn reserve(&mut self, _additional: usize) {}
 }
 
 pub trait BufferDynamicAlloc<T>: BufferAlloc<T, Handle = DynamicAllocHandle<T>> {
     fn allocate(&mut self, id: &str, size: usize) -> Arc<DynamicAllocHandle<T>>;
     fn free(&mut self, id: &str) -> Option<BufferAllocation>;
-    fn get_destroyed_handles(&self) -> Vec<BufferAllocationID>;
+
+    /// Frees all `ids` at once, unknown ids are skipped.
+    ///
+    /// Returns the freed allocations ordered by offset, with the offsets they had before
+    /// any of them was freed, so the data can be compacted in a single pass.
+    fn free_many(&mut self, ids: &[BufferAllocationID]) -> Vec<BufferAllocation> {
+        let mut seen = HashSet::new();
+
+        let mut freed = ids
+            .iter()
+            .filter(|id| seen.insert(*id))
+            .filter_map(|id| self.allocation(id))
+            .collect::<Vec<BufferAllocation>>();
+
+        for id in ids {
+            self.free(id);
+        }
+
+        freed.sort_by_key(|allocation| allocation.offset);
+
+        freed
+    }
+
+    /// Drains the ids of all destroyed handles, they still have to be freed.
+    fn get_destroyed_handles(&mut self) -> Vec<BufferAllocationID>;
+
+    /// The ids of all destroyed handles, without draining them.
+    ///
+    /// Lets a renderer find out what will be freed while the current frame may still draw
+    /// those regions, and free them later at a safe point.
+    fn peek_destroyed(&mut self) -> &[BufferAllocationID];
 }
 
+/// Allocators that can change the size of an allocation without moving it, needed by
+/// [`crate::IndexedBuffer::resize_indices`].
+pub trait BufferResizeAlloc<T>: BufferDynamicAlloc<T> {
+    /// Changes the size of the allocation `id` in place, the following allocations move by
+    /// the difference. Returns the previous allocation, None if `id` doesn't exist.
+    fn resize(&mut self, id: &str, size: usize) -> Option<BufferAllocation>;
+}
+
+/// Packs allocations back to back, freeing one moves all allocations behind it left,
+/// so there are never gaps between allocations.
 #[derive(Debug)]
 pub struct BufferDynamicAllocator<T> {
-    packets: HashMap<BufferAllocationID, Arc<DynamicAllocHandle<T>>>,
+    packets: IdMap<Arc<DynamicAllocHandle<T>>>,
+    // packets ordered by their offset, so a free only touches the packets behind it
+    order: BTreeSet<(usize, BufferAllocationID)>,
 
     destroy_requests: std::sync::mpsc::Receiver<BufferAllocationID>,
+    // destroy requests already received by `peek_destroyed`, but not drained yet.
+    // The set drops repeated requests for the same id, the vec keeps their order
+    destroyed: Vec<BufferAllocationID>,
+    destroyed_set: IdSet,
     dummy_destroy_sender: std::sync::mpsc::Sender<BufferAllocationID>,
 
     action_queue: std::sync::mpsc::Receiver<ModifyAction<T>>,
     dummy_action_sender: std::sync::mpsc::Sender<ModifyAction<T>>,
 
     size: usize,
+    // elements announced by `reserve` that weren't allocated yet
+    reserved: usize,
 }
 
 impl<T> Default for BufferDynamicAllocator<T> {
@@ -225,38 +461,75 @@ impl<T> Default for BufferDynamicAllocator<T> {
 
         Self {
             packets: Default::default(),
+            order: Default::default(),
             destroy_requests: rx,
+            destroyed: Vec::new(),
+            destroyed_set: Default::default(),
             dummy_destroy_sender: tx,
 
             action_queue: action_rx,
             dummy_action_sender: action_tx,
             size: Default::default(),
+            reserved: 0,
         }
     }
 }
 
-impl<T> BufferAlloc<T> for BufferDynamicAllocator<T> {
+impl<T: 'static> BufferAlloc<T> for BufferDynamicAllocator<T> {
     type Handle = DynamicAllocHandle<T>;
 
     fn get(&self, id: &str) -> Option<&Arc<DynamicAllocHandle<T>>> {
         self.packets.get(id)
     }
 
+    fn handles<'a>(&'a self) -> impl Iterator<Item = &'a Arc<DynamicAllocHandle<T>>>
+    where
+        DynamicAllocHandle<T>: 'a,
+    {
+        self.order.iter().filter_map(|(_, id)| self.packets.get(id))
+    }
+
     fn size(&self) -> usize {
         self.size
     }
 
-    fn update(&self, modify: impl Fn(ModifyAction<T>)) {
+    fn update(&self, mut modify: impl FnMut(ModifyAction<T>)) {
+        // consecutive actions on the same range are coalesced into one
+        let mut pending: Option<ModifyAction<T>> = None;
+
         while let Ok(action) = self.action_queue.try_recv() {
+            if self.is_cancelled(&action) {
+                continue;
+            }
+
+            let action = match pending.as_mut() {
+                Some(current) => match current.merge(action) {
+                    Ok(()) => continue,
+                    Err(action) => action,
+                },
+                None => action,
+            };
+
+            if let Some(previous) = pending.replace(action) {
+                modify(previous);
+            }
+        }
+
+        if let Some(action) = pending {
             modify(action);
         }
     }
+
+    fn reserve(&mut self, additional: usize) {
+        self.reserved += additional;
+    }
 }
 
-impl<T> BufferDynamicAlloc<T> for BufferDynamicAllocator<T> {
+impl<T: 'static> BufferDynamicAlloc<T> for BufferDynamicAllocator<T> {
     fn allocate(&mut self, id: &str, size: usize) -> Arc<DynamicAllocHandle<T>> {
         let offset = self.size;
         self.size += size;
+        self.reserved = self.reserved.saturating_sub(size);
 
         let handle = Arc::new(DynamicAllocHandle::new(
             id.to_string(),
@@ -266,6 +539,7 @@ impl<T> BufferDynamicAlloc<T> for BufferDynamicAllocator<T> {
         ));
 
         self.packets.insert(id.to_string(), handle.clone());
+        self.order.insert((offset, id.to_string()));
 
         handle
     }
@@ -274,14 +548,23 @@ impl<T> BufferDynamicAlloc<T> for BufferDynamicAllocator<T> {
         if let Some(remove_packet) = self.packets.remove(id) {
             self.size -= remove_packet.size();
 
+            self.order.remove(&(remove_packet.offset(), id.to_string()));
+
             // Update offsets of all packets after the removed one
-            for packet in self.packets.values_mut() {
-                if packet.offset() > remove_packet.offset() {
+            let tail = self
+                .order
+                .split_off(&(remove_packet.offset() + 1, String::new()));
+
+            for (offset, id) in tail {
+                if let Some(packet) = self.packets.get(&id) {
                     packet.move_offset_left(remove_packet.size());
                 }
+
+                self.order.insert((offset - remove_packet.size(), id));
             }
 
-            remove_packet.destroy();
+            // the allocator owns the receiver, so this can't fail
+            let _ = remove_packet.destroy();
 
             Some(remove_packet.allocation())
         } else {
@@ -289,8 +572,145 @@ impl<T> BufferDynamicAlloc<T> for BufferDynamicAllocator<T> {
         }
     }
 
-    fn get_destroyed_handles(&self) -> Vec<BufferAllocationID> {
-        self.destroy_requests.try_iter().collect()
+    /// Removes all packets first and moves the remaining ones in a single pass, instead of
+    /// once per freed packet like repeated [`BufferDynamicAlloc::free`] calls.
+    ///
+    /// The handles are marked destroyed without sending destroy requests.
+    fn free_many(&mut self, ids: &[BufferAllocationID]) -> Vec<BufferAllocation> {
+        let mut freed = ids
+            .iter()
+            .filter_map(|id| {
+                let packet = self.packets.remove(id)?;
+
+                self.order.remove(&(packet.offset(), id.clone()));
+                self.size -= packet.size();
+                packet.mark_destroyed();
+
+                Some(packet.allocation())
+            })
+            .collect::<Vec<BufferAllocation>>();
+
+        freed.sort_by_key(|allocation| allocation.offset);
+
+        let Some(first) = freed.first() else {
+            return freed;
+        };
+
+        let tail = self.order.split_off(&(first.offset, String::new()));
+
+        let mut removed = freed.iter().peekable();
+        let mut shift = 0;
+
+        for (offset, id) in tail {
+            while let Some(allocation) =
+                removed.next_if(|allocation| allocation.offset + allocation.size <= offset)
+            {
+                shift += allocation.size;
+            }
+
+            if let Some(packet) = self.packets.get(&id) {
+                packet.set_offset(offset - shift);
+            }
+
+            self.order.insert((offset - shift, id));
+        }
+
+        freed
+    }
+
+    fn get_destroyed_handles(&mut self) -> Vec<BufferAllocationID> {
+        self.receive_destroyed();
+        self.destroyed_set.clear();
+
+        std::mem::take(&mut self.destroyed)
+    }
+
+    fn peek_destroyed(&mut self) -> &[BufferAllocationID] {
+        self.receive_destroyed();
+
+        &self.destroyed
+    }
+}
+
+impl<T: 'static> BufferResizeAlloc<T> for BufferDynamicAllocator<T> {
+    fn resize(&mut self, id: &str, size: usize) -> Option<BufferAllocation> {
+        let packet = self.packets.get(id)?.clone();
+        let previous = packet.allocation();
+
+        // everything from the end of the allocation on lies behind it, also empty allocations
+        // at the same offset as an empty resized one. Empty allocations at its start lie in
+        // front of it, whichever way their ids sort
+        let mut tail = self
+            .order
+            .split_off(&(previous.offset + previous.size, String::new()));
+
+        if tail.remove(&(previous.offset, id.to_string())) {
+            self.order.insert((previous.offset, id.to_string()));
+        }
+
+        for (offset, id) in tail {
+            let moved = offset - previous.size + size;
+
+            if let Some(packet) = self.packets.get(&id) {
+                packet.set_offset(moved);
+            }
+
+            self.order.insert((moved, id));
+        }
+
+        packet.set_size(size);
+        self.size = self.size - previous.size + size;
+
+        Some(previous)
+    }
+}
+
+impl<T> BufferDynamicAllocator<T> {
+    fn receive_destroyed(&mut self) {
+        for id in self.destroy_requests.try_iter() {
+            if self.destroyed_set.insert(id.clone()) {
+                self.destroyed.push(id);
+            }
+        }
+    }
+
+    /// The sender for actions of this allocator, see [`DynamicAllocHandle::rebind`].
+    pub fn action_sender(&self) -> Sender<ModifyAction<T>> {
+        self.dummy_action_sender.clone()
+    }
+
+    /// The sender for destroy requests of this allocator, see [`DynamicAllocHandle::rebind`].
+    pub fn destroy_sender(&self) -> Sender<BufferAllocationID> {
+        self.dummy_destroy_sender.clone()
+    }
+
+    fn is_cancelled(&self, action: &ModifyAction<T>) -> bool {
+        action
+            .id()
+            .and_then(|id| self.packets.get(id))
+            .is_some_and(|handle| action.generation() < handle.generation())
+    }
+
+    /// Elements announced with [`BufferAlloc::reserve`] that weren't allocated yet.
+    pub fn reserved(&self) -> usize {
+        self.reserved
+    }
+
+    /// Iterates over all allocations ordered by their offset.
+    pub fn iter_allocations(
+        &self,
+    ) -> impl Iterator<Item = (&BufferAllocationID, BufferAllocation)> {
+        self.order.iter().map(|(offset, id)| {
+            let size = self.packets.get(id).map_or(0, |packet| packet.size());
+
+            (
+                id,
+                BufferAllocation {
+                    offset: *offset,
+                    size,
+                },
+            )
+        })
     }
 }
 
@@ -0,0 +1,236 @@
+use glam::Vec3;
+use wgpu::util::DeviceExt;
+
+use crate::{model::geometry::vertices_aabb, vertex::Vertex, Aabb, RetherError};
+
+use super::{alloc, raw::read_buffer, Buffer};
+
+const WORKGROUP_SIZE: usize = 256;
+// more workgroups only add work to the final reduction on the cpu
+const MAX_WORKGROUPS: usize = 1024;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    count: u32,
+    stride: u32,
+    _padding: [u32; 2],
+}
+
+impl<L: alloc::BufferAlloc<Vertex>> Buffer<Vertex, L> {
+    /// Computes the bounds of all vertices in the buffer with a compute shader.
+    ///
+    /// Each workgroup reduces its part of the buffer, the few remaining results are
+    /// reduced on the cpu. Meant for large meshes, where iterating the positions on
+    /// the cpu stalls. Buffers larger than `max_storage_buffer_binding_size` are bound
+    /// in chunks.
+    ///
+    /// The buffer has to be created with [`wgpu::BufferUsages::STORAGE`], see
+    /// [`Buffer::new_with_usage`].
+    pub fn compute_aabb(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Aabb, RetherError> {
+        let count = self.inner.size as usize;
+
+        if count == 0 {
+            return Err(RetherError::InvalidState(
+                "Cannot compute the bounds of an empty buffer",
+            ));
+        }
+
+        if !self.inner.usage().contains(wgpu::BufferUsages::STORAGE) {
+            return Err(RetherError::InvalidState(
+                "Computing the bounds needs a buffer with storage usage",
+            ));
+        }
+
+        let pipeline = self.aabb_pipeline.get_or_init(|| {
+            let shader = device.create_shader_module(wgpu::include_wgsl!("aabb.wgsl"));
+
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Aabb Pipeline"),
+                layout: None,
+                module: &shader,
+                entry_point: "main",
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        });
+
+        let limits = device.limits();
+        let stride = self.inner.stride();
+        let alignment = limits.min_storage_buffer_offset_alignment as u64;
+
+        // chunks start at a vertex that is also aligned for a storage binding
+        let step = lcm(stride, alignment) / stride;
+        let chunk_size = ((limits.max_storage_buffer_binding_size as u64 / stride) / step * step)
+            .max(step) as usize;
+
+        let chunks = (0..count)
+            .step_by(chunk_size)
+            .map(|offset| (offset, chunk_size.min(count - offset)))
+            .collect::<Vec<(usize, usize)>>();
+
+        // the first chunk is the largest, every chunk writes its results into its own region
+        let workgroups_of = |size: usize| size.div_ceil(WORKGROUP_SIZE).min(MAX_WORKGROUPS);
+        let region_size = ((workgroups_of(chunks[0].1) * 6 * std::mem::size_of::<f32>()) as u64)
+            .next_multiple_of(alignment);
+        let bounds_size = region_size * chunks.len() as u64;
+
+        let bounds_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Aabb Bounds Buffer"),
+            size: bounds_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Aabb Encoder"),
+        });
+
+        for (index, (offset, size)) in chunks.iter().enumerate() {
+            let params = Params {
+                count: *size as u32,
+                stride: (stride / std::mem::size_of::<f32>() as u64) as u32,
+                _padding: [0; 2],
+            };
+
+            let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Aabb Params Buffer"),
+                contents: bytemuck::bytes_of(&params),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Aabb Bind Group"),
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &self.inner.inner,
+                            offset: *offset as u64 * stride,
+                            size: wgpu::BufferSize::new(*size as u64 * stride),
+                        }),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &bounds_buffer,
+                            offset: index as u64 * region_size,
+                            size: wgpu::BufferSize::new(region_size),
+                        }),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Aabb Pass"),
+                timestamp_writes: None,
+            });
+
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(workgroups_of(*size) as u32, 1, 1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let bounds = read_buffer::<f32>(
+            &self.inner.sublabel("aabb readback"),
+            &bounds_buffer,
+            0,
+            bounds_size,
+            device,
+            queue,
+        )?;
+
+        let region_len = region_size as usize / std::mem::size_of::<f32>();
+
+        let aabb = chunks
+            .iter()
+            .zip(bounds.chunks_exact(region_len))
+            .flat_map(|((_, size), region)| region.chunks_exact(6).take(workgroups_of(*size)))
+            .map(|bounds| {
+                Aabb::new(
+                    Vec3::from_slice(&bounds[0..3]),
+                    Vec3::from_slice(&bounds[3..6]),
+                )
+            })
+            .reduce(|a, b| a.union(&b))
+            .expect("At least one workgroup is dispatched");
+
+        Ok(aabb)
+    }
+
+    /// The union of the bounds of all allocations, None if the buffer holds no vertices.
+    ///
+    /// The bounds are cached per allocation and only updated by [`Self::refit_bounds`],
+    /// allocations changed since keep their previous bounds or are missing if they are new.
+    pub fn scene_aabb(&self) -> Option<Aabb> {
+        self.bounds.values().copied().reduce(|a, b| a.union(&b))
+    }
+
+    /// Recomputes the cached bounds of the allocations written or modified since the last
+    /// refit, see [`Self::scene_aabb`].
+    ///
+    /// Meant to be called once per frame after [`Self::update`]. Only the stale
+    /// allocations are read back, all with a single mapping, and nothing is read if
+    /// nothing changed.
+    pub fn refit_bounds(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(), RetherError> {
+        // freed allocations have no bounds to refit
+        let stale = self
+            .stale_bounds
+            .lock()
+            .iter()
+            .filter_map(|id| Some((id.clone(), self.allocater.allocation(id)?)))
+            .collect::<Vec<_>>();
+
+        let ranges = stale
+            .iter()
+            .map(|(_, allocation)| (allocation.offset, allocation.size))
+            .filter(|(_, size)| *size > 0)
+            .collect::<Vec<(usize, usize)>>();
+
+        let vertices = match ranges.is_empty() {
+            true => Vec::new(),
+            false => self.inner.read_ranges::<Vertex>(&ranges, device, queue)?,
+        };
+
+        let mut start = 0;
+
+        for (id, allocation) in stale {
+            let range = start..start + allocation.size;
+            start = range.end;
+
+            match vertices.get(range).and_then(vertices_aabb) {
+                Some(aabb) => self.bounds.insert(id, aabb),
+                None => self.bounds.remove(&id),
+            };
+        }
+
+        self.stale_bounds.lock().clear();
+
+        Ok(())
+    }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let (mut x, mut y) = (a, b);
+
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+
+    a / x * b
+}
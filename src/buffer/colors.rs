@@ -0,0 +1,122 @@
+use wgpu::{Device, Queue};
+
+use crate::RetherError;
+
+use super::{
+    alloc::{self, AllocHandle},
+    raw::RawBuffer,
+    Buffer,
+};
+
+pub(super) type Color = [f32; 4];
+
+/// A copy of the colors of a [`Buffer`] in a buffer of their own, kept in step with the
+/// allocations of the buffer.
+#[derive(Debug)]
+pub(super) struct ColorStream<T> {
+    pub(super) inner: RawBuffer,
+    // where the colors are taken from when data is uploaded to the buffer
+    pub(super) color_of: fn(&T) -> Color,
+}
+
+impl<T> ColorStream<T> {
+    pub(super) fn colors(&self, data: &[T]) -> Vec<Color> {
+        data.iter().map(self.color_of).collect()
+    }
+}
+
+impl<T: bytemuck::Pod + bytemuck::Zeroable, L: alloc::BufferAlloc<T>> Buffer<T, L> {
+    /// Stores the colors in a separate buffer, so recoloring with [`Self::write_colors`]
+    /// doesn't upload the whole elements.
+    ///
+    /// `color_of` picks the color of an element whenever data is uploaded, e.g.
+    /// `|vertex: &Vertex| vertex.color`. The stream is filled from the current contents.
+    /// Draw with [`Self::render_with_streams`] binding [`Self::color_stream`] next to a
+    /// layout without colors, see [`crate::vertex::Vertex::desc_without_color`].
+    ///
+    /// Queued actions only change the elements, recolor through [`Self::write_colors`].
+    pub fn enable_color_stream(
+        &mut self,
+        color_of: fn(&T) -> Color,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<(), RetherError> {
+        let data = self
+            .inner
+            .read::<T>(0, self.inner.size as usize, device, queue)?;
+
+        let colors = data.iter().map(color_of).collect::<Vec<Color>>();
+
+        self.colors = Some(ColorStream {
+            inner: RawBuffer::new_init(
+                &colors,
+                &format!("Colors {}", self.inner.label()),
+                wgpu::BufferUsages::VERTEX,
+                device,
+            ),
+            color_of,
+        });
+
+        Ok(())
+    }
+
+    /// The separate colors, None until [`Self::enable_color_stream`] was called.
+    pub fn color_stream(&self) -> Option<wgpu::BufferSlice<'_>> {
+        self.colors
+            .as_ref()
+            .map(|colors| colors.inner.inner.slice(..))
+    }
+
+    /// Overwrites the colors of the allocation `id` starting at its first element, the
+    /// elements themselves stay untouched.
+    pub fn write_colors(
+        &self,
+        id: &str,
+        colors: &[Color],
+        queue: &Queue,
+    ) -> Result<(), RetherError> {
+        let stream = self
+            .colors
+            .as_ref()
+            .ok_or(RetherError::InvalidState("Buffer has no color stream"))?;
+
+        let allocation = self
+            .allocater
+            .get(id)
+            .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?;
+
+        if colors.len() > allocation.size() {
+            return Err(RetherError::ExceedsAllocation {
+                id: id.to_string(),
+                size: colors.len(),
+                allocation_size: allocation.size(),
+            });
+        }
+
+        stream.inner.write(queue, allocation.offset(), colors);
+
+        Ok(())
+    }
+
+    /// Reads back the separate colors of the allocation `id`.
+    pub fn read_colors(
+        &self,
+        id: &str,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<Vec<Color>, RetherError> {
+        let stream = self
+            .colors
+            .as_ref()
+            .ok_or(RetherError::InvalidState("Buffer has no color stream"))?;
+
+        let allocation = self
+            .allocater
+            .allocation(id)
+            .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?;
+
+        stream
+            .inner
+            .read::<Color>(allocation.offset, allocation.size, device, queue)
+    }
+}
@@ -0,0 +1,47 @@
+/// Decides how much room a buffer reserves once its current capacity no longer fits.
+///
+/// Capacities are counted in elements, not bytes.
+pub trait GrowthStrategy: std::fmt::Debug + Send + Sync {
+    /// Returns the capacity to use for `needed` elements given the `current` capacity.
+    ///
+    /// The returned value must be at least `needed`.
+    fn next_capacity(&self, current: usize, needed: usize) -> usize;
+}
+
+/// Always fits the buffer exactly to the needed size (minimal memory, copy on every growth).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Exact;
+
+impl GrowthStrategy for Exact {
+    fn next_capacity(&self, _current: usize, needed: usize) -> usize {
+        needed
+    }
+}
+
+/// Doubles the capacity until the needed size fits (minimal copies, never shrinks).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Doubling;
+
+impl GrowthStrategy for Doubling {
+    fn next_capacity(&self, current: usize, needed: usize) -> usize {
+        let mut capacity = current.max(1);
+
+        while capacity < needed {
+            capacity *= 2;
+        }
+
+        capacity
+    }
+}
+
+/// Rounds the needed size up to the next multiple of the chunk size.
+#[derive(Debug, Clone, Copy)]
+pub struct Chunked(pub usize);
+
+impl GrowthStrategy for Chunked {
+    fn next_capacity(&self, _current: usize, needed: usize) -> usize {
+        let chunk = self.0.max(1);
+
+        needed.div_ceil(chunk) * chunk
+    }
+}
@@ -0,0 +1,94 @@
+use wgpu::{
+    util::{DrawIndexedIndirectArgs, DrawIndirectArgs},
+    BufferAddress, Device, Queue,
+};
+
+use crate::RetherError;
+
+use super::raw::RawBuffer;
+
+/// Arguments of an indirect draw call, as stored in an [`IndirectBuffer`].
+pub trait IndirectArgs {
+    /// The plain data layout the gpu expects.
+    type Raw: bytemuck::Pod + bytemuck::Zeroable;
+
+    fn to_raw(&self) -> Self::Raw;
+}
+
+impl IndirectArgs for DrawIndirectArgs {
+    type Raw = [u32; 4];
+
+    fn to_raw(&self) -> Self::Raw {
+        bytemuck::pod_read_unaligned(self.as_bytes())
+    }
+}
+
+impl IndirectArgs for DrawIndexedIndirectArgs {
+    // the base vertex is signed, its bits are kept as they are
+    type Raw = [u32; 5];
+
+    fn to_raw(&self) -> Self::Raw {
+        bytemuck::pod_read_unaligned(self.as_bytes())
+    }
+}
+
+/// A buffer of indirect draw arguments, drawn with `render_indirect` of [`super::Buffer`]
+/// and [`super::IndexedBuffer`].
+///
+/// Entries are addressed by their index, so a compute pass or the cpu can update single
+/// draws without rebuilding the whole buffer.
+#[derive(Debug)]
+pub struct IndirectBuffer<A> {
+    inner: RawBuffer,
+    _phantom: std::marker::PhantomData<A>,
+}
+
+impl<A: IndirectArgs> IndirectBuffer<A> {
+    pub fn new(label: &str, device: &Device) -> Self {
+        Self {
+            inner: RawBuffer::new::<A::Raw>(0, label, wgpu::BufferUsages::INDIRECT, device),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Appends `args` and returns its index.
+    pub fn push(&mut self, args: &A, device: &Device, queue: &Queue) -> Result<usize, RetherError> {
+        let index = self.len();
+
+        self.inner.append(&[args.to_raw()], device, queue)?;
+
+        Ok(index)
+    }
+
+    /// Overwrites the entry at `index`.
+    pub fn write(&self, index: usize, args: &A, queue: &Queue) -> Result<(), RetherError> {
+        if index >= self.len() {
+            return Err(RetherError::InvalidState("Indirect index out of range"));
+        }
+
+        self.inner.write(queue, index, &[args.to_raw()]);
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.size as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn label(&self) -> &str {
+        self.inner.label()
+    }
+
+    /// The byte offset of the entry at `index`, as taken by the indirect draw calls.
+    pub fn offset(&self, index: usize) -> BufferAddress {
+        index as BufferAddress * self.inner.stride()
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.inner.inner
+    }
+}
@@ -1,13 +1,36 @@
 pub mod alloc;
+mod bounds;
+mod colors;
+pub mod growth;
+mod indirect;
+mod pick;
+mod pool;
 mod raw;
+mod transforms;
 
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, OnceLock},
+};
 
-use alloc::{AllocHandle, DynamicAllocHandle};
+use alloc::{AllocHandle, BufferAllocationID, DynamicAllocHandle};
+use colors::ColorStream;
+use growth::GrowthStrategy;
 
+pub use indirect::{IndirectArgs, IndirectBuffer};
+use parking_lot::Mutex;
+pub use pick::IdPicker;
+pub use pool::BufferPool;
 use raw::*;
+pub use transforms::{TransformBuffer, TransformUniform};
 use wgpu::{Device, Queue};
 
+use crate::{
+    model::{geometry::weld, BufferLocation},
+    vertex::VertexLayout,
+    Aabb, RetherError, SimpleGeometry,
+};
+
 #[derive(Debug)]
 pub struct BufferData<'a, T> {
     data: &'a [T],
@@ -34,10 +57,87 @@ impl<'a, T> IndexedBufferData<'a, T> {
     }
 }
 
+/// The allocations touched by an update.
+#[derive(Debug, Clone, Default)]
+pub struct BufferUpdate {
+    /// Allocations whose data was modified.
+    pub modified: Vec<BufferAllocationID>,
+    /// Allocations that were freed.
+    pub freed: Vec<BufferAllocationID>,
+}
+
+impl BufferUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.freed.is_empty()
+    }
+}
+
+/// The data of a single allocation at the time it was captured.
+#[derive(Debug, Clone)]
+pub struct Snapshot<T> {
+    id: BufferAllocationID,
+    data: Vec<T>,
+}
+
+impl<T> Snapshot<T> {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+}
+
+/// Groups allocations into separate passes, e.g. to draw transparent geometry after
+/// all opaque geometry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RenderLayer {
+    #[default]
+    Opaque,
+    Transparent,
+    Overlay,
+}
+
+/// Whether a buffer expects to be drawn first into a cleared target or on top of earlier draws.
+///
+/// The buffer never begins a render pass itself, the hint is only read by whoever sets up
+/// the pass, e.g. a tool building a render graph from a set of buffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderHint {
+    /// The pass should clear its targets before drawing the buffer.
+    Clear,
+    /// The buffer is composited over prior draws, the pass should load its targets.
+    #[default]
+    Load,
+}
+
+impl RenderHint {
+    /// The load operation matching the hint, `clear` is used for [`RenderHint::Clear`].
+    pub fn load_op<V>(&self, clear: V) -> wgpu::LoadOp<V> {
+        match self {
+            Self::Clear => wgpu::LoadOp::Clear(clear),
+            Self::Load => wgpu::LoadOp::Load,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Buffer<T, L> {
     inner: RawBuffer,
     allocater: Box<L>,
+    // allocations without an entry are opaque
+    layers: HashMap<BufferAllocationID, RenderLayer>,
+    render_hint: RenderHint,
+    layout: VertexLayout,
+    colors: Option<ColorStream<T>>,
+    // cached per allocation, allocations changed since the last refit are stale
+    bounds: HashMap<BufferAllocationID, Aabb>,
+    stale_bounds: Mutex<HashSet<BufferAllocationID>>,
+    // created by the first `compute_aabb`
+    aabb_pipeline: OnceLock<wgpu::ComputePipeline>,
+    // destroyed ids drained from the allocator, kept until their free succeeded
+    destroyed: Vec<BufferAllocationID>,
     _phantom: std::marker::PhantomData<T>,
 }
 
@@ -46,6 +146,268 @@ impl<T: bytemuck::Pod + bytemuck::Zeroable, L: alloc::BufferAlloc<T>> Buffer<T,
         render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
         render_pass.draw(self.inner.render_range.clone(), 0..1);
     }
+
+    /// Renders the buffer with additional vertex streams bound next to it.
+    ///
+    /// The buffer itself is bound at slot 0, every stream at its given slot. All streams
+    /// must hold at least as many elements as this buffer.
+    pub fn render_with_streams<'a, 'b: 'a>(
+        &'b self,
+        streams: &[(u32, wgpu::BufferSlice<'b>)],
+        render_pass: &'a mut wgpu::RenderPass<'b>,
+    ) {
+        render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
+
+        for (slot, slice) in streams {
+            render_pass.set_vertex_buffer(*slot, *slice);
+        }
+
+        render_pass.draw(self.inner.render_range.clone(), 0..1);
+    }
+
+    /// Renders `count` instances of the buffer, `instances` is bound at slot 1 and should
+    /// hold [`crate::InstanceData`] to give every instance its own transform and tint.
+    pub fn render_instanced_tinted<'a, 'b: 'a>(
+        &'b self,
+        instances: wgpu::BufferSlice<'b>,
+        count: u32,
+        render_pass: &'a mut wgpu::RenderPass<'b>,
+    ) {
+        render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
+        render_pass.set_vertex_buffer(1, instances);
+        render_pass.draw(self.inner.render_range.clone(), 0..count);
+    }
+
+    /// Renders the buffer with one draw call per entry of `indirect`.
+    pub fn render_indirect<'a, 'b: 'a>(
+        &'b self,
+        indirect: &'b IndirectBuffer<wgpu::util::DrawIndirectArgs>,
+        render_pass: &'a mut wgpu::RenderPass<'b>,
+    ) {
+        render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
+
+        for index in 0..indirect.len() {
+            render_pass.draw_indirect(indirect.buffer(), indirect.offset(index));
+        }
+    }
+
+    /// The arguments to draw the allocation `id` indirectly.
+    pub fn indirect_args(&self, id: &str) -> Option<wgpu::util::DrawIndirectArgs> {
+        let allocation = self.allocater.allocation(id)?;
+
+        Some(wgpu::util::DrawIndirectArgs {
+            vertex_count: allocation.size as u32,
+            instance_count: 1,
+            first_vertex: allocation.offset as u32,
+            first_instance: 0,
+        })
+    }
+
+    /// Renders only the allocations in `layer`, allocations without a layer are opaque.
+    pub fn render_layer<'a, 'b: 'a>(
+        &'b self,
+        layer: RenderLayer,
+        render_pass: &'a mut wgpu::RenderPass<'b>,
+    ) {
+        render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
+
+        for range in self.layer_ranges(layer) {
+            render_pass.draw(range, 0..1);
+        }
+    }
+
+    /// The ranges of the buffer drawn by [`Self::render_layer`], sorted and merged.
+    pub fn layer_ranges(&self, layer: RenderLayer) -> Vec<std::ops::Range<u32>> {
+        let tagged = merge_ranges(
+            self.layers
+                .iter()
+                .filter(|(_, tagged)| layer == RenderLayer::Opaque || **tagged == layer)
+                .filter_map(|(id, _)| self.allocater.allocation(id))
+                .map(|allocation| {
+                    allocation.offset as u32..(allocation.offset + allocation.size) as u32
+                })
+                .collect(),
+        );
+
+        if layer != RenderLayer::Opaque {
+            return tagged;
+        }
+
+        // the opaque layer is everything that isn't tagged with another layer
+        let mut ranges = Vec::new();
+        let mut start = self.inner.render_range.start;
+
+        for range in tagged {
+            if range.start > start {
+                ranges.push(start..range.start);
+            }
+
+            start = start.max(range.end);
+        }
+
+        ranges.push(start..self.inner.render_range.end);
+        ranges.retain(|range| !range.is_empty());
+
+        ranges
+    }
+
+    /// Renders only the allocations `ids`, e.g. a selection, binding the buffer once.
+    ///
+    /// The allocations are drawn in buffer order and neighbouring ones are merged into a
+    /// single draw, see [`Self::id_ranges`].
+    pub fn render_ids<'a, 'b: 'a>(
+        &'b self,
+        ids: &[&str],
+        render_pass: &'a mut wgpu::RenderPass<'b>,
+    ) {
+        render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
+
+        for range in self.id_ranges(ids) {
+            render_pass.draw(range, 0..1);
+        }
+    }
+
+    /// The ranges of the buffer drawn by [`Self::render_ids`].
+    ///
+    /// Overlapping and adjacent allocations are merged into one range, unknown ids are
+    /// skipped.
+    pub fn id_ranges(&self, ids: &[&str]) -> Vec<std::ops::Range<u32>> {
+        merge_ranges(
+            ids.iter()
+                .filter_map(|id| self.allocater.allocation(id))
+                .map(|allocation| {
+                    allocation.offset as u32..(allocation.offset + allocation.size) as u32
+                })
+                .collect(),
+        )
+    }
+
+    /// Renders the `locations` of the allocation `id`, e.g. the
+    /// [`crate::model::VisibilityModel::visible_locations`] of a tree woken with it.
+    ///
+    /// The locations are relative to the allocation, see [`Self::location_ranges`].
+    pub fn render_locations<'a, 'b: 'a>(
+        &'b self,
+        id: &str,
+        locations: &[BufferLocation],
+        render_pass: &'a mut wgpu::RenderPass<'b>,
+    ) {
+        render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
+
+        for range in self.location_ranges(id, locations) {
+            render_pass.draw(range, 0..1);
+        }
+    }
+
+    /// The ranges of the buffer drawn for `locations` of the allocation `id`.
+    ///
+    /// Locations are cut off at the end of the allocation, empty ranges are skipped and
+    /// an unknown id has no ranges.
+    pub fn location_ranges(
+        &self,
+        id: &str,
+        locations: &[BufferLocation],
+    ) -> Vec<std::ops::Range<u32>> {
+        let Some(allocation) = self.allocater.allocation(id) else {
+            return Vec::new();
+        };
+
+        locations
+            .iter()
+            .map(|location| {
+                let start = location.offset.min(allocation.size);
+                let end = (location.offset + location.size).min(allocation.size);
+
+                (allocation.offset + start) as u32..(allocation.offset + end) as u32
+            })
+            .filter(|range| !range.is_empty())
+            .collect()
+    }
+
+    /// Moves the allocation `id` into `layer`.
+    pub fn set_layer(&mut self, id: &str, layer: RenderLayer) -> Result<(), RetherError> {
+        if self.allocater.get(id).is_none() {
+            return Err(RetherError::UnknownAllocation(id.to_string()));
+        }
+
+        if layer == RenderLayer::Opaque {
+            self.layers.remove(id);
+        } else {
+            self.layers.insert(id.to_string(), layer);
+        }
+
+        Ok(())
+    }
+
+    pub fn layer(&self, id: &str) -> RenderLayer {
+        self.layers.get(id).copied().unwrap_or_default()
+    }
+
+    /// The label of the buffer, the resources created for it are labeled after it.
+    pub fn label(&self) -> &str {
+        self.inner.label()
+    }
+
+    pub fn render_hint(&self) -> RenderHint {
+        self.render_hint
+    }
+
+    pub fn set_render_hint(&mut self, render_hint: RenderHint) {
+        self.render_hint = render_hint;
+    }
+
+    /// The layout of the elements, to build a matching pipeline with. Defaults to the
+    /// layout of [`crate::vertex::Vertex`].
+    pub fn layout(&self) -> &VertexLayout {
+        &self.layout
+    }
+
+    /// Sets the layout of the elements, its stride has to match the size of `T`.
+    pub fn set_layout(&mut self, layout: VertexLayout) {
+        debug_assert_eq!(
+            layout.stride(),
+            std::mem::size_of::<T>() as wgpu::BufferAddress
+        );
+
+        self.layout = layout;
+    }
+
+    /// The slice of the whole buffer, e.g. to bind it as a stream of another buffer.
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        self.inner.inner.slice(..)
+    }
+
+    pub fn set_growth_strategy(&mut self, growth: impl GrowthStrategy + 'static) {
+        self.inner.set_growth_strategy(growth);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity as usize
+    }
+
+    /// Grows the buffer so `additional` elements can be allocated without copying it again.
+    ///
+    /// The allocator is told about the reservation as well, see [`alloc::BufferAlloc::reserve`].
+    pub fn reserve(
+        &mut self,
+        additional: usize,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<(), RetherError> {
+        self.inner.reserve(additional, device, queue)?;
+        self.allocater.reserve(additional);
+
+        Ok(())
+    }
+
+    fn mark_bounds_stale(&self, id: &str) {
+        self.stale_bounds.lock().insert(id.to_string());
+    }
+
+    fn forget_bounds(&mut self, id: &str) {
+        self.bounds.remove(id);
+        self.stale_bounds.lock().remove(id);
+    }
 }
 
 impl<T: bytemuck::Pod + bytemuck::Zeroable, L: alloc::BufferAlloc<T> + Default> Buffer<T, L> {
@@ -53,34 +415,181 @@ impl<T: bytemuck::Pod + bytemuck::Zeroable, L: alloc::BufferAlloc<T> + Default>
     where
         T: bytemuck::Pod + bytemuck::Zeroable,
     {
+        Self::new_with_usage(label, wgpu::BufferUsages::empty(), device)
+    }
+
+    /// Creates the buffer with `usage` on top of the vertex usage.
+    ///
+    /// E.g. [`wgpu::BufferUsages::STORAGE`] lets compute passes read the vertices, which
+    /// [`Self::compute_aabb`] needs. Not every device supports it, WebGL2 has no storage
+    /// buffers.
+    pub fn new_with_usage(label: &str, usage: wgpu::BufferUsages, device: &wgpu::Device) -> Self {
         // default allocater
         let allocater = L::default();
 
-        let inner =
-            RawBuffer::new::<T>(allocater.size(), label, wgpu::BufferUsages::VERTEX, device);
+        let inner = RawBuffer::new::<T>(
+            allocater.size(),
+            label,
+            wgpu::BufferUsages::VERTEX | usage,
+            device,
+        );
+
+        Self::with_raw(inner, allocater)
+    }
 
+    fn with_raw(inner: RawBuffer, allocater: L) -> Self {
         Self {
             inner,
             allocater: Box::new(allocater),
+            layers: HashMap::new(),
+            render_hint: RenderHint::default(),
+            layout: VertexLayout::vertex(),
+            colors: None,
+            bounds: HashMap::new(),
+            stale_bounds: Mutex::new(HashSet::new()),
+            aabb_pipeline: OnceLock::new(),
+            destroyed: Vec::new(),
             _phantom: std::marker::PhantomData,
         }
     }
 
-    pub fn write(&self, id: &str, buffer_data: BufferData<'_, T>, queue: &wgpu::Queue)
+    /// Writes `buffer_data` to the start of the allocation `id`.
+    ///
+    /// The data must fit into the allocation, writing past its end would overwrite the
+    /// neighbouring allocation. Larger data is rejected with [`RetherError::ExceedsAllocation`].
+    pub fn write(
+        &self,
+        id: &str,
+        buffer_data: BufferData<'_, T>,
+        queue: &wgpu::Queue,
+    ) -> Result<(), RetherError>
     where
         T: bytemuck::Pod + bytemuck::Zeroable,
     {
-        if let Some(allocation) = self.allocater.get(id) {
-            // assert!(buffer_data.data.len() <= allocation.size());
+        let allocation = self
+            .allocater
+            .get(id)
+            .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?;
+
+        if buffer_data.data.len() > allocation.size() {
+            return Err(RetherError::ExceedsAllocation {
+                id: id.to_string(),
+                size: buffer_data.data.len(),
+                allocation_size: allocation.size(),
+            });
+        }
 
-            self.inner
-                .write(queue, allocation.offset(), buffer_data.data);
+        self.inner
+            .write(queue, allocation.offset(), buffer_data.data);
+
+        if let Some(colors) = &self.colors {
+            colors
+                .inner
+                .write(queue, allocation.offset(), &colors.colors(buffer_data.data));
         }
+
+        self.mark_bounds_stale(id);
+
+        Ok(())
     }
 
     pub fn get(&self, id: &str) -> Option<&Arc<L::Handle>> {
         self.allocater.get(id)
     }
+
+    /// The number of elements of the allocation `id`.
+    pub fn allocation_size(&self, id: &str) -> Option<usize> {
+        self.allocater
+            .allocation(id)
+            .map(|allocation| allocation.size)
+    }
+
+    /// Reads back the current data of the allocation `id`, e.g. to undo later edits.
+    pub fn snapshot(
+        &self,
+        id: &str,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<Snapshot<T>, RetherError> {
+        let allocation = self
+            .allocater
+            .get(id)
+            .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?;
+
+        Ok(Snapshot {
+            id: id.to_string(),
+            data: self
+                .inner
+                .read(allocation.offset(), allocation.size(), device, queue)?,
+        })
+    }
+
+    /// Reads back the current data of the allocation `id` as geometry, e.g. to export a mesh
+    /// after it was edited on the gpu.
+    pub fn read_geometry(
+        &self,
+        id: &str,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<SimpleGeometry<T>, RetherError> {
+        let snapshot = self.snapshot(id, device, queue)?;
+
+        Ok(SimpleGeometry::init(snapshot.data))
+    }
+
+    /// Blocks until all writes, copies and modifications issued so far reached the gpu.
+    ///
+    /// Rendering doesn't need this, the queue keeps the order. It's meant for tests and export
+    /// paths that have to see the latest data, e.g. before [`Self::read_geometry`].
+    pub fn sync(&self, device: &Device, queue: &Queue) {
+        sync(device, queue);
+    }
+
+    /// Writes the data of `snapshot` back to its allocation.
+    ///
+    /// Nothing is written if the allocation no longer exists or changed its size.
+    pub fn restore(&self, snapshot: &Snapshot<T>, queue: &Queue) {
+        if let Some(allocation) = self.allocater.get(&snapshot.id) {
+            if allocation.size() == snapshot.data.len() {
+                self.inner.write(queue, allocation.offset(), &snapshot.data);
+
+                if let Some(colors) = &self.colors {
+                    colors
+                        .inner
+                        .write(queue, allocation.offset(), &colors.colors(&snapshot.data));
+                }
+
+                self.mark_bounds_stale(&snapshot.id);
+            }
+        }
+    }
+}
+
+impl<T: bytemuck::Pod + bytemuck::Zeroable, L: alloc::BufferDynamicAlloc<T> + Default>
+    Buffer<T, L>
+{
+    /// Creates the buffer with `buffer_data` as its first allocation `id`.
+    ///
+    /// The data is written while the buffer is mapped at creation instead of being staged
+    /// through the queue, the fastest way to upload a large static mesh once.
+    pub fn new_init(
+        label: &str,
+        id: &str,
+        buffer_data: BufferData<'_, T>,
+        device: &wgpu::Device,
+    ) -> (Self, Arc<DynamicAllocHandle<T>>) {
+        let mut allocater = L::default();
+
+        let inner =
+            RawBuffer::new_init(buffer_data.data, label, wgpu::BufferUsages::VERTEX, device);
+
+        let handle = allocater.allocate(id, buffer_data.data.len());
+
+        let buffer = Self::with_raw(inner, allocater);
+        buffer.mark_bounds_stale(id);
+
+        (buffer, handle)
+    }
 }
 
 impl<T: bytemuck::Pod + bytemuck::Zeroable, L: alloc::BufferDynamicAlloc<T>> Buffer<T, L> {
@@ -89,15 +598,19 @@ impl<T: bytemuck::Pod + bytemuck::Zeroable, L: alloc::BufferDynamicAlloc<T>> Buf
         id: &str,
         device: &Device,
         queue: &Queue,
-    ) -> Arc<DynamicAllocHandle<T>>
+    ) -> Result<Arc<DynamicAllocHandle<T>>, RetherError>
     where
         T: bytemuck::Pod + bytemuck::Zeroable,
     {
-        let handle = self.allocater.allocate(id, S);
+        self.inner.allocate(S, device, queue)?;
 
-        self.inner.allocate::<T>(S, device, queue);
+        if let Some(colors) = &mut self.colors {
+            colors.inner.allocate(S, device, queue)?;
+        }
+
+        self.mark_bounds_stale(id);
 
-        handle
+        Ok(self.allocater.allocate(id, S))
     }
 
     pub fn allocate_init(
@@ -106,32 +619,485 @@ impl<T: bytemuck::Pod + bytemuck::Zeroable, L: alloc::BufferDynamicAlloc<T>> Buf
         buffer_data: BufferData<'_, T>,
         device: &Device,
         queue: &Queue,
-    ) -> Arc<DynamicAllocHandle<T>>
+    ) -> Result<Arc<DynamicAllocHandle<T>>, RetherError>
     where
         T: bytemuck::Pod + bytemuck::Zeroable,
     {
-        let handle = self.allocater.allocate(id, buffer_data.data.len());
+        self.inner.append(buffer_data.data, device, queue)?;
 
-        self.inner.append(buffer_data.data, device, queue);
+        if let Some(colors) = &mut self.colors {
+            let data = colors.colors(buffer_data.data);
+            colors.inner.append(&data, device, queue)?;
+        }
 
-        handle
+        self.mark_bounds_stale(id);
+
+        Ok(self.allocater.allocate(id, buffer_data.data.len()))
     }
 
-    pub fn free(&mut self, id: &str, device: &Device, queue: &Queue) {
-        if let Some(allocation) = self.allocater.free(id) {
-            self.inner
-                .free::<T>(allocation.offset, allocation.size, device, queue);
+    /// Like [`Self::allocate_init`], but growing the buffer is recorded into `encoder`.
+    pub fn allocate_init_with_encoder(
+        &mut self,
+        id: &str,
+        buffer_data: BufferData<'_, T>,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<Arc<DynamicAllocHandle<T>>, RetherError> {
+        self.inner
+            .append_with_encoder(buffer_data.data, device, queue, encoder)?;
+
+        if let Some(colors) = &mut self.colors {
+            let data = colors.colors(buffer_data.data);
+            colors
+                .inner
+                .append_with_encoder(&data, device, queue, encoder)?;
         }
+
+        self.mark_bounds_stale(id);
+
+        Ok(self.allocater.allocate(id, buffer_data.data.len()))
     }
 
-    pub fn update(&mut self, device: &Device, queue: &Queue) {
-        self.allocater
-            .update(|mod_action| self.inner.modify(mod_action, device, queue));
+    /// Allocates all items at once, the buffer grows at most one time.
+    ///
+    /// Returns the handles in the order of `items`.
+    pub fn allocate_batch(
+        &mut self,
+        items: &[(&str, BufferData<'_, T>)],
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<Vec<Arc<DynamicAllocHandle<T>>>, RetherError>
+    where
+        T: bytemuck::Pod + bytemuck::Zeroable,
+    {
+        let total = items.iter().map(|(_, data)| data.data.len()).sum::<usize>();
 
-        for id in self.allocater.get_destroyed_handles() {
-            self.free(&id, device, queue);
+        // uploading the items as one block grows the raw buffer only once
+        let mut data = Vec::with_capacity(total);
+
+        for (_, buffer_data) in items {
+            data.extend_from_slice(buffer_data.data);
         }
+
+        self.inner.append(&data, device, queue)?;
+
+        if let Some(colors) = &mut self.colors {
+            let data = colors.colors(&data);
+            colors.inner.append(&data, device, queue)?;
+        }
+
+        for (id, _) in items {
+            self.mark_bounds_stale(id);
+        }
+
+        Ok(items
+            .iter()
+            .map(|(id, buffer_data)| self.allocater.allocate(id, buffer_data.data.len()))
+            .collect())
+    }
+
+    /// Frees the allocation and moves all following allocations left to close the gap.
+    ///
+    /// The buffer is compacted on every free, so it never fragments and needs no
+    /// separate defragmentation pass. The cost of a free grows with the buffer size,
+    /// batch frees of many allocations at a safe point, see [`Self::free_destroyed`].
+    ///
+    /// Fails without freeing anything if the allocation doesn't lie inside the buffer.
+    pub fn free(&mut self, id: &str, device: &Device, queue: &Queue) -> Result<(), RetherError> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&self.inner.sublabel("free")),
+        });
+
+        self.free_with_encoder(id, device, &mut encoder)?;
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(())
+    }
+
+    /// Like [`Self::free`], but the copies are recorded into `encoder`, see
+    /// [`Self::update_with_encoder`].
+    pub fn free_with_encoder(
+        &mut self,
+        id: &str,
+        device: &Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), RetherError> {
+        self.free_many_with_encoder(&[id.to_string()], device, encoder)
+    }
+
+    /// Frees all `ids` at once, unknown ids are skipped.
+    ///
+    /// The buffer is compacted in a single copy instead of once per allocation, e.g. when
+    /// clearing a large selection.
+    pub fn free_many(
+        &mut self,
+        ids: &[BufferAllocationID],
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<(), RetherError> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&self.inner.sublabel("free")),
+        });
+
+        self.free_many_with_encoder(ids, device, &mut encoder)?;
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(())
     }
+
+    /// Like [`Self::free_many`], but the copies are recorded into `encoder`.
+    pub fn free_many_with_encoder(
+        &mut self,
+        ids: &[BufferAllocationID],
+        device: &Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), RetherError> {
+        let ranges = allocation_ranges::<T, _>(&*self.allocater, ids);
+
+        // validated before the allocator moves the following allocations, so a failure
+        // leaves the allocator and the data in sync
+        self.inner.check_ranges(&ranges)?;
+
+        for id in ids {
+            self.layers.remove(id);
+            self.forget_bounds(id);
+        }
+
+        self.allocater.free_many(ids);
+
+        // destroy requests of the freed handles would otherwise free a later allocation
+        // reusing their id
+        let pending = self.allocater.get_destroyed_handles();
+        self.destroyed.extend(pending);
+        self.destroyed.retain(|id| !ids.contains(id));
+
+        if ranges.is_empty() {
+            return Ok(());
+        }
+
+        self.inner
+            .free_ranges_with_encoder(&ranges, device, encoder)?;
+
+        if let Some(colors) = &mut self.colors {
+            colors
+                .inner
+                .free_ranges_with_encoder(&ranges, device, encoder)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the allocation `id` into `dest`, the data is copied on the gpu.
+    ///
+    /// The allocation is freed in this buffer, so its old handle becomes destroyed.
+    /// Returns the handle of the new allocation in `dest`. Fails if `dest` already holds an
+    /// allocation `id`, replacing it would leave its data unreachable.
+    pub fn migrate<M: alloc::BufferDynamicAlloc<T>>(
+        &mut self,
+        id: &str,
+        dest: &mut Buffer<T, M>,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<Arc<DynamicAllocHandle<T>>, RetherError> {
+        let allocation = self
+            .allocater
+            .get(id)
+            .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?;
+
+        if dest.allocater.get(id).is_some() {
+            return Err(RetherError::InvalidState("Allocation id already in use"));
+        }
+        let (offset, size) = (allocation.offset(), allocation.size());
+
+        dest.inner.allocate(size, device, queue)?;
+        let handle = dest.allocater.allocate(id, size);
+
+        self.inner
+            .copy_to(offset, &dest.inner, handle.offset(), size, device, queue);
+
+        // without colors in this buffer the colors of the allocation stay zeroed in `dest`
+        if let Some(dest_colors) = &mut dest.colors {
+            dest_colors.inner.allocate(size, device, queue)?;
+
+            if let Some(colors) = &self.colors {
+                colors.inner.copy_to(
+                    offset,
+                    &dest_colors.inner,
+                    handle.offset(),
+                    size,
+                    device,
+                    queue,
+                );
+            }
+        }
+
+        if let Some(layer) = self.layers.get(id) {
+            dest.layers.insert(id.to_string(), *layer);
+        }
+
+        if let Some(aabb) = self.bounds.get(id) {
+            dest.bounds.insert(id.to_string(), *aabb);
+        }
+        if self.stale_bounds.lock().contains(id) {
+            dest.mark_bounds_stale(id);
+        }
+
+        self.free(id, device, queue)?;
+
+        Ok(handle)
+    }
+
+    /// Makes `dest` hold the same allocations as this buffer with a copy of their data, e.g.
+    /// a second buffer drawn by a shadow or id pass with its own layout.
+    ///
+    /// Meant to be called after every [`Self::update`]. The allocations of `dest` are only
+    /// rebuilt when they differ from this buffer, the data is copied with a single gpu copy.
+    /// Layers and colors of `dest` aren't mirrored.
+    ///
+    /// All allocations of `dest` are freed before it's rebuilt, so ids of this buffer never
+    /// collide with them.
+    pub fn mirror_to<M: alloc::BufferDynamicAlloc<T>>(
+        &self,
+        dest: &mut Buffer<T, M>,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<(), RetherError> {
+        let source = allocation_layout(self.allocater.handles());
+        let mirrored = allocation_layout(dest.allocater.handles());
+
+        if source != mirrored {
+            let stale = mirrored
+                .into_iter()
+                .map(|(id, _, _)| id)
+                .collect::<Vec<BufferAllocationID>>();
+
+            dest.free_many(&stale, device, queue)?;
+
+            let size = source.iter().map(|(_, _, size)| size).sum();
+
+            dest.inner.allocate(size, device, queue)?;
+
+            if let Some(colors) = &mut dest.colors {
+                colors.inner.allocate(size, device, queue)?;
+            }
+
+            for (id, _, size) in source.iter() {
+                dest.allocater.allocate(id, *size);
+            }
+        }
+
+        for (id, _, _) in source.iter() {
+            dest.mark_bounds_stale(id);
+        }
+
+        self.inner.copy_to(
+            0,
+            &dest.inner,
+            0,
+            self.inner.size.min(dest.inner.size) as usize,
+            device,
+            queue,
+        );
+
+        Ok(())
+    }
+
+    /// Like [`Self::update`], but the frees are recorded into the frame's `encoder`
+    /// instead of being submitted one by one.
+    ///
+    /// Queued actions are applied first, actions reading back their range still submit
+    /// their own copy. Writes through the queue land before the recorded copies, so
+    /// allocations shouldn't be written until `encoder` is submitted.
+    pub fn update_with_encoder(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<BufferUpdate, RetherError> {
+        let mut changes = BufferUpdate::default();
+
+        let result = self.modify_queued(&mut changes.modified, device, queue);
+
+        let freed = self
+            .free_destroyed_by(|buffer, ids| buffer.free_many_with_encoder(ids, device, encoder));
+
+        result.and(freed).map(|freed| {
+            changes.freed = freed;
+            changes
+        })
+    }
+
+    /// Applies all queued actions and frees destroyed handles.
+    ///
+    /// Returns the allocations that were modified or freed. If reading back an allocation
+    /// fails, the remaining actions and frees are still applied and the first error is
+    /// returned. Destroyed handles that couldn't be freed stay pending for the next call.
+    pub fn update(&mut self, device: &Device, queue: &Queue) -> Result<BufferUpdate, RetherError> {
+        let mut changes = BufferUpdate::default();
+
+        let result = self.modify_queued(&mut changes.modified, device, queue);
+
+        let freed = self.free_destroyed(device, queue);
+
+        result.and(freed).map(|freed| {
+            changes.freed = freed;
+            changes
+        })
+    }
+
+    /// Applies all queued actions, destroyed handles are left for [`Self::free_destroyed`].
+    ///
+    /// Returns the modified allocations, or the first error after applying the remaining
+    /// actions.
+    pub fn apply_actions(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<Vec<BufferAllocationID>, RetherError> {
+        let mut modified = Vec::new();
+
+        self.modify_queued(&mut modified, device, queue)
+            .map(|_| modified)
+    }
+
+    fn modify_queued(
+        &mut self,
+        modified: &mut Vec<BufferAllocationID>,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<(), RetherError> {
+        let mut error = None;
+
+        self.allocater.update(|mod_action| {
+            if let Some(id) = mod_action.id() {
+                if !modified.contains(id) {
+                    modified.push(id.clone());
+                }
+            }
+
+            if let Err(err) = self.inner.modify(mod_action, device, queue) {
+                error.get_or_insert(err);
+            }
+        });
+
+        for id in modified.iter() {
+            self.mark_bounds_stale(id);
+        }
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Frees the allocations of all destroyed handles at once, see [`Self::free_many`],
+    /// and returns their ids.
+    ///
+    /// If the free fails nothing is freed, the handles stay pending for the next call.
+    pub fn free_destroyed(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<Vec<BufferAllocationID>, RetherError> {
+        self.free_destroyed_by(|buffer, ids| buffer.free_many(ids, device, queue))
+    }
+
+    fn free_destroyed_by(
+        &mut self,
+        free: impl FnOnce(&mut Self, &[BufferAllocationID]) -> Result<(), RetherError>,
+    ) -> Result<Vec<BufferAllocationID>, RetherError> {
+        let destroyed = std::mem::take(&mut self.destroyed)
+            .into_iter()
+            .chain(self.allocater.get_destroyed_handles())
+            .collect::<Vec<BufferAllocationID>>();
+
+        let mut seen = HashSet::new();
+
+        let freed = destroyed
+            .iter()
+            .filter(|id| self.allocater.get(id).is_some() && seen.insert(*id))
+            .cloned()
+            .collect::<Vec<BufferAllocationID>>();
+
+        match free(self, &freed) {
+            Ok(()) => Ok(freed),
+            Err(err) => {
+                // a failed free leaves the allocator untouched, retry them next time
+                self.destroyed.splice(0..0, destroyed);
+                Err(err)
+            }
+        }
+    }
+
+    /// The ids of destroyed handles that weren't freed yet, they are freed by the next
+    /// [`Self::update`] or [`Self::free_destroyed`].
+    pub fn peek_destroyed(&mut self) -> &[BufferAllocationID] {
+        let peeked = self.allocater.get_destroyed_handles();
+        self.destroyed.extend(peeked);
+
+        &self.destroyed
+    }
+}
+
+// the id, offset and size of every allocation in buffer order
+fn allocation_layout<'a, T: 'a>(
+    handles: impl Iterator<Item = &'a Arc<DynamicAllocHandle<T>>>,
+) -> Vec<(BufferAllocationID, usize, usize)> {
+    handles
+        .map(|handle| (handle.id().clone(), handle.offset(), handle.size()))
+        .collect()
+}
+
+// the vertex and the index handle of an allocation of an `IndexedBuffer`
+type IndexedHandles<T> = (Arc<DynamicAllocHandle<T>>, Arc<DynamicAllocHandle<T>>);
+
+// the current ranges of `ids` sorted by offset, unknown and repeated ids are skipped
+fn allocation_ranges<T, A: alloc::BufferAlloc<T>>(
+    allocater: &A,
+    ids: &[BufferAllocationID],
+) -> Vec<(usize, usize)> {
+    let mut ranges = ids
+        .iter()
+        .filter_map(|id| allocater.allocation(id))
+        .map(|allocation| (allocation.offset, allocation.size))
+        .collect::<Vec<(usize, usize)>>();
+
+    ranges.sort_unstable();
+    ranges.dedup();
+
+    ranges
+}
+
+// sorts the ranges and merges overlapping and adjacent ones, empty ranges are dropped
+fn merge_ranges(mut ranges: Vec<std::ops::Range<u32>>) -> Vec<std::ops::Range<u32>> {
+    ranges.retain(|range| !range.is_empty());
+    ranges.sort_by_key(|range| range.start);
+
+    ranges.into_iter().fold(
+        Vec::new(),
+        |mut merged: Vec<std::ops::Range<u32>>, range| {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+
+            merged
+        },
+    )
+}
+
+/// How the indices of an [`IndexedBuffer`] refer to its vertices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexMode {
+    /// Indices point into the whole vertex buffer, e.g. geometry merged with `Expandable`.
+    #[default]
+    Global,
+    /// Indices of every allocation start at zero, its vertex offset is passed as
+    /// `base_vertex` when drawing. Freeing an allocation doesn't require rewriting indices.
+    Local,
 }
 
 #[derive(Debug)]
@@ -145,6 +1111,9 @@ where
     index: RawBuffer,
     allocater: Box<L>,
     allocator_index: Box<I>,
+    index_mode: IndexMode,
+    // destroyed ids drained from the allocators, kept until their free succeeded
+    destroyed: Vec<BufferAllocationID>,
     _phantom: std::marker::PhantomData<T>,
 }
 
@@ -154,10 +1123,95 @@ where
     L: alloc::BufferAlloc<T>,
     I: alloc::BufferAlloc<T>,
 {
+    /// The label of the vertex buffer, the index buffer is labeled `Index <label>`.
+    pub fn label(&self) -> &str {
+        self.inner.label()
+    }
+
     pub fn render<'a, 'b: 'a>(&'b self, render_pass: &'a mut wgpu::RenderPass<'b>) {
         render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
         render_pass.set_index_buffer(self.index.inner.slice(..), wgpu::IndexFormat::Uint32);
-        render_pass.draw_indexed(0..self.index.size as u32, 0, 0..1);
+
+        match self.index_mode {
+            IndexMode::Global => render_pass.draw_indexed(0..self.index.size as u32, 0, 0..1),
+            IndexMode::Local => {
+                for handle in self.allocator_index.handles() {
+                    self.draw_id(handle.id(), render_pass);
+                }
+            }
+        }
+    }
+
+    /// Renders only the allocation `id`.
+    pub fn render_id<'a, 'b: 'a>(&'b self, id: &str, render_pass: &'a mut wgpu::RenderPass<'b>) {
+        render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
+        render_pass.set_index_buffer(self.index.inner.slice(..), wgpu::IndexFormat::Uint32);
+
+        self.draw_id(id, render_pass);
+    }
+
+    fn draw_id(&self, id: &str, render_pass: &mut wgpu::RenderPass<'_>) {
+        let Some(index_allocation) = self.allocator_index.allocation(id) else {
+            return;
+        };
+
+        let base_vertex = match self.index_mode {
+            IndexMode::Global => 0,
+            IndexMode::Local => self
+                .allocater
+                .allocation(id)
+                .map_or(0, |allocation| allocation.offset as i32),
+        };
+
+        let start = index_allocation.offset as u32;
+
+        render_pass.draw_indexed(
+            start..start + index_allocation.size as u32,
+            base_vertex,
+            0..1,
+        );
+    }
+
+    /// Renders the buffer with one draw call per entry of `indirect`.
+    pub fn render_indirect<'a, 'b: 'a>(
+        &'b self,
+        indirect: &'b IndirectBuffer<wgpu::util::DrawIndexedIndirectArgs>,
+        render_pass: &'a mut wgpu::RenderPass<'b>,
+    ) {
+        render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
+        render_pass.set_index_buffer(self.index.inner.slice(..), wgpu::IndexFormat::Uint32);
+
+        for index in 0..indirect.len() {
+            render_pass.draw_indexed_indirect(indirect.buffer(), indirect.offset(index));
+        }
+    }
+
+    /// The arguments to draw the allocation `id` indirectly, the base vertex follows the
+    /// index mode like in [`Self::render_id`].
+    pub fn indirect_args(&self, id: &str) -> Option<wgpu::util::DrawIndexedIndirectArgs> {
+        let index_allocation = self.allocator_index.get(id)?;
+
+        let base_vertex = match self.index_mode {
+            IndexMode::Global => 0,
+            IndexMode::Local => self.allocater.get(id)?.offset() as i32,
+        };
+
+        Some(wgpu::util::DrawIndexedIndirectArgs {
+            index_count: index_allocation.size() as u32,
+            instance_count: 1,
+            first_index: index_allocation.offset() as u32,
+            base_vertex,
+            first_instance: 0,
+        })
+    }
+
+    /// Sets how indices refer to vertices, should be chosen before allocating.
+    pub fn set_index_mode(&mut self, index_mode: IndexMode) {
+        self.index_mode = index_mode;
+    }
+
+    pub fn index_mode(&self) -> IndexMode {
+        self.index_mode
     }
 }
 
@@ -179,7 +1233,7 @@ where
         let index = RawBuffer::new::<u32>(
             allocator_index.size(),
             &format!("Index {}", label),
-            wgpu::BufferUsages::VERTEX,
+            wgpu::BufferUsages::INDEX,
             device,
         );
 
@@ -188,39 +1242,216 @@ where
             index,
             allocater: Box::new(allocater),
             allocator_index: Box::new(allocator_index),
+            index_mode: IndexMode::default(),
+            destroyed: Vec::new(),
             _phantom: std::marker::PhantomData,
         }
     }
 }
 
+impl<T, L, I> IndexedBuffer<T, L, I>
+where
+    T: bytemuck::Pod + bytemuck::Zeroable,
+    L: alloc::BufferDynamicAlloc<T> + Default,
+    I: alloc::BufferDynamicAlloc<T> + Default,
+{
+    /// Creates the buffer with `buffer_data` as its first allocation `id`, written through
+    /// mappings at creation like [`Buffer::new_init`].
+    ///
+    /// Returns the handles like [`Self::allocate`].
+    pub fn new_init(
+        label: &str,
+        id: &str,
+        buffer_data: IndexedBufferData<'_, T>,
+        device: &wgpu::Device,
+    ) -> (Self, IndexedHandles<T>) {
+        let mut allocater = L::default();
+        let mut allocator_index = I::default();
+
+        let inner =
+            RawBuffer::new_init(buffer_data.data, label, wgpu::BufferUsages::VERTEX, device);
+        let index = RawBuffer::new_init(
+            buffer_data.indices,
+            &format!("Index {}", label),
+            wgpu::BufferUsages::INDEX,
+            device,
+        );
+
+        let handles = (
+            allocater.allocate(id, buffer_data.data.len()),
+            allocator_index.allocate(id, buffer_data.indices.len()),
+        );
+
+        let buffer = Self {
+            inner,
+            index,
+            allocater: Box::new(allocater),
+            allocator_index: Box::new(allocator_index),
+            index_mode: IndexMode::default(),
+            destroyed: Vec::new(),
+            _phantom: std::marker::PhantomData,
+        };
+
+        (buffer, handles)
+    }
+}
+
 impl<T, L, I> IndexedBuffer<T, L, I>
 where
     T: bytemuck::Pod + bytemuck::Zeroable,
     L: alloc::BufferAlloc<T> + Default,
     I: alloc::BufferAlloc<T> + Default,
 {
-    pub fn write<const DS: usize, const IS: usize>(
+    /// Writes the vertices and indices to the start of the allocation `id`.
+    ///
+    /// Both must fit into their allocations, otherwise [`RetherError::ExceedsAllocation`]
+    /// is returned.
+    pub fn write(
         &self,
         id: &str,
         buffer_data: IndexedBufferData<'_, T>,
         queue: &wgpu::Queue,
-    ) where
+    ) -> Result<(), RetherError>
+    where
         T: bytemuck::Pod + bytemuck::Zeroable,
     {
-        if let Some(allocation) = self.allocater.get(id) {
-            self.inner
-                .write(queue, allocation.offset(), buffer_data.data);
+        let allocation = self
+            .allocater
+            .get(id)
+            .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?;
+        let index_allocation = self
+            .allocator_index
+            .get(id)
+            .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?;
+
+        if buffer_data.data.len() > allocation.size() {
+            return Err(RetherError::ExceedsAllocation {
+                id: id.to_string(),
+                size: buffer_data.data.len(),
+                allocation_size: allocation.size(),
+            });
         }
 
-        if let Some(allocation) = self.allocator_index.get(id) {
-            self.index
-                .write(queue, allocation.offset(), buffer_data.indices);
+        if buffer_data.indices.len() > index_allocation.size() {
+            return Err(RetherError::ExceedsAllocation {
+                id: id.to_string(),
+                size: buffer_data.indices.len(),
+                allocation_size: index_allocation.size(),
+            });
         }
+
+        self.inner
+            .write(queue, allocation.offset(), buffer_data.data);
+        self.index
+            .write(queue, index_allocation.offset(), buffer_data.indices);
+
+        Ok(())
+    }
+
+    /// Writes only the indices of the allocation `id`, the vertices stay untouched, e.g.
+    /// after re-triangulating a mesh.
+    ///
+    /// The indices must fit into the index allocation, see [`Self::resize_indices`].
+    pub fn write_indices(
+        &self,
+        id: &str,
+        indices: &[u32],
+        queue: &wgpu::Queue,
+    ) -> Result<(), RetherError> {
+        let index_allocation = self
+            .allocator_index
+            .get(id)
+            .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?;
+
+        if indices.len() > index_allocation.size() {
+            return Err(RetherError::ExceedsAllocation {
+                id: id.to_string(),
+                size: indices.len(),
+                allocation_size: index_allocation.size(),
+            });
+        }
+
+        self.index.write(queue, index_allocation.offset(), indices);
+
+        Ok(())
+    }
+
+    /// Like [`Self::write`], but identical vertices are merged first, see
+    /// [`crate::model::geometry::weld`].
+    ///
+    /// The indices left over in the allocation are filled with degenerate triangles, so
+    /// the stale indices of earlier writes aren't drawn anymore. With [`IndexMode::Global`]
+    /// the indices are absolute like the ones of [`Self::write`], either way they have to
+    /// point into the written vertices.
+    pub fn write_welded(
+        &self,
+        id: &str,
+        buffer_data: IndexedBufferData<'_, T>,
+        queue: &wgpu::Queue,
+    ) -> Result<(), RetherError> {
+        let base = match self.index_mode {
+            IndexMode::Global => {
+                self.allocater
+                    .allocation(id)
+                    .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?
+                    .offset as u32
+            }
+            IndexMode::Local => 0,
+        };
+
+        // welding works on indices relative to the written vertices
+        let local = buffer_data
+            .indices
+            .iter()
+            .map(|index| {
+                index
+                    .checked_sub(base)
+                    .filter(|index| (*index as usize) < buffer_data.data.len())
+            })
+            .collect::<Option<Vec<u32>>>()
+            .ok_or(RetherError::InvalidState(
+                "Welded indices have to point into the written vertices",
+            ))?;
+
+        let (vertices, mut indices) = weld(buffer_data.data, &local);
+
+        for index in indices.iter_mut() {
+            *index += base;
+        }
+
+        if let Some(index_allocation) = self.allocator_index.get(id) {
+            let padding = indices.first().copied().unwrap_or(0);
+
+            indices.resize(index_allocation.size().max(indices.len()), padding);
+        }
+
+        self.write(id, IndexedBufferData::create(&vertices, &indices), queue)
+    }
+
+    /// Reads back the indices of the allocation `id`.
+    pub fn read_indices(
+        &self,
+        id: &str,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<Vec<u32>, RetherError> {
+        let allocation = self
+            .allocator_index
+            .allocation(id)
+            .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?;
+
+        self.index
+            .read::<u32>(allocation.offset, allocation.size, device, queue)
     }
 
     pub fn get(&self, id: &str) -> Option<&Arc<L::Handle>> {
         self.allocater.get(id)
     }
+
+    /// Blocks until all writes issued so far reached the gpu, see [`Buffer::sync`].
+    pub fn sync(&self, device: &Device, queue: &Queue) {
+        sync(device, queue);
+    }
 }
 
 impl<T, L, I> IndexedBuffer<T, L, I>
@@ -229,61 +1460,164 @@ where
     L: alloc::BufferDynamicAlloc<T>,
     I: alloc::BufferDynamicAlloc<T>,
 {
+    /// Allocates `DS` vertices and `IS` indices.
+    ///
+    /// Returns the handles of the vertices and of the indices, as taken by
+    /// [`crate::model::IndexedModel::wake`].
     pub fn allocate<const DS: usize, const IS: usize>(
         &mut self,
         id: &str,
         device: &Device,
         queue: &Queue,
-    ) where
+    ) -> Result<IndexedHandles<T>, RetherError>
+    where
         T: bytemuck::Pod + bytemuck::Zeroable,
     {
-        self.allocater.allocate(id, DS);
-        self.allocator_index.allocate(id, IS);
+        // reserve both first, so a failure doesn't leave only one of them allocated
+        self.inner.reserve(DS, device, queue)?;
+        self.index.reserve(IS, device, queue)?;
+
+        self.inner.allocate(DS, device, queue)?;
+        self.index.allocate(IS, device, queue)?;
 
-        self.inner.allocate::<T>(DS, device, queue);
-        self.index.allocate::<u32>(IS, device, queue);
+        Ok((
+            self.allocater.allocate(id, DS),
+            self.allocator_index.allocate(id, IS),
+        ))
     }
 
+    /// Allocates and uploads the data, returns the handles like [`Self::allocate`].
     pub fn allocate_init(
         &mut self,
         id: &str,
         buffer_data: IndexedBufferData<'_, T>,
         device: &Device,
         queue: &Queue,
-    ) where
+    ) -> Result<IndexedHandles<T>, RetherError>
+    where
         T: bytemuck::Pod + bytemuck::Zeroable,
     {
-        self.allocater.allocate(id, buffer_data.data.len());
-        self.allocator_index.allocate(id, buffer_data.indices.len());
+        // reserve both first, so a failure doesn't leave only one of them allocated
+        self.inner.reserve(buffer_data.data.len(), device, queue)?;
+        self.index
+            .reserve(buffer_data.indices.len(), device, queue)?;
+
+        self.inner.append(buffer_data.data, device, queue)?;
+        self.index.append(buffer_data.indices, device, queue)?;
 
-        self.inner.append(buffer_data.data, device, queue);
-        self.index.append(buffer_data.data, device, queue);
+        Ok((
+            self.allocater.allocate(id, buffer_data.data.len()),
+            self.allocator_index.allocate(id, buffer_data.indices.len()),
+        ))
     }
 
-    pub fn free(&mut self, id: &str, device: &Device, queue: &Queue) {
-        if let Some(allocation) = self.allocater.free(id) {
-            self.inner
-                .free::<T>(allocation.offset, allocation.size, device, queue);
-        }
+    /// Frees the vertices and the indices of `id`, see [`Buffer::free`].
+    ///
+    /// Fails without freeing anything if either allocation doesn't lie inside its buffer.
+    pub fn free(&mut self, id: &str, device: &Device, queue: &Queue) -> Result<(), RetherError> {
+        let ids = [id.to_string()];
+        let ranges = allocation_ranges::<T, _>(&*self.allocater, &ids);
+        let index_ranges = allocation_ranges::<T, _>(&*self.allocator_index, &ids);
 
-        if let Some(allocation) = self.allocator_index.free(id) {
-            self.index
-                .free::<u32>(allocation.offset, allocation.size, device, queue);
-        }
+        self.inner.check_ranges(&ranges)?;
+        self.index.check_ranges(&index_ranges)?;
+
+        self.allocater.free(id);
+        self.allocator_index.free(id);
+
+        self.inner.free_ranges(&ranges, device, queue)?;
+        self.index.free_ranges(&index_ranges, device, queue)?;
+
+        Ok(())
     }
 
-    pub fn update(&mut self, device: &Device, queue: &Queue) {
-        self.allocater
-            .update(|mod_action| self.inner.modify(mod_action, device, queue));
+    /// Applies all queued actions and frees destroyed handles.
+    ///
+    /// Returns the allocations that were modified or freed. If reading back an allocation
+    /// fails, the remaining actions and frees are still applied and the first error is
+    /// returned. Destroyed handles that couldn't be freed stay pending for the next call.
+    pub fn update(&mut self, device: &Device, queue: &Queue) -> Result<BufferUpdate, RetherError> {
+        let mut changes = BufferUpdate::default();
+        let mut error = None;
+
+        self.allocater.update(|mod_action| {
+            if let Some(id) = mod_action.id() {
+                if !changes.modified.contains(id) {
+                    changes.modified.push(id.clone());
+                }
+            }
+
+            if let Err(err) = self.inner.modify(mod_action, device, queue) {
+                error.get_or_insert(err);
+            }
+        });
 
-        let mut pending_destroyed_handles = self.allocater.get_destroyed_handles();
+        let mut pending_destroyed_handles = std::mem::take(&mut self.destroyed);
+        pending_destroyed_handles.extend(self.allocater.get_destroyed_handles());
 
         self.allocator_index.update(|_| {});
 
         pending_destroyed_handles.extend(self.allocator_index.get_destroyed_handles());
 
         for id in pending_destroyed_handles {
-            self.free(&id, device, queue);
+            if self.allocater.get(&id).is_some() || self.allocator_index.get(&id).is_some() {
+                match self.free(&id, device, queue) {
+                    Ok(()) => changes.freed.push(id),
+                    Err(err) => {
+                        // nothing was freed, retry with the next update
+                        if !self.destroyed.contains(&id) {
+                            self.destroyed.push(id);
+                        }
+
+                        error.get_or_insert(err);
+                    }
+                }
+            }
+        }
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(changes),
         }
     }
 }
+
+impl<T, L, I> IndexedBuffer<T, L, I>
+where
+    T: bytemuck::Pod + bytemuck::Zeroable,
+    L: alloc::BufferDynamicAlloc<T>,
+    I: alloc::BufferResizeAlloc<T>,
+{
+    /// Resizes only the index allocation of `id` to `len` indices, the vertex allocation
+    /// and the handles stay as they are.
+    ///
+    /// Indices up to the smaller length are kept, added ones are zeroed until they are
+    /// written with [`Self::write_indices`].
+    pub fn resize_indices(
+        &mut self,
+        id: &str,
+        len: usize,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<(), RetherError> {
+        let index_allocation = self
+            .allocator_index
+            .get(id)
+            .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?;
+
+        let (offset, size) = (index_allocation.offset(), index_allocation.size());
+
+        match len.cmp(&size) {
+            std::cmp::Ordering::Less => self.index.free(offset + len, size - len, device, queue)?,
+            std::cmp::Ordering::Greater => {
+                self.index
+                    .insert(offset + size, len - size, device, queue)?
+            }
+            std::cmp::Ordering::Equal => return Ok(()),
+        }
+
+        self.allocator_index.resize(id, len);
+
+        Ok(())
+    }
+}
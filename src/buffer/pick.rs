@@ -0,0 +1,234 @@
+use crate::{texture::Texture, vertex::Vertex, RetherError};
+
+use super::{
+    alloc::{self, AllocHandle, BufferAllocationID},
+    raw::read_buffer,
+    Buffer,
+};
+
+// ids start at one, zero is the cleared background
+const BACKGROUND: u32 = 0;
+
+/// Picks allocations by rendering their ids into an offscreen texture and reading back
+/// the pixel under the cursor.
+///
+/// Matches exactly what is drawn, unlike raycasting against hitboxes, at the cost of a
+/// render pass and a gpu readback per pick.
+#[derive(Debug)]
+pub struct IdPicker {
+    pipeline: wgpu::RenderPipeline,
+    id_texture: wgpu::Texture,
+    depth_view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+}
+
+impl IdPicker {
+    /// Creates a picker with targets of the given size.
+    ///
+    /// `camera_layout` is the layout of the camera bind group used by the main shader,
+    /// it's bound at group 0 when picking.
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("pick.wgsl"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pick Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Pick Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::R32Uint,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let (id_texture, depth_view) = Self::create_targets(device, width, height);
+
+        Self {
+            pipeline,
+            id_texture,
+            depth_view,
+            width,
+            height,
+        }
+    }
+
+    /// Recreates the targets, should follow the size of the surface.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        let (id_texture, depth_view) = Self::create_targets(device, width, height);
+
+        self.id_texture = id_texture;
+        self.depth_view = depth_view;
+        self.width = width;
+        self.height = height;
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let id_texture = Texture::create_id_texture(device, width, height, "Pick Id Texture");
+
+        let depth_view = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Pick Depth Texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Depth32Float,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        (id_texture, depth_view)
+    }
+}
+
+impl<L: alloc::BufferAlloc<Vertex>> Buffer<Vertex, L> {
+    /// Returns the allocation drawn at pixel `(x, y)`, None if nothing was drawn there
+    /// or the pixel lies outside the picker.
+    pub fn pick_id(
+        &self,
+        picker: &IdPicker,
+        camera: &wgpu::BindGroup,
+        x: u32,
+        y: u32,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Option<BufferAllocationID>, RetherError> {
+        if x >= picker.width || y >= picker.height {
+            return Ok(None);
+        }
+
+        let handles = self.allocater.handles().collect::<Vec<_>>();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Pick Encoder"),
+        });
+
+        {
+            let id_view = picker
+                .id_texture
+                .create_view(&wgpu::TextureViewDescriptor::default());
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Pick Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &id_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &picker.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(&picker.pipeline);
+            render_pass.set_bind_group(0, camera, &[]);
+            render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
+
+            for (index, handle) in handles.iter().enumerate() {
+                let id = index as u32 + 1;
+                let start = handle.offset() as u32;
+
+                render_pass.draw(start..start + handle.size() as u32, id..id + 1);
+            }
+        }
+
+        let pixel_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Pixel Buffer"),
+            size: std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &picker.id_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &pixel_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let pixel = read_buffer::<u32>(
+            &self.inner.sublabel("pick readback"),
+            &pixel_buffer,
+            0,
+            std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            device,
+            queue,
+        )?;
+
+        Ok(match pixel[0] {
+            BACKGROUND => None,
+            id => handles
+                .get(id as usize - 1)
+                .map(|handle| handle.id().clone()),
+        })
+    }
+}
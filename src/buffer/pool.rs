@@ -0,0 +1,179 @@
+use std::{collections::HashMap, sync::Arc};
+
+use wgpu::{Device, Queue};
+
+use crate::{model::VisibilityModel, RetherError};
+
+use super::{
+    alloc::{BufferAllocationID, BufferDynamicAllocator, DynamicAllocHandle},
+    Buffer, BufferData, BufferUpdate,
+};
+
+type PoolBuffer<T> = Buffer<T, BufferDynamicAllocator<T>>;
+
+/// Spreads allocations over several [`Buffer`]s, a new buffer is created once an
+/// allocation doesn't fit into any existing one.
+///
+/// Buffers are limited by the `max_buffer_size` of the device, or a smaller size set with
+/// [`Self::with_max_elements`]. Allocations are addressed by id like in a single buffer.
+#[derive(Debug)]
+pub struct BufferPool<T> {
+    label: String,
+    buffers: Vec<PoolBuffer<T>>,
+    // index of the buffer every allocation lives in
+    locations: HashMap<BufferAllocationID, usize>,
+    max_elements: Option<usize>,
+}
+
+impl<T: bytemuck::Pod + bytemuck::Zeroable + 'static> BufferPool<T> {
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            buffers: Vec::new(),
+            locations: HashMap::new(),
+            max_elements: None,
+        }
+    }
+
+    /// Limits every buffer to `max_elements`, the device limit still applies.
+    ///
+    /// A larger allocation gets a new buffer of its own.
+    pub fn with_max_elements(mut self, max_elements: usize) -> Self {
+        self.max_elements = Some(max_elements);
+        self
+    }
+
+    pub fn allocate_init(
+        &mut self,
+        id: &str,
+        buffer_data: BufferData<'_, T>,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<Arc<DynamicAllocHandle<T>>, RetherError> {
+        // replacing an allocation could leave the old one unreachable in another buffer
+        if self.locations.contains_key(id) {
+            return Err(RetherError::InvalidState("Allocation id already in use"));
+        }
+
+        let max_elements = self.max_elements(device);
+        let size = buffer_data.data.len();
+
+        let (index, handle) = match self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.inner.size as usize + size <= max_elements)
+        {
+            Some(index) => (
+                index,
+                self.buffers[index].allocate_init(id, buffer_data, device, queue)?,
+            ),
+            None => {
+                // only kept once the allocation succeeded, a failure leaves no empty buffer
+                let label = format!("{} {}", self.label, self.buffers.len());
+                let mut buffer = Buffer::new(&label, device);
+
+                let handle = buffer.allocate_init(id, buffer_data, device, queue)?;
+                self.buffers.push(buffer);
+
+                (self.buffers.len() - 1, handle)
+            }
+        };
+
+        self.locations.insert(id.to_string(), index);
+
+        Ok(handle)
+    }
+
+    pub fn write(
+        &self,
+        id: &str,
+        buffer_data: BufferData<'_, T>,
+        queue: &Queue,
+    ) -> Result<(), RetherError> {
+        self.buffer_of(id)
+            .ok_or_else(|| RetherError::UnknownAllocation(id.to_string()))?
+            .write(id, buffer_data, queue)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Arc<DynamicAllocHandle<T>>> {
+        self.buffer_of(id)?.get(id)
+    }
+
+    pub fn free(&mut self, id: &str, device: &Device, queue: &Queue) -> Result<(), RetherError> {
+        match self.locations.remove(id) {
+            Some(index) => self.buffers[index].free(id, device, queue),
+            None => Ok(()),
+        }
+    }
+
+    /// Updates all buffers, the changes of all buffers are combined.
+    ///
+    /// A failing buffer doesn't stop the others from updating, the first error is returned.
+    pub fn update(&mut self, device: &Device, queue: &Queue) -> Result<BufferUpdate, RetherError> {
+        let mut changes = BufferUpdate::default();
+        let mut error = None;
+
+        for buffer in self.buffers.iter_mut() {
+            match buffer.update(device, queue) {
+                Ok(update) => {
+                    changes.modified.extend(update.modified);
+                    changes.freed.extend(update.freed);
+                }
+                Err(err) => {
+                    error.get_or_insert(err);
+                }
+            }
+        }
+
+        // forget freed ids even on error, their buffers no longer know them
+        self.locations
+            .retain(|id, index| self.buffers[*index].get(id).is_some());
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(changes),
+        }
+    }
+
+    pub fn render<'a, 'b: 'a>(&'b self, render_pass: &'a mut wgpu::RenderPass<'b>) {
+        for buffer in self.buffers.iter() {
+            buffer.render(render_pass);
+        }
+    }
+
+    /// Renders the visible parts of `model` woken with the allocation `id`, parts lying in
+    /// other allocations of the pool are drawn from their buffers.
+    ///
+    /// Parts in allocations the pool doesn't know are skipped.
+    pub fn render_visible<'a, 'b: 'a>(
+        &'b self,
+        id: &str,
+        model: &impl VisibilityModel,
+        render_pass: &'a mut wgpu::RenderPass<'b>,
+    ) {
+        for (allocation_id, locations) in model.visible_allocations() {
+            let allocation_id = allocation_id.as_deref().unwrap_or(id);
+
+            if let Some(buffer) = self.buffer_of(allocation_id) {
+                buffer.render_locations(allocation_id, &locations, render_pass);
+            }
+        }
+    }
+
+    /// The buffer holding the allocation `id`.
+    pub fn buffer_of(&self, id: &str) -> Option<&PoolBuffer<T>> {
+        self.locations.get(id).map(|index| &self.buffers[*index])
+    }
+
+    pub fn buffers(&self) -> &[PoolBuffer<T>] {
+        &self.buffers
+    }
+
+    fn max_elements(&self, device: &Device) -> usize {
+        let stride = std::mem::size_of::<T>() as u64;
+        let device_max = (device.limits().max_buffer_size / stride) as usize;
+
+        self.max_elements
+            .map_or(device_max, |max| max.min(device_max))
+    }
+}
@@ -1,4 +1,8 @@
-use super::alloc::ModifyAction;
+use super::{
+    alloc::ModifyAction,
+    growth::{Exact, GrowthStrategy},
+};
+use crate::RetherError;
 use wgpu::{BufferAddress, BufferDescriptor};
 
 #[derive(Debug)]
@@ -7,8 +11,12 @@ pub struct RawBuffer {
     pub render_range: std::ops::Range<u32>,
 
     usage: wgpu::BufferUsages,
+    growth: Box<dyn GrowthStrategy>,
 
     pub size: BufferAddress,
+    pub capacity: BufferAddress,
+    // size of a single element in bytes, sizes and offsets are counted in elements
+    stride: BufferAddress,
     label: String,
 }
 
@@ -34,180 +42,754 @@ impl RawBuffer {
             render_range: 0..size as u32,
 
             usage,
+            growth: Box::new(Exact),
 
             size: size as BufferAddress,
+            capacity: size as BufferAddress,
+            stride: std::mem::size_of::<T>() as BufferAddress,
             label: label.to_string(),
         }
     }
 
-    pub fn allocate<T>(&mut self, size: usize, device: &wgpu::Device, queue: &wgpu::Queue)
+    /// Creates a buffer holding `data`, written through a mapping at creation.
+    ///
+    /// Skips the staging copy of `queue.write_buffer`, the fastest path for one-shot uploads.
+    pub fn new_init<T>(
+        data: &[T],
+        label: &str,
+        usage: wgpu::BufferUsages,
+        device: &wgpu::Device,
+    ) -> Self
     where
         T: bytemuck::Pod + bytemuck::Zeroable,
     {
-        let old_bytes = self.size * std::mem::size_of::<T>() as BufferAddress;
+        if data.is_empty() {
+            return Self::new::<T>(0, label, usage, device);
+        }
 
-        let buffer = device.create_buffer(&BufferDescriptor {
-            label: Some(&self.label),
-            size: old_bytes + (size * std::mem::size_of::<T>()) as BufferAddress,
-            usage: self.usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
-            mapped_at_creation: false,
+        let bytes = bytemuck::cast_slice::<T, u8>(data);
+
+        // mapped buffers have to be a multiple of the copy alignment
+        let inner = device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size: (bytes.len() as BufferAddress).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            usage: usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: true,
         });
 
+        inner.slice(..).get_mapped_range_mut()[..bytes.len()].copy_from_slice(bytes);
+        inner.unmap();
+
+        Self {
+            inner,
+            render_range: 0..data.len() as u32,
+
+            usage,
+            growth: Box::new(Exact),
+
+            size: data.len() as BufferAddress,
+            capacity: data.len() as BufferAddress,
+            stride: std::mem::size_of::<T>() as BufferAddress,
+            label: label.to_string(),
+        }
+    }
+
+    pub fn set_growth_strategy(&mut self, growth: impl GrowthStrategy + 'static) {
+        self.growth = Box::new(growth);
+    }
+
+    pub fn stride(&self) -> BufferAddress {
+        self.stride
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The usages the buffer was created with, without the copy usages every buffer has.
+    pub fn usage(&self) -> wgpu::BufferUsages {
+        self.usage
+    }
+
+    /// The label of a resource created for this buffer, e.g. `terrain:readback`.
+    pub fn sublabel(&self, purpose: &str) -> String {
+        format!("{}:{}", self.label, purpose)
+    }
+
+    fn grow(
+        &mut self,
+        needed: usize,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(), RetherError> {
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Buffer Copy Encoder"),
+            label: Some(&self.sublabel("grow")),
         });
-        encoder.copy_buffer_to_buffer(&self.inner, 0, &buffer, 0, old_bytes);
+
+        self.grow_with_encoder(needed, device, &mut encoder)?;
 
         queue.submit(std::iter::once(encoder.finish()));
 
-        self.inner.destroy();
+        Ok(())
+    }
 
+    fn grow_with_encoder(
+        &mut self,
+        needed: usize,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), RetherError> {
+        let limit = device.limits().max_buffer_size;
+
+        let size = (needed as BufferAddress).saturating_mul(self.stride);
+
+        if size > limit {
+            return Err(RetherError::ExceedsDeviceLimit { size, limit });
+        }
+
+        // the growth strategy may overshoot, but never past what the device supports
+        let capacity = self
+            .growth
+            .next_capacity(self.capacity as usize, needed)
+            .max(needed)
+            .min((limit / self.stride) as usize);
+
+        let used_bytes = self.size * self.stride;
+
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some(&self.label),
+            size: capacity as BufferAddress * self.stride,
+            usage: self.usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_buffer_to_buffer(&self.inner, 0, &buffer, 0, used_bytes);
+
+        // dropping the old buffer instead of destroying it lets wgpu free it
+        // once the queued copy has finished reading from it
         self.inner = buffer;
+        self.capacity = capacity as BufferAddress;
+
+        Ok(())
+    }
+
+    /// Makes sure `additional` elements fit without another grow.
+    pub fn reserve(
+        &mut self,
+        additional: usize,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(), RetherError> {
+        let needed = self.size as usize + additional;
+
+        if needed > self.capacity as usize {
+            self.grow(needed, device, queue)?;
+        }
+
+        Ok(())
+    }
+
+    /// Like [`Self::reserve`], but a grow is recorded into `encoder`.
+    pub fn reserve_with_encoder(
+        &mut self,
+        additional: usize,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), RetherError> {
+        let needed = self.size as usize + additional;
+
+        if needed > self.capacity as usize {
+            self.grow_with_encoder(needed, device, encoder)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn allocate(
+        &mut self,
+        size: usize,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(), RetherError> {
+        self.reserve(size, device, queue)?;
 
         self.size += size as BufferAddress;
         self.render_range = 0..self.size as u32;
+
+        Ok(())
     }
 
-    pub fn append<T>(&mut self, data: &[T], device: &wgpu::Device, queue: &wgpu::Queue)
+    /// Writes `data` behind the used part of the buffer.
+    ///
+    /// As long as the capacity suffices this is a single `write_buffer` at the tail, the
+    /// buffer is only reallocated and copied when it has to grow.
+    pub fn append<T>(
+        &mut self,
+        data: &[T],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(), RetherError>
     where
         T: bytemuck::Pod + bytemuck::Zeroable,
     {
-        let old_bytes = self.size * std::mem::size_of::<T>() as BufferAddress;
+        debug_assert_eq!(std::mem::size_of::<T>() as BufferAddress, self.stride);
 
-        let buffer = device.create_buffer(&BufferDescriptor {
-            label: Some(&self.label),
-            size: old_bytes + std::mem::size_of_val(data) as BufferAddress,
-            usage: self.usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
-            mapped_at_creation: false,
+        self.reserve(data.len(), device, queue)?;
+
+        let old_bytes = self.size * self.stride;
+
+        queue.write_buffer(&self.inner, old_bytes, bytemuck::cast_slice(data));
+
+        self.size += data.len() as BufferAddress;
+        self.render_range = 0..self.size as u32;
+
+        Ok(())
+    }
+
+    /// Like [`Self::append`], but a grow is recorded into `encoder`.
+    ///
+    /// The data itself still goes through the queue, it lands behind the part copied by
+    /// the grow.
+    pub fn append_with_encoder<T>(
+        &mut self,
+        data: &[T],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), RetherError>
+    where
+        T: bytemuck::Pod + bytemuck::Zeroable,
+    {
+        debug_assert_eq!(std::mem::size_of::<T>() as BufferAddress, self.stride);
+
+        self.reserve_with_encoder(data.len(), device, encoder)?;
+
+        let old_bytes = self.size * self.stride;
+
+        queue.write_buffer(&self.inner, old_bytes, bytemuck::cast_slice(data));
+
+        self.size += data.len() as BufferAddress;
+        self.render_range = 0..self.size as u32;
+
+        Ok(())
+    }
+
+    /// Removes `size` elements at `offset` and moves the following elements left.
+    ///
+    /// Fails with [`RetherError::OutOfBounds`] if the range reaches past the end of the
+    /// buffer, which would otherwise underflow the size of the tail copy.
+    pub fn free(
+        &mut self,
+        offset: usize,
+        size: usize,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(), RetherError> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&self.sublabel("free")),
         });
 
+        self.free_with_encoder(offset, size, device, &mut encoder)?;
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(())
+    }
+
+    /// Like [`Self::free`], but the copies are recorded into `encoder`.
+    pub fn free_with_encoder(
+        &mut self,
+        offset: usize,
+        size: usize,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), RetherError> {
+        self.free_ranges_with_encoder(&[(offset, size)], device, encoder)
+    }
+
+    /// Fails like [`Self::free_ranges`] would, without changing anything, e.g. to validate
+    /// the ranges before the allocator forgets them.
+    pub fn check_ranges(&self, ranges: &[(usize, usize)]) -> Result<(), RetherError> {
+        if let Some((offset, size)) = ranges.iter().copied().find(|(offset, size)| {
+            offset
+                .checked_add(*size)
+                .is_none_or(|end| end > self.size as usize)
+        }) {
+            return Err(RetherError::OutOfBounds {
+                offset,
+                size,
+                buffer_size: self.size as usize,
+            });
+        }
+
+        if ranges
+            .windows(2)
+            .any(|pair| pair[0].0 + pair[0].1 > pair[1].0)
+        {
+            return Err(RetherError::InvalidState(
+                "Freed ranges must be sorted and disjoint",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Removes all `(offset, size)` ranges and moves the remaining elements together, the
+    /// buffer is copied once however many ranges are removed.
+    ///
+    /// The ranges have to be sorted by offset and must not overlap.
+    pub fn free_ranges(
+        &mut self,
+        ranges: &[(usize, usize)],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(), RetherError> {
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Buffer Copy Encoder"),
+            label: Some(&self.sublabel("free")),
         });
-        encoder.copy_buffer_to_buffer(&self.inner, 0, &buffer, 0, old_bytes);
+
+        self.free_ranges_with_encoder(ranges, device, &mut encoder)?;
 
         queue.submit(std::iter::once(encoder.finish()));
 
-        queue.write_buffer(&buffer, old_bytes, bytemuck::cast_slice(data));
+        Ok(())
+    }
+
+    /// Like [`Self::free_ranges`], but the copies are recorded into `encoder`.
+    pub fn free_ranges_with_encoder(
+        &mut self,
+        ranges: &[(usize, usize)],
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), RetherError> {
+        self.check_ranges(ranges)?;
+
+        let remaining = self.size as usize - ranges.iter().map(|(_, size)| size).sum::<usize>();
 
-        self.inner.destroy();
+        let capacity = self
+            .growth
+            .next_capacity(self.capacity as usize, remaining)
+            .max(remaining);
 
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some(&self.label),
+            size: capacity as BufferAddress * self.stride,
+            usage: self.usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        // copies the kept part in front of every range, the end of the buffer closes the last one
+        let mut kept = 0;
+        let mut dest = 0;
+
+        for (offset, size) in ranges
+            .iter()
+            .copied()
+            .chain(std::iter::once((self.size as usize, 0)))
+        {
+            let len = offset - kept;
+
+            encoder.copy_buffer_to_buffer(
+                &self.inner,
+                kept as BufferAddress * self.stride,
+                &buffer,
+                dest as BufferAddress * self.stride,
+                len as BufferAddress * self.stride,
+            );
+
+            dest += len;
+            kept = offset + size;
+        }
+
+        // dropping the old buffer instead of destroying it lets wgpu free it
+        // once the queued copy has finished reading from it
         self.inner = buffer;
 
-        self.size += data.len() as BufferAddress;
+        self.size = remaining as BufferAddress;
+        self.capacity = capacity as BufferAddress;
         self.render_range = 0..self.size as u32;
+
+        Ok(())
     }
 
-    pub fn free<T>(
+    /// Inserts `size` zeroed elements at `offset` and moves the following elements right,
+    /// the counterpart of [`Self::free`].
+    ///
+    /// Only the elements behind `offset` are moved while the capacity suffices, otherwise
+    /// the buffer grows and the data is copied around the gap.
+    pub fn insert(
         &mut self,
         offset: usize,
         size: usize,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-    ) where
-        T: bytemuck::Pod + bytemuck::Zeroable,
-    {
-        let old_bytes = self.size * std::mem::size_of::<T>() as BufferAddress;
+    ) -> Result<(), RetherError> {
+        if offset > self.size as usize {
+            return Err(RetherError::OutOfBounds {
+                offset,
+                size,
+                buffer_size: self.size as usize,
+            });
+        }
+
+        let limit = device.limits().max_buffer_size;
+        let needed = self.size as usize + size;
+
+        let bytes = (needed as BufferAddress).saturating_mul(self.stride);
+
+        if bytes > limit {
+            return Err(RetherError::ExceedsDeviceLimit { size: bytes, limit });
+        }
+
+        if needed <= self.capacity as usize {
+            self.insert_in_place(offset, size, device, queue);
+
+            return Ok(());
+        }
 
+        let capacity = self
+            .growth
+            .next_capacity(self.capacity as usize, needed)
+            .max(needed)
+            .min((limit / self.stride) as usize);
+
+        // new buffers are zeroed, so only the data around the gap is copied
         let buffer = device.create_buffer(&BufferDescriptor {
             label: Some(&self.label),
-            size: old_bytes - (size * std::mem::size_of::<T>()) as BufferAddress,
+            size: capacity as BufferAddress * self.stride,
             usage: self.usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
             mapped_at_creation: false,
         });
 
-        let byte_offset = offset * std::mem::size_of::<T>();
-        let byte_size_to_free = size * std::mem::size_of::<T>();
+        let byte_offset = offset as BufferAddress * self.stride;
+        let byte_size = size as BufferAddress * self.stride;
 
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Buffer Copy Encoder"),
+            label: Some(&self.sublabel("insert")),
         });
 
-        encoder.copy_buffer_to_buffer(&self.inner, 0, &buffer, 0, byte_offset as BufferAddress);
+        encoder.copy_buffer_to_buffer(&self.inner, 0, &buffer, 0, byte_offset);
 
         encoder.copy_buffer_to_buffer(
             &self.inner,
-            (byte_offset + byte_size_to_free) as BufferAddress,
+            byte_offset,
             &buffer,
-            byte_offset as BufferAddress,
-            old_bytes - (byte_offset + byte_size_to_free) as BufferAddress,
+            byte_offset + byte_size,
+            self.size * self.stride - byte_offset,
         );
 
         queue.submit(std::iter::once(encoder.finish()));
 
-        self.inner.destroy();
-
         self.inner = buffer;
 
-        self.size -= size as BufferAddress;
+        self.size += size as BufferAddress;
+        self.capacity = capacity as BufferAddress;
         self.render_range = 0..self.size as u32;
+
+        Ok(())
+    }
+
+    // a buffer can't be copied onto itself, so the tail takes a detour through a
+    // buffer of its own size instead of reallocating the whole buffer
+    fn insert_in_place(
+        &mut self,
+        offset: usize,
+        size: usize,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let byte_offset = offset as BufferAddress * self.stride;
+        let byte_size = size as BufferAddress * self.stride;
+        let tail_bytes = self.size * self.stride - byte_offset;
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&self.sublabel("insert")),
+        });
+
+        if tail_bytes > 0 {
+            let tail = device.create_buffer(&BufferDescriptor {
+                label: Some(&self.sublabel("insert tail")),
+                size: tail_bytes,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+
+            encoder.copy_buffer_to_buffer(&self.inner, byte_offset, &tail, 0, tail_bytes);
+            encoder.copy_buffer_to_buffer(
+                &tail,
+                0,
+                &self.inner,
+                byte_offset + byte_size,
+                tail_bytes,
+            );
+        }
+
+        // the gap may hold stale elements, inserted elements are zeroed like in a new buffer
+        encoder.clear_buffer(&self.inner, byte_offset, Some(byte_size));
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.size += size as BufferAddress;
+        self.render_range = 0..self.size as u32;
+    }
+
+    /// Copies `size` elements starting at `offset` into `dest` at `dest_offset`.
+    pub fn copy_to(
+        &self,
+        offset: usize,
+        dest: &RawBuffer,
+        dest_offset: usize,
+        size: usize,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        debug_assert_eq!(self.stride, dest.stride);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&self.sublabel("copy")),
+        });
+
+        encoder.copy_buffer_to_buffer(
+            &self.inner,
+            offset as BufferAddress * self.stride,
+            &dest.inner,
+            dest_offset as BufferAddress * dest.stride,
+            size as BufferAddress * self.stride,
+        );
+
+        queue.submit(std::iter::once(encoder.finish()));
     }
 
     pub fn write<T>(&self, queue: &wgpu::Queue, offset: usize, data: &[T])
     where
         T: bytemuck::Pod + bytemuck::Zeroable,
     {
-        let offset_bytes = offset * std::mem::size_of::<T>();
+        debug_assert_eq!(std::mem::size_of::<T>() as BufferAddress, self.stride);
+
+        let offset_bytes = offset * self.stride as usize;
 
         queue.write_buffer(&self.inner, offset_bytes as u64, bytemuck::cast_slice(data));
     }
 
+    pub fn read<T>(
+        &self,
+        offset: usize,
+        size: usize,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Vec<T>, RetherError>
+    where
+        T: bytemuck::Pod + bytemuck::Zeroable,
+    {
+        debug_assert_eq!(std::mem::size_of::<T>() as BufferAddress, self.stride);
+
+        let offset_bytes = offset * self.stride as usize;
+        let size_bytes = size * self.stride as usize;
+
+        read_buffer(
+            &self.sublabel("readback"),
+            &self.inner,
+            offset_bytes as BufferAddress,
+            size_bytes as BufferAddress,
+            device,
+            queue,
+        )
+    }
+
+    /// Reads the element ranges `(offset, size)` back with a single mapping, the data of
+    /// all ranges is returned back to back.
+    pub fn read_ranges<T>(
+        &self,
+        ranges: &[(usize, usize)],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Vec<T>, RetherError>
+    where
+        T: bytemuck::Pod + bytemuck::Zeroable,
+    {
+        debug_assert_eq!(std::mem::size_of::<T>() as BufferAddress, self.stride);
+
+        let ranges = ranges
+            .iter()
+            .map(|(offset, size)| {
+                (
+                    *offset as BufferAddress * self.stride,
+                    *size as BufferAddress * self.stride,
+                )
+            })
+            .collect::<Vec<(BufferAddress, BufferAddress)>>();
+
+        read_buffer_ranges(
+            &self.sublabel("readback"),
+            &self.inner,
+            &ranges,
+            device,
+            queue,
+        )
+    }
+
     pub fn modify<T>(
         &self,
         mut modify_action: ModifyAction<T>,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-    ) where
+    ) -> Result<(), RetherError>
+    where
         T: bytemuck::Pod + bytemuck::Zeroable,
     {
-        let offset_bytes = modify_action.offset * std::mem::size_of::<T>();
-        let size_bytes = modify_action.size * std::mem::size_of::<T>();
+        if modify_action.is_write_only() {
+            self.modify_write_only(modify_action, queue);
 
-        let read_buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("Read Buffer"),
-            size: size_bytes as BufferAddress,
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
+            return Ok(());
+        }
 
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Buffer Copy Encoder"),
-        });
+        let mut data = self.read::<T>(modify_action.offset, modify_action.size, device, queue)?;
+
+        modify_action.act(&mut data);
+
+        self.write(queue, modify_action.offset, &data);
+
+        Ok(())
+    }
+
+    /// Runs the action on a zeroed scratch slice and writes it, without reading the range back.
+    pub fn modify_write_only<T>(&self, mut modify_action: ModifyAction<T>, queue: &wgpu::Queue)
+    where
+        T: bytemuck::Pod + bytemuck::Zeroable,
+    {
+        let mut data = vec![T::zeroed(); modify_action.size];
+
+        modify_action.act(&mut data);
+
+        self.write(queue, modify_action.offset, &data);
+    }
+}
 
+/// Submits the writes queued so far and blocks until the gpu finished all submitted work.
+pub(super) fn sync(device: &wgpu::Device, queue: &wgpu::Queue) {
+    // staged writes are only flushed with the next submission
+    queue.submit(std::iter::empty());
+    device.poll(wgpu::Maintain::Wait);
+}
+
+// non-blocking polls before falling back to waiting for the whole queue
+const MAP_POLLS: u32 = 16;
+// polls retried right away, later ones back off
+const MAP_SPINS: u32 = 4;
+const MAP_MAX_BACKOFF: std::time::Duration = std::time::Duration::from_millis(1);
+
+/// Waits for the result of a `map_async`.
+///
+/// Polls without blocking first, small copies are usually mapped after a few polls, and
+/// only waits for all submitted work if the mapping isn't ready by then. Between polls the
+/// thread backs off exponentially, so a slow copy doesn't keep a core busy.
+fn await_map(
+    receiver: &flume::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    device: &wgpu::Device,
+) -> Option<Result<(), wgpu::BufferAsyncError>> {
+    for attempt in 0..MAP_POLLS {
+        if let Ok(result) = receiver.try_recv() {
+            return Some(result);
+        }
+
+        if device.poll(wgpu::Maintain::Poll).is_queue_empty() {
+            // the callback runs inside the poll that drained the queue
+            return receiver.try_recv().ok();
+        }
+
+        if attempt < MAP_SPINS {
+            std::hint::spin_loop();
+        } else {
+            let backoff = std::time::Duration::from_micros(1 << (attempt - MAP_SPINS));
+            std::thread::sleep(backoff.min(MAP_MAX_BACKOFF));
+        }
+    }
+
+    device.poll(wgpu::Maintain::Wait);
+
+    receiver.recv().ok()
+}
+
+/// Copies `size_bytes` bytes starting at `offset_bytes` out of `source` and maps them for reading.
+///
+/// The staging buffer and the encoder are labeled with `label`.
+pub(super) fn read_buffer<T>(
+    label: &str,
+    source: &wgpu::Buffer,
+    offset_bytes: BufferAddress,
+    size_bytes: BufferAddress,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<Vec<T>, RetherError>
+where
+    T: bytemuck::Pod + bytemuck::Zeroable,
+{
+    read_buffer_ranges(label, source, &[(offset_bytes, size_bytes)], device, queue)
+}
+
+/// Like [`read_buffer`], but copies the byte ranges `(offset, size)` back to back into one
+/// staging buffer, so they are read back with a single mapping.
+pub(super) fn read_buffer_ranges<T>(
+    label: &str,
+    source: &wgpu::Buffer,
+    ranges: &[(BufferAddress, BufferAddress)],
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<Vec<T>, RetherError>
+where
+    T: bytemuck::Pod + bytemuck::Zeroable,
+{
+    let read_buffer = device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size: ranges.iter().map(|(_, size)| size).sum(),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some(label) });
+
+    let mut dest_offset = 0;
+
+    for (offset_bytes, size_bytes) in ranges {
         encoder.copy_buffer_to_buffer(
-            &self.inner,
-            offset_bytes as BufferAddress,
+            source,
+            *offset_bytes,
             &read_buffer,
-            0,
-            size_bytes as BufferAddress,
+            dest_offset,
+            *size_bytes,
         );
 
-        queue.submit(std::iter::once(encoder.finish()));
+        dest_offset += size_bytes;
+    }
 
-        let (sender, receiver) = flume::bounded(1);
+    queue.submit(std::iter::once(encoder.finish()));
 
-        read_buffer
-            .slice(..)
-            .map_async(wgpu::MapMode::Read, move |_| {
-                sender.send(()).unwrap();
-            });
-        device.poll(wgpu::Maintain::Wait);
+    let (sender, receiver) = flume::bounded(1);
 
-        match receiver.recv() {
-            Ok(_) => {
-                let raw_data = read_buffer.slice(..).get_mapped_range();
+    read_buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+
+    match await_map(&receiver, device) {
+        Some(Ok(())) => {
+            let raw_data = read_buffer.slice(..).get_mapped_range();
 
-                let mut data = bytemuck::cast_slice::<u8, T>(&raw_data).to_vec();
-                modify_action.act(&mut data);
+            let data = bytemuck::cast_slice::<u8, T>(&raw_data).to_vec();
 
-                drop(raw_data);
+            drop(raw_data);
 
-                read_buffer.unmap();
-                read_buffer.destroy();
+            read_buffer.unmap();
+            read_buffer.destroy();
+
+            Ok(data)
+        }
+        _ => {
+            read_buffer.destroy();
 
-                self.write(queue, modify_action.offset, &data);
-            }
-            Err(_) => panic!("Failed to map read buffer"),
+            Err(RetherError::ReadbackFailed)
         }
     }
 }
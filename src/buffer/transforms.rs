@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use glam::Mat3;
+use wgpu::{BufferAddress, Device, Queue};
+
+use crate::Transform;
+
+use super::{
+    alloc::{self, AllocHandle, BufferAllocationID},
+    Buffer, IndexedBuffer,
+};
+
+// slot 0 always holds the identity, allocations without a transform are drawn with it
+const IDENTITY_SLOT: u32 = 0;
+
+/// The uniform of one allocation, matching
+///
+/// ```wgsl
+/// struct ModelTransform {
+///     model: mat4x4<f32>,
+///     normal: mat3x3<f32>,
+/// }
+/// ```
+///
+/// The columns of the normal matrix are padded to four floats like in WGSL.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TransformUniform {
+    pub model: [[f32; 4]; 4],
+    pub normal: [[f32; 4]; 3],
+}
+
+impl From<&Transform> for TransformUniform {
+    fn from(transform: &Transform) -> Self {
+        let model = transform.matrix();
+        let normal = Mat3::from_mat4(model).inverse().transpose();
+
+        Self {
+            model: model.to_cols_array_2d(),
+            normal: [normal.x_axis, normal.y_axis, normal.z_axis]
+                .map(|column| column.extend(0.0).to_array()),
+        }
+    }
+}
+
+/// A [`Transform`] per allocation in a uniform buffer, so moving an object only uploads
+/// its matrix instead of rewriting its vertices through a [`super::alloc::ModifyAction`].
+///
+/// Every transform lives at its own dynamic offset of one binding, the shader reads it as
+/// a [`TransformUniform`] at binding 0 of the group passed to `render_transformed`.
+/// Allocations without a transform are drawn with the identity.
+#[derive(Debug)]
+pub struct TransformBuffer {
+    inner: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    slots: HashMap<BufferAllocationID, u32>,
+    free_slots: Vec<u32>,
+    capacity: u32,
+    // size of a uniform rounded up to the offset alignment of the device
+    stride: BufferAddress,
+    label: String,
+}
+
+impl TransformBuffer {
+    pub fn new(label: &str, device: &Device, queue: &Queue) -> Self {
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as BufferAddress;
+        let stride =
+            (std::mem::size_of::<TransformUniform>() as BufferAddress).next_multiple_of(alignment);
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        std::mem::size_of::<TransformUniform>() as u64,
+                    ),
+                },
+                count: None,
+            }],
+        });
+
+        let capacity = 1;
+        let inner = Self::create_buffer(label, capacity, stride, device);
+        let bind_group = Self::create_bind_group(label, &layout, &inner, device);
+
+        queue.write_buffer(
+            &inner,
+            0,
+            bytemuck::bytes_of(&TransformUniform::from(&Transform::default())),
+        );
+
+        Self {
+            inner,
+            layout,
+            bind_group,
+            slots: HashMap::new(),
+            free_slots: Vec::new(),
+            capacity,
+            stride,
+            label: label.to_string(),
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The layout to build the pipeline with.
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Sets the transform of the allocation `id`, only its uniform is uploaded.
+    pub fn set(&mut self, id: &str, transform: &Transform, device: &Device, queue: &Queue) {
+        let slot = match self.slots.get(id) {
+            Some(slot) => *slot,
+            None => {
+                let slot = self.next_slot(device, queue);
+                self.slots.insert(id.to_string(), slot);
+
+                slot
+            }
+        };
+
+        queue.write_buffer(
+            &self.inner,
+            slot as BufferAddress * self.stride,
+            bytemuck::bytes_of(&TransformUniform::from(transform)),
+        );
+    }
+
+    /// Removes the transform of `id`, it's drawn with the identity again.
+    pub fn remove(&mut self, id: &str) {
+        if let Some(slot) = self.slots.remove(id) {
+            self.free_slots.push(slot);
+        }
+    }
+
+    /// The dynamic offset of the transform of `id`, the identity for unknown ids.
+    pub fn offset(&self, id: &str) -> u32 {
+        let slot = self.slots.get(id).copied().unwrap_or(IDENTITY_SLOT);
+
+        (slot as BufferAddress * self.stride) as u32
+    }
+
+    /// Drops the transforms of allocations that no longer exist, e.g. with the freed ids of
+    /// a [`super::BufferUpdate`].
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        let removed = self
+            .slots
+            .keys()
+            .filter(|id| !keep(id))
+            .cloned()
+            .collect::<Vec<BufferAllocationID>>();
+
+        for id in removed {
+            self.remove(&id);
+        }
+    }
+
+    fn next_slot(&mut self, device: &Device, queue: &Queue) -> u32 {
+        if let Some(slot) = self.free_slots.pop() {
+            return slot;
+        }
+
+        let slot = self.slots.len() as u32 + 1;
+
+        if slot >= self.capacity {
+            self.grow((self.capacity * 2).max(slot + 1), device, queue);
+        }
+
+        slot
+    }
+
+    fn grow(&mut self, capacity: u32, device: &Device, queue: &Queue) {
+        let inner = Self::create_buffer(&self.label, capacity, self.stride, device);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&format!("{}:grow", self.label)),
+        });
+
+        encoder.copy_buffer_to_buffer(
+            &self.inner,
+            0,
+            &inner,
+            0,
+            self.capacity as BufferAddress * self.stride,
+        );
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.bind_group = Self::create_bind_group(&self.label, &self.layout, &inner, device);
+        self.inner = inner;
+        self.capacity = capacity;
+    }
+
+    fn create_buffer(
+        label: &str,
+        capacity: u32,
+        stride: BufferAddress,
+        device: &Device,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: capacity as BufferAddress * stride,
+            usage: wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        label: &str,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        device: &Device,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<TransformUniform>() as u64),
+                }),
+            }],
+        })
+    }
+}
+
+impl<T: bytemuck::Pod + bytemuck::Zeroable, L: alloc::BufferAlloc<T>> Buffer<T, L> {
+    /// Renders every allocation with its transform of `transforms` bound at `group`, the
+    /// vertices themselves stay untransformed.
+    pub fn render_transformed<'a, 'b: 'a>(
+        &'b self,
+        transforms: &'b TransformBuffer,
+        group: u32,
+        render_pass: &'a mut wgpu::RenderPass<'b>,
+    ) {
+        render_pass.set_vertex_buffer(0, self.inner.inner.slice(..));
+
+        for handle in self.allocater.handles() {
+            let start = handle.offset() as u32;
+
+            render_pass.set_bind_group(
+                group,
+                transforms.bind_group(),
+                &[transforms.offset(handle.id())],
+            );
+            render_pass.draw(start..start + handle.size() as u32, 0..1);
+        }
+    }
+}
+
+impl<T, L, I> IndexedBuffer<T, L, I>
+where
+    T: bytemuck::Pod + bytemuck::Zeroable,
+    L: alloc::BufferAlloc<T>,
+    I: alloc::BufferAlloc<T>,
+{
+    /// Like [`Buffer::render_transformed`], one draw per index allocation.
+    pub fn render_transformed<'a, 'b: 'a>(
+        &'b self,
+        transforms: &'b TransformBuffer,
+        group: u32,
+        render_pass: &'a mut wgpu::RenderPass<'b>,
+    ) {
+        for handle in self.allocator_index.handles() {
+            self.render_id_transformed(handle.id(), transforms, group, render_pass);
+        }
+    }
+
+    /// Renders only the allocation `id` with its transform of `transforms`, see
+    /// [`Self::render_id`].
+    pub fn render_id_transformed<'a, 'b: 'a>(
+        &'b self,
+        id: &str,
+        transforms: &'b TransformBuffer,
+        group: u32,
+        render_pass: &'a mut wgpu::RenderPass<'b>,
+    ) {
+        render_pass.set_bind_group(group, transforms.bind_group(), &[transforms.offset(id)]);
+
+        self.render_id(id, render_pass);
+    }
+}
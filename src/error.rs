@@ -0,0 +1,64 @@
+use crate::alloc::BufferAllocationID;
+
+/// Errors returned by the fallible operations of the crate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetherError {
+    /// An action or destroy request couldn't be sent because the allocator was dropped.
+    SendFailed,
+    /// The operation isn't possible in the current state, e.g. transforming a destroyed model.
+    InvalidState(&'static str),
+    /// There is no allocation with this id.
+    UnknownAllocation(BufferAllocationID),
+    /// The data doesn't fit into the allocation.
+    ExceedsAllocation {
+        id: BufferAllocationID,
+        size: usize,
+        allocation_size: usize,
+    },
+    /// Mapping a buffer for reading it back failed.
+    ReadbackFailed,
+    /// The buffer would have to grow past the `max_buffer_size` limit of the device.
+    ExceedsDeviceLimit { size: u64, limit: u64 },
+    /// The range reaches past the end of the buffer, counted in elements.
+    OutOfBounds {
+        offset: usize,
+        size: usize,
+        buffer_size: usize,
+    },
+}
+
+impl std::fmt::Display for RetherError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SendFailed => write!(f, "Failed to send request, the allocator was dropped"),
+            Self::InvalidState(message) => write!(f, "{}", message),
+            Self::UnknownAllocation(id) => write!(f, "Unknown allocation {}", id),
+            Self::ExceedsAllocation {
+                id,
+                size,
+                allocation_size,
+            } => write!(
+                f,
+                "Data of {} elements exceeds allocation {} of size {}",
+                size, id, allocation_size
+            ),
+            Self::ReadbackFailed => write!(f, "Failed to map read buffer"),
+            Self::ExceedsDeviceLimit { size, limit } => write!(
+                f,
+                "Buffer of {} bytes exceeds the device limit of {} bytes",
+                size, limit
+            ),
+            Self::OutOfBounds {
+                offset,
+                size,
+                buffer_size,
+            } => write!(
+                f,
+                "Range of {} elements at {} exceeds the buffer of {} elements",
+                size, offset, buffer_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RetherError {}
@@ -1,14 +1,33 @@
+pub mod aabb;
 mod buffer;
+pub mod error;
 pub mod light;
 pub mod model;
 pub mod picking;
+pub mod prelude;
 pub mod texture;
 pub mod vertex;
 
+pub use aabb::Aabb;
 pub use buffer::Buffer;
+pub use buffer::BufferPool;
+pub use buffer::BufferUpdate;
+pub use buffer::IdPicker;
+pub use buffer::IndexMode;
 pub use buffer::IndexedBuffer;
+pub use buffer::IndirectArgs;
+pub use buffer::IndirectBuffer;
+pub use buffer::RenderHint;
+pub use buffer::RenderLayer;
+pub use buffer::Snapshot;
+pub use buffer::TransformBuffer;
+pub use buffer::TransformUniform;
+pub use error::RetherError;
 
 pub use buffer::alloc;
+pub use buffer::growth;
 
 pub use model::geometry::SimpleGeometry;
-pub use model::transform::{Rotate, Scale, Transform, Translate};
+pub use model::transform::{
+    pack_transforms, Affine, InstanceData, InstanceRaw, Rotate, Scale, Transform, Translate,
+};
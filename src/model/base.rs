@@ -1,24 +1,26 @@
-use core::panic;
-
 use glam::Vec3;
-use parking_lot::RwLock;
+use parking_lot::{RwLock, RwLockWriteGuard};
 
 use crate::{
     alloc::{AllocHandle, DynamicAllocHandle, ModifyAction, StaticAllocHandle},
     vertex::{Vertex, VertexRotator, VertexScaler},
-    SimpleGeometry,
+    Aabb, RetherError, SimpleGeometry,
 };
 
 use super::{
+    check_not_indexed,
     geometry::IndexedGeometry,
+    model_bounds, move_bounds,
     transform::{Rotate, Scale, Translate},
-    Model, ModelState, RotateModel, ScaleModel, TranslateModel,
+    wake_bounds, wake_indexed, AwakeBounds, IndexedModel, Model, ModelState, RotateModel,
+    ScaleModel, TranslateModel,
 };
 
 #[derive(Debug)]
 pub struct BaseModel<T, H: AllocHandle<T>> {
     state: RwLock<ModelState<T, H>>,
     transform: RwLock<crate::Transform>,
+    bounds: AwakeBounds,
 }
 
 impl<T, H> BaseModel<T, H>
@@ -29,6 +31,7 @@ where
         Self {
             state: RwLock::new(ModelState::Dormant(geometry)),
             transform: RwLock::new(crate::Transform::default()),
+            bounds: RwLock::new(None),
         }
     }
 
@@ -36,6 +39,7 @@ where
         Self {
             state: RwLock::new(ModelState::DormantIndexed(geometry)),
             transform: RwLock::new(crate::Transform::default()),
+            bounds: RwLock::new(None),
         }
     }
 }
@@ -43,45 +47,86 @@ where
 impl Model<Vertex, StaticAllocHandle<Vertex>> for BaseModel<Vertex, StaticAllocHandle<Vertex>>
 // C: Translate + Scale + Rotate,
 {
-    fn wake(&self, handle: std::sync::Arc<StaticAllocHandle<Vertex>>) {
-        *self.state.write() = ModelState::Awake(handle);
+    fn wake(&self, handle: std::sync::Arc<StaticAllocHandle<Vertex>>) -> Result<(), RetherError> {
+        let mut state = self.state.write();
+
+        check_not_indexed(&*state)?;
+        wake_bounds(&*state, &self.bounds);
+        *state = ModelState::Awake(handle);
+
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        model_bounds(&*self.state.read(), &self.bounds)
     }
 
     fn transform(&self) -> crate::Transform {
         self.transform.read().clone()
     }
 
-    fn state(&self) -> &RwLock<ModelState<Vertex, StaticAllocHandle<Vertex>>> {
-        &self.state
+    fn state(&self) -> Result<&RwLock<ModelState<Vertex, StaticAllocHandle<Vertex>>>, RetherError> {
+        Ok(&self.state)
     }
 
-    fn destroy(&self) {
-        panic!("Static handle cannot be destroyed");
+    fn destroy(&self) -> Result<(), RetherError> {
+        Err(RetherError::InvalidState(
+            "Static handle cannot be destroyed",
+        ))
+    }
+}
+
+impl<H: AllocHandle<Vertex>> IndexedModel<Vertex, H> for BaseModel<Vertex, H>
+where
+    Self: Model<Vertex, H>,
+{
+    fn wake(
+        &self,
+        handle: std::sync::Arc<H>,
+        index_handle: std::sync::Arc<H>,
+    ) -> Result<(), RetherError> {
+        wake_indexed(&self.state, handle, &index_handle, |state| {
+            wake_bounds(state, &self.bounds)
+        })
     }
 }
 
 impl Model<Vertex, DynamicAllocHandle<Vertex>> for BaseModel<Vertex, DynamicAllocHandle<Vertex>> {
-    fn wake(&self, handle: std::sync::Arc<DynamicAllocHandle<Vertex>>) {
-        *self.state.write() = ModelState::Awake(handle);
+    fn wake(&self, handle: std::sync::Arc<DynamicAllocHandle<Vertex>>) -> Result<(), RetherError> {
+        let mut state = self.state.write();
+
+        check_not_indexed(&*state)?;
+        wake_bounds(&*state, &self.bounds);
+        *state = ModelState::Awake(handle);
+
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        model_bounds(&*self.state.read(), &self.bounds)
     }
 
     fn transform(&self) -> crate::Transform {
         self.transform.read().clone()
     }
 
-    fn state(&self) -> &RwLock<ModelState<Vertex, DynamicAllocHandle<Vertex>>> {
-        &self.state
+    fn state(
+        &self,
+    ) -> Result<&RwLock<ModelState<Vertex, DynamicAllocHandle<Vertex>>>, RetherError> {
+        Ok(&self.state)
     }
 
-    fn destroy(&self) {
+    fn destroy(&self) -> Result<(), RetherError> {
         match &*self.state.read() {
             ModelState::Awake(ref handle) => {
-                handle.destroy();
+                handle.destroy()?;
             }
-            _ => panic!("Cannot destroy a dead handle"),
+            _ => return Err(RetherError::InvalidState("Cannot destroy a dead handle")),
         };
 
         *self.state.write() = ModelState::Destroyed;
+
+        Ok(())
     }
 
     fn is_destroyed(&self) -> bool {
@@ -90,94 +135,195 @@ impl Model<Vertex, DynamicAllocHandle<Vertex>> for BaseModel<Vertex, DynamicAllo
 }
 
 // Translate, Rotate and Scale are implemented for BaseModel
+//
+// The state is only locked to mutate dormant geometry or to clone the handle, so sending an
+// action doesn't block readers of the state and `transform()` only ever waits on the short
+// transform updates.
 impl<T, H> TranslateModel for BaseModel<T, H>
 where
     T: Translate,
     H: AllocHandle<T>,
 {
-    fn translate(&self, translation: glam::Vec3) {
-        match &mut *self.state.write() {
-            ModelState::Awake(ref mut handle) => {
-                let mod_action = Box::new(move |data: &mut [T]| data.translate(translation));
+    fn translate(&self, translation: glam::Vec3) -> Result<(), RetherError> {
+        self.translate_locked(self.state.write(), translation)
+    }
+}
+
+impl<H> RotateModel for BaseModel<Vertex, H>
+where
+    H: AllocHandle<Vertex>,
+{
+    fn rotate(&self, rotation: glam::Quat, center: Option<Vec3>) -> Result<(), RetherError> {
+        self.rotate_locked(self.state.write(), rotation, center)
+    }
+}
+
+impl<H> ScaleModel for BaseModel<Vertex, H>
+where
+    H: AllocHandle<Vertex>,
+{
+    fn scale(&self, scale: glam::Vec3, center: Option<Vec3>) -> Result<(), RetherError> {
+        self.scale_locked(self.state.write(), scale, center)
+    }
+}
 
-                let action = ModifyAction::new(0, handle.size(), mod_action);
+impl<T, H> BaseModel<T, H>
+where
+    T: Translate,
+    H: AllocHandle<T>,
+{
+    /// Like [`TranslateModel::translate`], but returns `Ok(false)` without translating if the
+    /// state is locked, e.g. by a loader uploading the geometry, instead of waiting for it.
+    pub fn try_translate(&self, translation: glam::Vec3) -> Result<bool, RetherError> {
+        match self.state.try_write() {
+            Some(state) => self.translate_locked(state, translation).map(|_| true),
+            None => Ok(false),
+        }
+    }
 
-                self.transform.write().translate(translation);
-                handle.send_action(action).expect("Failed to send action");
-            }
+    fn translate_locked(
+        &self,
+        mut state: RwLockWriteGuard<'_, ModelState<T, H>>,
+        translation: glam::Vec3,
+    ) -> Result<(), RetherError> {
+        let handle = match &mut *state {
+            ModelState::Awake(handle) => handle.clone(),
             ModelState::Dormant(ref mut geometry) => {
                 self.transform.write().translate(translation);
                 geometry.translate(translation);
+
+                return Ok(());
             }
             ModelState::DormantIndexed(ref mut geometry) => {
                 self.transform.write().translate(translation);
                 geometry.translate(translation);
+
+                return Ok(());
             }
-            _ => panic!("Cannot translate a dead handle"),
-        }
+            _ => return Err(RetherError::InvalidState("Cannot translate a dead handle")),
+        };
+        // the transform is locked before the state is released, so concurrent transforms
+        // update it and send their actions in the order they got the state
+        let mut transform = self.transform.write();
+        drop(state);
+
+        let mod_action = Box::new(move |data: &mut [T]| data.translate(translation));
+
+        let action = ModifyAction::new(0, handle.size(), mod_action);
+
+        transform.translate(translation);
+        move_bounds(&self.bounds, |corner| corner + translation);
+        handle.send_action(action)
     }
 }
 
-impl<H> RotateModel for BaseModel<Vertex, H>
+impl<H> BaseModel<Vertex, H>
 where
     H: AllocHandle<Vertex>,
 {
-    fn rotate(&self, rotation: glam::Quat, center: Option<Vec3>) {
-        match &mut *self.state.write() {
-            ModelState::Awake(ref mut handle) => {
-                let mod_action = Box::new(move |data: &mut [Vertex]| {
-                    VertexRotator::new(data).rotate(rotation, center.unwrap_or(Vec3::ZERO))
-                });
+    /// The non blocking [`RotateModel::rotate`], see [`Self::try_translate`].
+    pub fn try_rotate(
+        &self,
+        rotation: glam::Quat,
+        center: Option<Vec3>,
+    ) -> Result<bool, RetherError> {
+        match self.state.try_write() {
+            Some(state) => self.rotate_locked(state, rotation, center).map(|_| true),
+            None => Ok(false),
+        }
+    }
 
-                let action = ModifyAction::new(0, handle.size(), mod_action);
+    /// The non blocking [`ScaleModel::scale`], see [`Self::try_translate`].
+    pub fn try_scale(&self, scale: glam::Vec3, center: Option<Vec3>) -> Result<bool, RetherError> {
+        match self.state.try_write() {
+            Some(state) => self.scale_locked(state, scale, center).map(|_| true),
+            None => Ok(false),
+        }
+    }
 
-                self.transform
-                    .write()
-                    .rotate(rotation, center.unwrap_or(Vec3::ZERO));
-                handle.send_action(action).expect("Failed to send action");
-            }
+    fn rotate_locked(
+        &self,
+        mut state: RwLockWriteGuard<'_, ModelState<Vertex, H>>,
+        rotation: glam::Quat,
+        center: Option<Vec3>,
+    ) -> Result<(), RetherError> {
+        let handle = match &mut *state {
+            ModelState::Awake(handle) => handle.clone(),
             ModelState::Dormant(ref mut geometry) => {
                 self.transform
                     .write()
                     .rotate(rotation, center.unwrap_or(Vec3::ZERO));
                 geometry.rotate(rotation, center.unwrap_or(Vec3::ZERO));
+
+                return Ok(());
             }
             ModelState::DormantIndexed(ref mut geometry) => {
                 self.transform
                     .write()
                     .rotate(rotation, center.unwrap_or(Vec3::ZERO));
                 geometry.rotate(rotation, center.unwrap_or(Vec3::ZERO));
+
+                return Ok(());
             }
-            _ => panic!("Cannot rotate a dead handle"),
-        }
-    }
-}
+            _ => return Err(RetherError::InvalidState("Cannot rotate a dead handle")),
+        };
+        // see `translate_locked`
+        let mut transform = self.transform.write();
+        drop(state);
 
-impl<H> ScaleModel for BaseModel<Vertex, H>
-where
-    H: AllocHandle<Vertex>,
-{
-    fn scale(&self, scale: glam::Vec3, center: Option<Vec3>) {
-        match &mut *self.state.write() {
-            ModelState::Awake(ref mut handle) => {
-                let mod_action = Box::new(move |data: &mut [Vertex]| {
-                    VertexScaler::new(data, center.unwrap_or(Vec3::ZERO)).scale(scale);
-                });
+        let mod_action = Box::new(move |data: &mut [Vertex]| {
+            VertexRotator::new(data).rotate(rotation, center.unwrap_or(Vec3::ZERO))
+        });
 
-                let action = ModifyAction::new(0, handle.size(), mod_action);
+        let action = ModifyAction::new(0, handle.size(), mod_action);
 
-                self.transform.write().scale(scale);
-                handle.send_action(action).expect("Failed to send action");
-            }
+        transform.rotate(rotation, center.unwrap_or(Vec3::ZERO));
+        move_bounds(&self.bounds, |corner| {
+            let center = center.unwrap_or(Vec3::ZERO);
+
+            rotation * (corner - center) + center
+        });
+        handle.send_action(action)
+    }
+
+    fn scale_locked(
+        &self,
+        mut state: RwLockWriteGuard<'_, ModelState<Vertex, H>>,
+        scale: glam::Vec3,
+        center: Option<Vec3>,
+    ) -> Result<(), RetherError> {
+        let handle = match &mut *state {
+            ModelState::Awake(handle) => handle.clone(),
             ModelState::Dormant(ref mut geometry) => {
                 self.transform.write().scale(scale);
                 geometry.scale(scale);
+
+                return Ok(());
             }
             ModelState::DormantIndexed(ref mut geometry) => {
                 self.transform.write().scale(scale);
                 geometry.scale(scale);
+
+                return Ok(());
             }
-            _ => panic!("Cannot scale a dead handle"),
-        }
+            _ => return Err(RetherError::InvalidState("Cannot scale a dead handle")),
+        };
+        // see `translate_locked`
+        let mut transform = self.transform.write();
+        drop(state);
+
+        let mod_action = Box::new(move |data: &mut [Vertex]| {
+            VertexScaler::new(data, center.unwrap_or(Vec3::ZERO)).scale(scale);
+        });
+
+        let action = ModifyAction::new(0, handle.size(), mod_action);
+
+        transform.scale(scale);
+        move_bounds(&self.bounds, |corner| {
+            let center = center.unwrap_or(Vec3::ZERO);
+
+            (corner - center) * scale + center
+        });
+        handle.send_action(action)
     }
 }
@@ -1,12 +1,19 @@
-use glam::Vec3;
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use glam::{Mat3, Vec3};
 
 use crate::{
     buffer::{BufferData, IndexedBufferData},
-    vertex::{Vertex, VertexRotator},
+    picking::{intersect_triangle, Ray},
+    vertex::{PackedVertex, Vertex, VertexRotator},
+    Aabb, RetherError,
 };
 
 use super::{
-    transform::{Rotate, Scale, Translate},
+    transform::{Rotate, Scale, Transform, Translate},
     Expandable,
 };
 
@@ -39,6 +46,15 @@ pub trait Geometry: Expandable {
 
     fn build_data(&self) -> Self::Data<'_>;
     fn data_len(&self) -> usize;
+
+    fn vertex_count(&self) -> usize {
+        self.data_len()
+    }
+
+    /// The number of triangles drawn, defaults to a plain triangle list.
+    fn triangle_count(&self) -> usize {
+        self.data_len() / 3
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -56,6 +72,159 @@ impl<T> SimpleGeometry<T> {
     pub fn init(vertices: Vec<T>) -> Self {
         Self { vertices }
     }
+
+    pub fn vertices(&self) -> &[T] {
+        &self.vertices
+    }
+}
+
+impl<T> Default for SimpleGeometry<T> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl SimpleGeometry<Vertex> {
+    /// Builds the vertices from separate attribute arrays, e.g. as imported from glTF.
+    ///
+    /// Missing attributes are filled with the defaults of [`Vertex`]. Fails if a given
+    /// array doesn't have one entry per position.
+    pub fn from_arrays(
+        positions: &[[f32; 3]],
+        normals: Option<&[[f32; 3]]>,
+        colors: Option<&[[f32; 4]]>,
+    ) -> Result<Self, RetherError> {
+        let lengths_match = normals.is_none_or(|normals| normals.len() == positions.len())
+            && colors.is_none_or(|colors| colors.len() == positions.len());
+
+        if !lengths_match {
+            return Err(RetherError::InvalidState(
+                "Attribute arrays must have the same length as the positions",
+            ));
+        }
+
+        let vertices = positions
+            .iter()
+            .enumerate()
+            .map(|(index, position)| {
+                let default = Vertex::default();
+
+                Vertex {
+                    position: *position,
+                    normal: normals.map_or(default.normal, |normals| normals[index]),
+                    color: colors.map_or(default.color, |colors| colors[index]),
+                }
+            })
+            .collect();
+
+        Ok(Self::init(vertices))
+    }
+
+    /// The bounds of all vertex positions, None if the geometry is empty.
+    pub fn aabb(&self) -> Option<Aabb> {
+        vertices_aabb(&self.vertices)
+    }
+
+    /// Like [`Expandable::expand`], but the appended vertices are recolored to `color`,
+    /// e.g. to highlight the merged part.
+    pub fn expand_with_color(&mut self, other: &Self, color: [f32; 4]) {
+        self.vertices.extend(
+            other
+                .vertices
+                .iter()
+                .map(|vertex| Vertex { color, ..*vertex }),
+        );
+    }
+
+    /// A copy of the geometry with `transform` baked into the positions and normals.
+    pub fn transformed(&self, transform: &Transform) -> SimpleGeometry<Vertex> {
+        let matrix = transform.matrix();
+        let normal_matrix = Mat3::from_mat4(matrix).inverse().transpose();
+
+        let vertices = self
+            .vertices
+            .iter()
+            .map(|vertex| Vertex {
+                position: matrix.transform_point3(Vec3::from(vertex.position)).into(),
+                normal: (normal_matrix * Vec3::from(vertex.normal))
+                    .normalize_or_zero()
+                    .into(),
+                color: vertex.color,
+            })
+            .collect();
+
+        SimpleGeometry::init(vertices)
+    }
+
+    /// Packs the vertices into [`PackedVertex`]es quantized inside the bounds of the
+    /// geometry, the bounds are returned to unpack them again.
+    ///
+    /// None if the geometry is empty.
+    pub fn pack(&self) -> Option<(Vec<PackedVertex>, Aabb)> {
+        let bounds = self.aabb()?;
+
+        let packed = self
+            .vertices
+            .iter()
+            .map(|vertex| PackedVertex::new(vertex, &bounds))
+            .collect();
+
+        Some((packed, bounds))
+    }
+
+    /// The vertices grouped into triangles, like they are drawn as a triangle list.
+    ///
+    /// Trailing vertices that don't form a whole triangle are skipped.
+    pub fn iter_triangles(&self) -> impl Iterator<Item = [Vertex; 3]> + '_ {
+        self.vertices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+    }
+
+    /// Tests the ray against every triangle and returns the index of the nearest hit
+    /// triangle with the distance along the ray, both sides of the triangles are hit.
+    ///
+    /// Linear in the number of triangles, build a [`crate::picking::HitboxRoot`] for large
+    /// meshes or many rays.
+    pub fn raycast(&self, ray: &Ray) -> Option<(usize, f32)> {
+        self.iter_triangles()
+            .enumerate()
+            .filter_map(|(index, triangle)| {
+                let corners = triangle.map(|vertex| Vec3::from(vertex.position));
+
+                intersect_triangle(ray, &corners, None).map(|distance| (index, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+
+    /// The average of all vertex positions, zero if the geometry is empty.
+    pub fn centroid(&self) -> Vec3 {
+        if self.vertices.is_empty() {
+            return Vec3::ZERO;
+        }
+
+        self.vertices
+            .iter()
+            .map(|vertex| Vec3::from(vertex.position))
+            .sum::<Vec3>()
+            / self.vertices.len() as f32
+    }
+
+    /// The center of the bounding box, zero if the geometry is empty.
+    ///
+    /// Unlike the centroid it isn't pulled towards densely tessellated areas.
+    pub fn aabb_center(&self) -> Vec3 {
+        self.aabb().map_or(Vec3::ZERO, |aabb| aabb.center())
+    }
+
+    /// Moves the geometry so its centroid lies at the origin, returns the applied translation.
+    pub fn recenter(&mut self) -> Vec3 {
+        let translation = -self.centroid();
+
+        self.translate(translation);
+
+        translation
+    }
 }
 
 impl<T> Geometry for SimpleGeometry<T>
@@ -103,6 +272,20 @@ pub struct IndexedGeometry<T> {
     indices: Vec<u32>,
 }
 
+impl<T> IndexedGeometry<T> {
+    pub fn vertices(&self) -> &[T] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn index_count(&self) -> usize {
+        self.indices.len()
+    }
+}
+
 impl<T: Clone> IndexedGeometry<T> {
     pub fn empty() -> Self {
         Self {
@@ -118,6 +301,320 @@ impl<T: Clone> IndexedGeometry<T> {
     pub fn into_simple(self) -> SimpleGeometry<T> {
         SimpleGeometry::init(self.vertices)
     }
+
+    /// Converts the triangle list into triangle strips by greedily walking over shared edges.
+    ///
+    /// A strip only continues into a neighbour whose winding matches the alternating
+    /// winding of the strip, so the culled side of every triangle is kept.
+    pub fn stripify(&self) -> TriangleStripGeometry<T> {
+        let adjacency = self.build_adjacency();
+        let faces = self.indices.chunks_exact(3).collect::<Vec<_>>();
+
+        let mut visited = vec![false; faces.len()];
+        let mut indices = Vec::new();
+
+        for start in 0..faces.len() {
+            if visited[start] {
+                continue;
+            }
+
+            visited[start] = true;
+
+            if !indices.is_empty() {
+                indices.push(STRIP_RESTART);
+            }
+
+            let mut strip = faces[start].to_vec();
+
+            loop {
+                let [a, b] = [strip[strip.len() - 2], strip[strip.len() - 1]];
+                // odd triangles of a strip are drawn with their first two vertices swapped
+                let odd = strip.len() % 2 == 1;
+
+                let next = adjacency.edge_faces(a, b).iter().find_map(|face| {
+                    if visited[*face] {
+                        return None;
+                    }
+
+                    let c = *faces[*face]
+                        .iter()
+                        .find(|index| **index != a && **index != b)?;
+                    let triangle = if odd { [b, a, c] } else { [a, b, c] };
+
+                    same_winding(&triangle, faces[*face]).then_some((*face, c))
+                });
+
+                match next {
+                    Some((face, c)) => {
+                        visited[face] = true;
+                        strip.push(c);
+                    }
+                    None => break,
+                }
+            }
+
+            indices.extend(strip);
+        }
+
+        TriangleStripGeometry::init(self.vertices.clone(), indices)
+    }
+
+    /// Builds the face adjacency of the mesh, build it once and pass it to every operation
+    /// that needs it.
+    pub fn build_adjacency(&self) -> Adjacency {
+        Adjacency::new(self.vertices.len(), &self.indices)
+    }
+
+    /// Removes the vertices at `indices` and every triangle that uses one of them, the
+    /// remaining indices are shifted onto the compacted vertices.
+    ///
+    /// Indices out of range are ignored, the order of the kept vertices and triangles is
+    /// preserved.
+    pub fn remove_vertices(&mut self, indices: &[u32]) {
+        let mut removed = vec![false; self.vertices.len()];
+
+        for index in indices {
+            if let Some(removed) = removed.get_mut(*index as usize) {
+                *removed = true;
+            }
+        }
+
+        // new index of every kept vertex, removed vertices keep the count of the ones before
+        let mut remap = Vec::with_capacity(removed.len());
+        let mut kept = 0;
+
+        for removed in removed.iter() {
+            remap.push(kept);
+
+            if !removed {
+                kept += 1;
+            }
+        }
+
+        self.indices = self
+            .indices
+            .chunks_exact(3)
+            .filter(|triangle| !triangle.iter().any(|index| removed[*index as usize]))
+            .flatten()
+            .map(|index| remap[*index as usize])
+            .collect();
+
+        let mut removed = removed.into_iter();
+        self.vertices.retain(|_| !removed.next().unwrap_or(false));
+    }
+}
+
+impl<T: Clone> Default for IndexedGeometry<T> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T: bytemuck::Pod> IndexedGeometry<T> {
+    /// Merges identical vertices, see [`weld`].
+    pub fn weld(&mut self) {
+        let (vertices, indices) = weld(&self.vertices, &self.indices);
+
+        self.vertices = vertices;
+        self.indices = indices;
+    }
+}
+
+impl IndexedGeometry<Vertex> {
+    /// The bounds of all vertex positions, None if the geometry is empty.
+    pub fn aabb(&self) -> Option<Aabb> {
+        vertices_aabb(&self.vertices)
+    }
+
+    /// Merges vertices that match within the tolerances of `config`, see [`weld_with`].
+    pub fn weld_with(&mut self, config: &WeldConfig) {
+        let (vertices, indices) = weld_with(&self.vertices, &self.indices, config);
+
+        self.vertices = vertices;
+        self.indices = indices;
+    }
+
+    /// Compares the geometry vertex by vertex within the tolerances of `config`, the
+    /// indices have to be identical.
+    ///
+    /// Unlike `==` this doesn't quantize, so it can't be matched up with
+    /// [`Self::content_hash`].
+    pub fn approx_eq(&self, other: &Self, config: &WeldConfig) -> bool {
+        self.indices == other.indices
+            && self.vertices.len() == other.vertices.len()
+            && self
+                .vertices
+                .iter()
+                .zip(other.vertices.iter())
+                .all(|(a, b)| config.matches(a, b))
+    }
+
+    /// A hash of the quantized vertices and the indices, e.g. to find repeated meshes that
+    /// can share one allocation.
+    ///
+    /// Equal geometry always has the same hash. The hash is stable for one build of the crate,
+    /// but shouldn't be persisted.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+
+        self.vertices.len().hash(&mut hasher);
+        for vertex in self.vertices.iter() {
+            quantize_vertex(vertex).hash(&mut hasher);
+        }
+        self.indices.hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    /// The vertices of every triangle resolved through the indices.
+    pub fn iter_triangles(&self) -> impl Iterator<Item = [Vertex; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|corner| self.vertices[triangle[corner] as usize]))
+    }
+
+    /// Cuts the mesh with the plane of all points `p` with `normal.dot(p) == distance`, like
+    /// [`crate::picking::PlaneHitbox`], and returns the segments of the contour.
+    ///
+    /// Every segment comes from one crossed triangle, its ends are interpolated from the
+    /// endpoints of the crossed edges with [`Vertex::lerp`], so they carry normals and
+    /// colors as well. Segments aren't ordered or joined into loops.
+    pub fn slice_plane(&self, normal: Vec3, distance: f32) -> Vec<[Vertex; 2]> {
+        let normal = normal.normalize_or_zero();
+
+        let signed_distance = |vertex: &Vertex| normal.dot(Vec3::from(vertex.position)) - distance;
+
+        self.iter_triangles()
+            .filter_map(|triangle| {
+                let distances = triangle.map(|vertex| signed_distance(&vertex));
+
+                // points on the plane count as above, so a triangle crosses exactly 0 or 2 edges
+                let mut crossings = [(0, 1), (1, 2), (2, 0)]
+                    .into_iter()
+                    .filter(|&(a, b)| (distances[a] < 0.0) != (distances[b] < 0.0))
+                    .map(|(a, b)| {
+                        let t = distances[a] / (distances[a] - distances[b]);
+
+                        triangle[a].lerp(&triangle[b], t)
+                    });
+
+                Some([crossings.next()?, crossings.next()?])
+            })
+            .collect()
+    }
+
+    /// Splits the mesh into chunks of a grid with cubic cells of `cell_size`, e.g. to
+    /// allocate and cull every chunk separately.
+    ///
+    /// Triangles go to the cell of their centroid, so they are never cut and chunks can
+    /// overlap slightly. Every chunk only holds the vertices it uses, chunks are ordered by
+    /// their cell. The whole mesh is a single chunk if `cell_size` isn't positive.
+    pub fn partition_grid(&self, cell_size: f32) -> Vec<IndexedGeometry<Vertex>> {
+        if cell_size.is_nan() || cell_size <= 0.0 {
+            return vec![self.clone()];
+        }
+
+        // the geometry of a cell and where the vertices of the mesh ended up in it
+        type Cell = (IndexedGeometry<Vertex>, HashMap<u32, u32>);
+
+        let mut cells: BTreeMap<[i32; 3], Cell> = BTreeMap::new();
+
+        for triangle in self.indices.chunks_exact(3) {
+            let centroid = triangle
+                .iter()
+                .map(|index| Vec3::from(self.vertices[*index as usize].position))
+                .sum::<Vec3>()
+                / 3.0;
+
+            let cell = (centroid / cell_size).floor().as_ivec3().to_array();
+            let (geometry, remap) = cells.entry(cell).or_default();
+
+            for index in triangle {
+                let local = *remap.entry(*index).or_insert_with(|| {
+                    geometry.vertices.push(self.vertices[*index as usize]);
+
+                    geometry.vertices.len() as u32 - 1
+                });
+
+                geometry.indices.push(local);
+            }
+        }
+
+        cells.into_values().map(|(geometry, _)| geometry).collect()
+    }
+
+    /// Whether `point` lies inside the mesh, which has to be closed.
+    ///
+    /// Counts the triangles crossed by rays from the point, an odd count means inside.
+    /// A ray grazing an edge or vertex can miscount, so three skewed rays vote on the result.
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        const DIRECTIONS: [Vec3; 3] = [
+            Vec3::new(1.0, 0.000_173, 0.000_311),
+            Vec3::new(0.000_227, 1.0, 0.000_137),
+            Vec3::new(0.000_293, 0.000_191, 1.0),
+        ];
+
+        let inside = DIRECTIONS
+            .iter()
+            .filter(|direction| {
+                let ray = Ray::new(point, **direction);
+
+                let crossings = self
+                    .iter_triangles()
+                    .filter(|triangle| {
+                        let corners = triangle.map(|vertex| Vec3::from(vertex.position));
+
+                        intersect_triangle(&ray, &corners, None).is_some()
+                    })
+                    .count();
+
+                crossings % 2 == 1
+            })
+            .count();
+
+        inside >= 2
+    }
+
+    /// Recomputes the normals around the `changed` vertices.
+    ///
+    /// Only the faces touching a changed vertex are considered, every vertex of those faces
+    /// (the changed vertices and their one-ring neighbours) gets the area weighted average
+    /// of its face normals.
+    pub fn recompute_normals_for(&mut self, changed: &[u32]) {
+        let adjacency = self.build_adjacency();
+
+        self.recompute_normals_with(&adjacency, changed);
+    }
+
+    /// Same as [`Self::recompute_normals_for`] with an already built adjacency, which has
+    /// to match the current indices.
+    pub fn recompute_normals_with(&mut self, adjacency: &Adjacency, changed: &[u32]) {
+        let mut affected = changed
+            .iter()
+            .flat_map(|index| adjacency.vertex_faces(*index).iter())
+            .flat_map(|face| self.indices[face * 3..face * 3 + 3].iter().copied())
+            .collect::<Vec<u32>>();
+
+        affected.sort_unstable();
+        affected.dedup();
+
+        for index in affected {
+            let normal = adjacency
+                .vertex_faces(index)
+                .iter()
+                .map(|face| {
+                    let [a, b, c] = [0, 1, 2].map(|corner| {
+                        Vec3::from(self.vertices[self.indices[face * 3 + corner] as usize].position)
+                    });
+
+                    (b - a).cross(c - a)
+                })
+                .sum::<Vec3>()
+                .normalize_or_zero();
+
+            self.vertices[index as usize].normal = normal.into();
+        }
+    }
 }
 
 impl<T> Geometry for IndexedGeometry<T>
@@ -133,6 +630,41 @@ where
     fn data_len(&self) -> usize {
         self.vertices.len()
     }
+
+    fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+/// Compares the vertices after quantizing them like [`IndexedGeometry::content_hash`], so
+/// tiny float differences don't break deduplication.
+impl PartialEq for IndexedGeometry<Vertex> {
+    fn eq(&self, other: &Self) -> bool {
+        self.indices == other.indices
+            && self.vertices.len() == other.vertices.len()
+            && self
+                .vertices
+                .iter()
+                .zip(other.vertices.iter())
+                .all(|(a, b)| quantize_vertex(a) == quantize_vertex(b))
+    }
+}
+
+// steps of 1e-5, fine enough to only merge vertices that are meant to be identical
+fn quantize_vertex(vertex: &Vertex) -> [i64; 10] {
+    let mut quantized = [0; 10];
+
+    for (target, value) in quantized.iter_mut().zip(
+        vertex
+            .position
+            .iter()
+            .chain(vertex.normal.iter())
+            .chain(vertex.color.iter()),
+    ) {
+        *target = (value * 1e5).round() as i64;
+    }
+
+    quantized
 }
 
 impl<T: Clone> Expandable for IndexedGeometry<T> {
@@ -168,3 +700,331 @@ impl<T: Scale> Scale for IndexedGeometry<T> {
         self.vertices.scale(scale)
     }
 }
+
+/// Index separating two strips, see [`TriangleStripGeometry`].
+pub const STRIP_RESTART: u32 = u32::MAX;
+
+/// Geometry drawn as triangle strips, strips are separated by [`STRIP_RESTART`].
+///
+/// Uploaded like indexed geometry, the pipeline has to use the topology and restart index
+/// of [`TriangleStripGeometry::primitive_state`].
+#[derive(Debug, Clone)]
+pub struct TriangleStripGeometry<T> {
+    vertices: Vec<T>,
+    indices: Vec<u32>,
+}
+
+impl<T> TriangleStripGeometry<T> {
+    pub fn init(vertices: Vec<T>, indices: Vec<u32>) -> Self {
+        Self { vertices, indices }
+    }
+
+    pub fn vertices(&self) -> &[T] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// The primitive state to draw the strips with, counter clockwise and back face culled.
+    pub fn primitive_state() -> wgpu::PrimitiveState {
+        wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            strip_index_format: Some(wgpu::IndexFormat::Uint32),
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        }
+    }
+
+    /// The indices of every triangle with the winding they are drawn with, degenerate
+    /// triangles are skipped.
+    pub fn iter_triangle_indices(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        self.indices
+            .split(|index| *index == STRIP_RESTART)
+            .flat_map(|strip| {
+                strip.windows(3).enumerate().map(|(position, window)| {
+                    if position % 2 == 0 {
+                        [window[0], window[1], window[2]]
+                    } else {
+                        [window[1], window[0], window[2]]
+                    }
+                })
+            })
+            .filter(|[a, b, c]| a != b && b != c && a != c)
+    }
+}
+
+impl TriangleStripGeometry<Vertex> {
+    /// The vertices of every triangle, see [`Self::iter_triangle_indices`].
+    pub fn iter_triangles(&self) -> impl Iterator<Item = [Vertex; 3]> + '_ {
+        self.iter_triangle_indices()
+            .map(|triangle| triangle.map(|index| self.vertices[index as usize]))
+    }
+}
+
+impl<T> Geometry for TriangleStripGeometry<T>
+where
+    T: Clone,
+{
+    type Data<'a> = IndexedBufferData<'a, T> where T: 'a;
+
+    fn build_data(&self) -> Self::Data<'_> {
+        IndexedBufferData::create(&self.vertices, &self.indices)
+    }
+
+    fn data_len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.iter_triangle_indices().count()
+    }
+}
+
+impl<T: Clone> Expandable for TriangleStripGeometry<T> {
+    fn expand(&mut self, other: &Self) {
+        let offset = self.vertices.len() as u32;
+
+        self.vertices.extend_from_slice(&other.vertices);
+
+        if !self.indices.is_empty() && !other.indices.is_empty() {
+            self.indices.push(STRIP_RESTART);
+        }
+
+        self.indices
+            .extend(other.indices.iter().map(|index| match *index {
+                STRIP_RESTART => STRIP_RESTART,
+                index => index + offset,
+            }));
+    }
+}
+
+fn same_winding(a: &[u32; 3], b: &[u32]) -> bool {
+    (0..3).any(|rotation| (0..3).all(|corner| a[corner] == b[(corner + rotation) % 3]))
+}
+
+/// Which faces share a vertex or an edge, faces are indices into the triangles of the mesh.
+#[derive(Debug, Clone, Default)]
+pub struct Adjacency {
+    vertex_faces: Vec<Vec<usize>>,
+    // keyed by the sorted vertex indices of the edge
+    edge_faces: HashMap<(u32, u32), Vec<usize>>,
+}
+
+impl Adjacency {
+    pub fn new(vertex_count: usize, indices: &[u32]) -> Self {
+        let mut vertex_faces = vec![Vec::new(); vertex_count];
+        let mut edge_faces: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+
+        for (face, triangle) in indices.chunks_exact(3).enumerate() {
+            for corner in 0..3 {
+                let (a, b) = (triangle[corner], triangle[(corner + 1) % 3]);
+
+                vertex_faces[a as usize].push(face);
+                edge_faces.entry(edge_key(a, b)).or_default().push(face);
+            }
+        }
+
+        Self {
+            vertex_faces,
+            edge_faces,
+        }
+    }
+
+    /// The faces using the vertex.
+    pub fn vertex_faces(&self, vertex: u32) -> &[usize] {
+        self.vertex_faces
+            .get(vertex as usize)
+            .map_or(&[], |faces| faces.as_slice())
+    }
+
+    /// The faces sharing the edge between `a` and `b`, in either direction.
+    pub fn edge_faces(&self, a: u32, b: u32) -> &[usize] {
+        self.edge_faces
+            .get(&edge_key(a, b))
+            .map_or(&[], |faces| faces.as_slice())
+    }
+
+    /// Every edge once, with the lower vertex index first.
+    pub fn edges(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.edge_faces.keys().copied()
+    }
+
+    /// Edges used by a single face, an empty iterator means the mesh is closed.
+    pub fn boundary_edges(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.edge_faces
+            .iter()
+            .filter(|(_, faces)| faces.len() == 1)
+            .map(|(edge, _)| *edge)
+    }
+}
+
+fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+pub(crate) fn vertices_aabb(vertices: &[Vertex]) -> Option<Aabb> {
+    let positions = vertices
+        .iter()
+        .map(|vertex| Vec3::from(vertex.position))
+        .collect::<Vec<Vec3>>();
+
+    Aabb::from_points(&positions)
+}
+
+/// Tolerances deciding which vertices [`weld_with`] merges.
+///
+/// The default only compares positions with a small epsilon, set `normal_angle_deg` and
+/// `compare_color` to keep hard edges and color seams apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeldConfig {
+    /// The maximum distance between two merged positions.
+    pub position_eps: f32,
+    /// The maximum angle between two merged normals in degrees, None ignores normals.
+    pub normal_angle_deg: Option<f32>,
+    /// Only merges vertices with the same color.
+    pub compare_color: bool,
+}
+
+impl Default for WeldConfig {
+    fn default() -> Self {
+        Self {
+            position_eps: 1e-5,
+            normal_angle_deg: None,
+            compare_color: false,
+        }
+    }
+}
+
+impl WeldConfig {
+    /// Compares all attributes, positions within `position_eps` and normals within a
+    /// fraction of a degree.
+    pub fn strict(position_eps: f32) -> Self {
+        Self {
+            position_eps,
+            normal_angle_deg: Some(0.1),
+            compare_color: true,
+        }
+    }
+
+    /// Whether `a` and `b` would be merged.
+    pub fn matches(&self, a: &Vertex, b: &Vertex) -> bool {
+        let position_eps = self.position_eps.max(0.0);
+
+        if Vec3::from(a.position).distance(Vec3::from(b.position)) > position_eps {
+            return false;
+        }
+
+        if let Some(angle) = self.normal_angle_deg {
+            let (normal_a, normal_b) = (Vec3::from(a.normal), Vec3::from(b.normal));
+
+            let same = normal_a == normal_b
+                || normal_a
+                    .normalize_or_zero()
+                    .dot(normal_b.normalize_or_zero())
+                    >= angle.to_radians().cos();
+
+            if !same {
+                return false;
+            }
+        }
+
+        !self.compare_color
+            || a.color
+                .iter()
+                .zip(b.color.iter())
+                .all(|(a, b)| (a - b).abs() <= COLOR_EPS)
+    }
+
+    // cells at least as large as the epsilon, so matches are always in neighbouring cells
+    fn cell(&self, position: [f32; 3]) -> [i32; 3] {
+        let size = self.position_eps.max(MIN_WELD_CELL);
+
+        position.map(|value| (value / size).floor() as i32)
+    }
+}
+
+const COLOR_EPS: f32 = 1e-5;
+// keeps the cells of a zero epsilon from saturating into one
+const MIN_WELD_CELL: f32 = 1e-6;
+
+/// Merges vertices that match within the tolerances of `config` and remaps the indices
+/// onto the remaining ones.
+///
+/// The first vertex of every group is kept with its attributes, later vertices are only
+/// compared against kept vertices, so chains of close vertices don't collapse into one.
+pub fn weld_with(
+    vertices: &[Vertex],
+    indices: &[u32],
+    config: &WeldConfig,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let mut welded: Vec<Vertex> = Vec::new();
+    let mut grid: HashMap<[i32; 3], Vec<u32>> = HashMap::new();
+
+    let remap = vertices
+        .iter()
+        .map(|vertex| {
+            let cell = config.cell(vertex.position);
+
+            let existing = neighbour_cells(cell)
+                .filter_map(|neighbour| grid.get(&neighbour))
+                .flatten()
+                .copied()
+                .filter(|index| config.matches(&welded[*index as usize], vertex))
+                .min();
+
+            existing.unwrap_or_else(|| {
+                welded.push(*vertex);
+
+                let index = welded.len() as u32 - 1;
+                grid.entry(cell).or_default().push(index);
+
+                index
+            })
+        })
+        .collect::<Vec<u32>>();
+
+    let indices = indices.iter().map(|index| remap[*index as usize]).collect();
+
+    (welded, indices)
+}
+
+fn neighbour_cells(cell: [i32; 3]) -> impl Iterator<Item = [i32; 3]> {
+    (-1..=1).flat_map(move |x| {
+        (-1..=1).flat_map(move |y| {
+            (-1..=1).map(move |z| {
+                [
+                    cell[0].saturating_add(x),
+                    cell[1].saturating_add(y),
+                    cell[2].saturating_add(z),
+                ]
+            })
+        })
+    })
+}
+
+/// Merges vertices with identical bytes and remaps the indices onto the remaining ones.
+///
+/// The first occurrence of a vertex is kept, so the order of the vertices is preserved.
+/// See [`weld_with`] to merge vertices within a tolerance.
+pub fn weld<T: bytemuck::Pod>(vertices: &[T], indices: &[u32]) -> (Vec<T>, Vec<u32>) {
+    let mut welded = Vec::new();
+    let mut lookup: HashMap<&[u8], u32> = HashMap::new();
+
+    let remap = vertices
+        .iter()
+        .map(|vertex| {
+            *lookup.entry(bytemuck::bytes_of(vertex)).or_insert_with(|| {
+                welded.push(*vertex);
+
+                welded.len() as u32 - 1
+            })
+        })
+        .collect::<Vec<u32>>();
+
+    let indices = indices.iter().map(|index| remap[*index as usize]).collect();
+
+    (welded, indices)
+}
@@ -1,18 +1,24 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use geometry::IndexedGeometry;
 use glam::Vec3;
 use parking_lot::RwLock;
 
-use crate::{alloc::AllocHandle, Rotate, Scale, SimpleGeometry, Transform, Translate};
+use crate::{
+    alloc::{AllocHandle, BufferAllocationID},
+    vertex::Vertex,
+    Aabb, RetherError, Rotate, Scale, SimpleGeometry, Transform, Translate,
+};
 
 mod base;
 pub mod geometry;
+mod scene;
 pub mod transform;
 mod tree;
 
 pub use base::BaseModel;
-pub use tree::TreeModel;
+pub use scene::{NodeDescriptor, NodeKind, SceneDescriptor};
+pub use tree::{SubTree, TreeModel, TreeModelBuilder};
 
 #[derive(Debug, Clone)]
 pub struct BufferLocation {
@@ -38,6 +44,120 @@ impl<T, H> ModelState<T, H> {
     }
 }
 
+impl<T, H: AllocHandle<T>> ModelState<T, H> {
+    /// The number of vertices, of the geometry while dormant or of the allocation while
+    /// awake. 0 for a destroyed model.
+    pub fn vertex_count(&self) -> usize {
+        match self {
+            Self::Dormant(geometry) => geometry.vertices().len(),
+            Self::DormantIndexed(geometry) => geometry.vertices().len(),
+            Self::Awake(handle) => handle.size(),
+            Self::Destroyed => 0,
+        }
+    }
+}
+
+impl<T: Clone, H> ModelState<T, H> {
+    /// Moves the geometry out of a dormant state without cloning it, the state is left
+    /// dormant with empty geometry.
+    ///
+    /// The indices of indexed geometry are dropped, see [`Self::take_indexed_geometry`].
+    /// None if the state isn't dormant.
+    pub fn take_geometry(&mut self) -> Option<SimpleGeometry<T>> {
+        match self {
+            Self::Dormant(geometry) => Some(std::mem::take(geometry)),
+            Self::DormantIndexed(_) => self
+                .take_indexed_geometry()
+                .map(|geometry| geometry.into_simple()),
+            Self::Awake(_) | Self::Destroyed => None,
+        }
+    }
+
+    /// Moves the geometry out of an indexed dormant state, the state is left dormant with
+    /// empty geometry. None for any other state.
+    pub fn take_indexed_geometry(&mut self) -> Option<IndexedGeometry<T>> {
+        match self {
+            Self::DormantIndexed(geometry) => Some(std::mem::take(geometry)),
+            _ => None,
+        }
+    }
+}
+
+impl<H> ModelState<Vertex, H> {
+    /// The bounds of the dormant geometry, None for any other state.
+    pub fn dormant_aabb(&self) -> Option<Aabb> {
+        match self {
+            Self::Dormant(geometry) => geometry.aabb(),
+            Self::DormantIndexed(geometry) => geometry.aabb(),
+            Self::Awake(_) | Self::Destroyed => None,
+        }
+    }
+}
+
+// The bounds of an awake model are kept as the corners of the box at wake and moved along
+// with every transform, so repeated rotations don't grow the box.
+pub(crate) type AwakeBounds = RwLock<Option<[Vec3; 8]>>;
+
+// a single handle only covers the vertices, the indices of indexed geometry would be dropped
+pub(crate) fn check_not_indexed<T, H>(state: &ModelState<T, H>) -> Result<(), RetherError> {
+    match state {
+        ModelState::DormantIndexed(_) => Err(RetherError::InvalidState(
+            "Indexed geometry is woken with IndexedModel::wake and the handles of an IndexedBuffer",
+        )),
+        _ => Ok(()),
+    }
+}
+
+// transforms only touch the vertices, so the model keeps the vertex handle, the index handle
+// only has to belong to the same allocation of the IndexedBuffer
+pub(crate) fn wake_indexed<T, H: AllocHandle<T>>(
+    state: &RwLock<ModelState<T, H>>,
+    handle: Arc<H>,
+    index_handle: &H,
+    wake_bounds: impl FnOnce(&ModelState<T, H>),
+) -> Result<(), RetherError> {
+    if handle.id() != index_handle.id() {
+        return Err(RetherError::InvalidState(
+            "The vertex and index handles belong to different allocations",
+        ));
+    }
+
+    let mut state = state.write();
+
+    if let ModelState::Dormant(_) = &*state {
+        return Err(RetherError::InvalidState(
+            "Simple geometry is woken with Model::wake",
+        ));
+    }
+
+    wake_bounds(&*state);
+    *state = ModelState::Awake(handle);
+
+    Ok(())
+}
+
+// takes the bounds of the dormant geometry before the state is replaced by the handle
+pub(crate) fn wake_bounds<H>(state: &ModelState<Vertex, H>, bounds: &AwakeBounds) {
+    if !state.is_alive() {
+        *bounds.write() = state.dormant_aabb().map(|aabb| aabb.corners());
+    }
+}
+
+pub(crate) fn move_bounds(bounds: &AwakeBounds, f: impl Fn(Vec3) -> Vec3) {
+    if let Some(corners) = bounds.write().as_mut() {
+        for corner in corners.iter_mut() {
+            *corner = f(*corner);
+        }
+    }
+}
+
+pub(crate) fn model_bounds<H>(state: &ModelState<Vertex, H>, bounds: &AwakeBounds) -> Option<Aabb> {
+    match state {
+        ModelState::Awake(_) => (*bounds.read()).and_then(|corners| Aabb::from_points(&corners)),
+        state => state.dormant_aabb(),
+    }
+}
+
 impl<T, H> From<SimpleGeometry<T>> for ModelState<T, H> {
     fn from(geometry: SimpleGeometry<T>) -> Self {
         Self::Dormant(geometry)
@@ -51,42 +171,79 @@ impl<T, H> From<IndexedGeometry<T>> for ModelState<T, H> {
 }
 
 pub trait TranslateModel {
-    fn translate(&self, translation: glam::Vec3);
+    fn translate(&self, translation: glam::Vec3) -> Result<(), RetherError>;
+
+    /// Moves the model along with its parent. Models keeping a transform relative to their
+    /// parent leave it untouched, by default this is [`Self::translate`].
+    fn translate_with_parent(&self, translation: glam::Vec3) -> Result<(), RetherError> {
+        self.translate(translation)
+    }
 }
 
 pub trait RotateModel {
-    fn rotate(&self, rotation: glam::Quat, center: Option<Vec3>);
+    fn rotate(&self, rotation: glam::Quat, center: Option<Vec3>) -> Result<(), RetherError>;
+
+    /// See [`TranslateModel::translate_with_parent`].
+    fn rotate_with_parent(
+        &self,
+        rotation: glam::Quat,
+        center: Option<Vec3>,
+    ) -> Result<(), RetherError> {
+        self.rotate(rotation, center)
+    }
 }
 
 pub trait ScaleModel {
-    fn scale(&self, scale: glam::Vec3, center: Option<Vec3>);
+    fn scale(&self, scale: glam::Vec3, center: Option<Vec3>) -> Result<(), RetherError>;
+
+    /// See [`TranslateModel::translate_with_parent`].
+    fn scale_with_parent(
+        &self,
+        scale: glam::Vec3,
+        center: Option<Vec3>,
+    ) -> Result<(), RetherError> {
+        self.scale(scale, center)
+    }
 }
 
 pub trait TransformModel: TranslateModel + RotateModel + ScaleModel {}
 
-pub trait Model<T: Translate + Rotate + Scale, H: AllocHandle<T>>:
-    TranslateModel + RotateModel + ScaleModel
-{
-    fn wake(&self, handle: Arc<H>);
+/// Show/hide state of a model, independent of picking and of the allocation itself.
+pub trait VisibilityModel {
+    /// Sets the visibility of the model and its whole subtree.
+    fn set_visible_recursive(&self, visible: bool);
+    fn is_visible(&self) -> bool;
 
-    fn destroy(&self) {}
-    fn is_destroyed(&self) -> bool {
-        false
-    }
+    /// The buffer ranges of all visible parts, hidden subtrees are skipped.
+    fn visible_locations(&self) -> Vec<BufferLocation>;
 
-    fn center(&self) -> Option<glam::Vec3> {
-        None
+    /// Like [`Self::visible_locations`], but also the parts that lie in other allocations,
+    /// keyed by their id. The ranges in the allocation of the model itself are under None.
+    fn visible_allocations(&self) -> HashMap<Option<BufferAllocationID>, Vec<BufferLocation>> {
+        HashMap::from([(None, self.visible_locations())])
     }
 
-    fn transform(&self) -> Transform;
-    fn state(&self) -> &RwLock<ModelState<T, H>>;
+    /// The ranges the model takes up whether visible or not, keyed like
+    /// [`Self::visible_allocations`]. Parents only draw the parts of their own range that
+    /// none of their children take up.
+    fn occupied_allocations(&self) -> HashMap<Option<BufferAllocationID>, Vec<BufferLocation>> {
+        self.visible_allocations()
+    }
 }
 
-pub trait IndexedModel<T: Translate + Rotate + Scale, H: AllocHandle<T>>:
+pub trait Model<T: Translate + Rotate + Scale, H: AllocHandle<T>>:
     TranslateModel + RotateModel + ScaleModel
 {
-    fn wake(&self, handle: Arc<H>, index_handle: Arc<H>);
-    fn destroy(&self) {}
+    /// Moves the model onto the gpu allocation of `handle`, e.g. from
+    /// [`crate::Buffer::allocate_init`].
+    ///
+    /// Fails with [`RetherError::InvalidState`] for dormant indexed geometry, a single
+    /// handle would silently drop its indices. Wake it with [`IndexedModel::wake`] instead.
+    fn wake(&self, handle: Arc<H>) -> Result<(), RetherError>;
+
+    fn destroy(&self) -> Result<(), RetherError> {
+        Ok(())
+    }
     fn is_destroyed(&self) -> bool {
         false
     }
@@ -94,8 +251,31 @@ pub trait IndexedModel<T: Translate + Rotate + Scale, H: AllocHandle<T>>:
     fn center(&self) -> Option<glam::Vec3> {
         None
     }
+
+    /// The current bounds of the model, whether it's dormant or awake. None if it has no
+    /// geometry or its bounds aren't tracked.
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
+
     fn transform(&self) -> Transform;
-    fn state(&self) -> &RwLock<ModelState<T, H>>;
+    /// The state of the model, fails for parts of a model that don't hold one, e.g. the
+    /// nodes and leaves of a [`TreeModel`].
+    fn state(&self) -> Result<&RwLock<ModelState<T, H>>, RetherError>;
+}
+
+/// Models that can hold indexed geometry, woken with the handles of
+/// [`crate::IndexedBuffer::allocate_init`].
+///
+/// Both traits have a `wake`, call it as `IndexedModel::wake(&model, ...)` if [`Model`] is
+/// in scope as well.
+pub trait IndexedModel<T: Translate + Rotate + Scale, H: AllocHandle<T>>: Model<T, H> {
+    /// Moves dormant indexed geometry onto the allocations of `handle` and `index_handle`.
+    ///
+    /// Transforms only modify the vertices, so the model keeps `handle`. Both handles have
+    /// to belong to the same allocation, the buffer frees both once the model is destroyed.
+    /// Fails with [`RetherError::InvalidState`] for simple geometry.
+    fn wake(&self, handle: Arc<H>, index_handle: Arc<H>) -> Result<(), RetherError>;
 }
 
 pub trait Expandable {
@@ -0,0 +1,185 @@
+use std::sync::atomic::Ordering;
+
+use crate::{
+    alloc::{AllocHandle, BufferAllocationID},
+    RetherError, SimpleGeometry, Transform,
+};
+
+use super::{
+    geometry::IndexedGeometry,
+    tree::{SubTree, TreeModel},
+    BufferLocation, ModelState, VisibilityModel,
+};
+
+/// A snapshot of a [`TreeModel`] that can be stored and turned into a model again.
+///
+/// Only the geometry, the hierarchy and the transform are captured, gpu handles are not.
+/// A loaded scene is dormant and has to be uploaded again.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SceneDescriptor<T> {
+    pub vertices: Vec<T>,
+    pub indices: Option<Vec<u32>>,
+    pub transform: Transform,
+    pub visible: bool,
+    pub children: Vec<NodeDescriptor>,
+}
+
+/// Whether a [`NodeDescriptor`] is loaded as a node or a leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeKind {
+    /// A node, even without children, so children can be attached to it again.
+    Node,
+    /// A leaf, loaded as a node anyway if the descriptor has children.
+    #[default]
+    Leaf,
+}
+
+/// A node or leaf of a [`SceneDescriptor`], the location points into the scene's vertices
+/// unless the node lies in another allocation.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeDescriptor {
+    pub offset: usize,
+    pub size: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub allocation_id: Option<BufferAllocationID>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kind: NodeKind,
+    pub transform: Transform,
+    pub visible: bool,
+    pub children: Vec<NodeDescriptor>,
+}
+
+impl<T: Clone, H: AllocHandle<T>> TreeModel<SubTree<T, H>, T, H> {
+    /// Describes the tree, fails if it isn't a dormant root.
+    ///
+    /// The geometry of an awake model lives on the gpu, read it back with
+    /// [`crate::Buffer::snapshot`] and fill in the descriptor's vertices instead.
+    pub fn to_descriptor(&self) -> Result<SceneDescriptor<T>, RetherError> {
+        match self {
+            Self::Root {
+                state,
+                transform,
+                sub_handles,
+                ..
+            } => {
+                let (vertices, indices) = match &*state.read() {
+                    ModelState::Dormant(geometry) => (geometry.vertices().to_vec(), None),
+                    ModelState::DormantIndexed(geometry) => (
+                        geometry.vertices().to_vec(),
+                        Some(geometry.indices().to_vec()),
+                    ),
+                    _ => {
+                        return Err(RetherError::InvalidState(
+                            "Cannot describe an awake or destroyed model",
+                        ))
+                    }
+                };
+
+                Ok(SceneDescriptor {
+                    vertices,
+                    indices,
+                    transform: transform.read().clone(),
+                    visible: self.is_visible(),
+                    children: node_descriptors(sub_handles),
+                })
+            }
+            Self::Node { .. } | Self::Leaf { .. } => {
+                Err(RetherError::InvalidState("Cannot describe a node or leaf"))
+            }
+        }
+    }
+
+    /// Creates a dormant tree from a descriptor.
+    ///
+    /// Nodes lying in other allocations have to be bound to them again with
+    /// [`TreeModel::bind_allocation`].
+    pub fn from_descriptor(descriptor: SceneDescriptor<T>) -> Self {
+        let tree = match descriptor.indices {
+            Some(indices) => Self::create_root_with_models(
+                IndexedGeometry::init(descriptor.vertices, indices),
+                sub_trees(descriptor.children),
+            ),
+            None => Self::create_root_with_models(
+                SimpleGeometry::init(descriptor.vertices),
+                sub_trees(descriptor.children),
+            ),
+        };
+
+        *tree.transform_lock().write() = descriptor.transform;
+        tree.visible_flag()
+            .store(descriptor.visible, Ordering::Relaxed);
+
+        // descriptors store world transforms, the local ones are derived from them
+        tree.relate_to(&Transform::default());
+
+        tree
+    }
+}
+
+fn node_descriptors<T, H: AllocHandle<T>>(sub_handles: &[SubTree<T, H>]) -> Vec<NodeDescriptor> {
+    sub_handles
+        .iter()
+        .filter_map(|handle| match &**handle {
+            TreeModel::Node {
+                location,
+                allocation_id,
+                sub_handles,
+                ..
+            } => Some(NodeDescriptor {
+                offset: location.offset,
+                size: location.size,
+                allocation_id: allocation_id.clone(),
+                kind: NodeKind::Node,
+                transform: handle.transform_lock().read().clone(),
+                visible: handle.is_visible(),
+                children: node_descriptors(sub_handles),
+            }),
+            TreeModel::Leaf {
+                location,
+                allocation_id,
+                ..
+            } => Some(NodeDescriptor {
+                offset: location.offset,
+                size: location.size,
+                allocation_id: allocation_id.clone(),
+                kind: NodeKind::Leaf,
+                transform: handle.transform_lock().read().clone(),
+                visible: handle.is_visible(),
+                children: Vec::new(),
+            }),
+            // roots only exist at the top of a tree
+            TreeModel::Root { .. } => None,
+        })
+        .collect()
+}
+
+fn sub_trees<T, H: AllocHandle<T>>(descriptors: Vec<NodeDescriptor>) -> Vec<SubTree<T, H>> {
+    descriptors
+        .into_iter()
+        .map(|descriptor| {
+            let location = BufferLocation {
+                offset: descriptor.offset,
+                size: descriptor.size,
+            };
+
+            let children = sub_trees(descriptor.children);
+            let is_node = descriptor.kind == NodeKind::Node || !children.is_empty();
+
+            let tree = match (descriptor.allocation_id, is_node) {
+                (None, false) => TreeModel::create_leaf(location),
+                (None, true) => TreeModel::create_node_with_models(location, children),
+                (Some(id), false) => TreeModel::create_leaf_in(&id, location),
+                (Some(id), true) => TreeModel::create_node_in(&id, location, children),
+            };
+
+            *tree.transform_lock().write() = descriptor.transform;
+            tree.visible_flag()
+                .store(descriptor.visible, Ordering::Relaxed);
+
+            SubTree::from(tree)
+        })
+        .collect()
+}
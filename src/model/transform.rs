@@ -1,6 +1,7 @@
-use glam::{Mat4, Vec3};
+use glam::{Mat3, Mat4, Vec3};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Transform {
     pub translation: glam::Vec3,
     pub rotation: glam::Quat,
@@ -29,6 +30,12 @@ pub trait Scale {
     fn scale(&mut self, scale: glam::Vec3);
 }
 
+/// Elements an affine matrix can be applied to as a whole, see
+/// [`crate::alloc::ModifyAction::transform`].
+pub trait Affine: Sized {
+    fn apply_matrix(data: &mut [Self], matrix: &Mat4);
+}
+
 impl Translate for Transform {
     fn translate(&mut self, translation: glam::Vec3) {
         self.translation += translation;
@@ -53,4 +60,156 @@ impl Transform {
             * Mat4::from_quat(self.rotation)
             * Mat4::from_scale(self.scale)
     }
+
+    /// Splits an affine matrix into a transform, shears are lost.
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    /// The transform that applied after `parent` results in this transform.
+    pub fn relative_to(&self, parent: &Transform) -> Self {
+        Self::from_matrix(parent.matrix().inverse() * self.matrix())
+    }
+
+    /// The matrix as column major arrays, ready for uploading.
+    pub fn to_cols_array_2d(&self) -> [[f32; 4]; 4] {
+        self.matrix().to_cols_array_2d()
+    }
+}
+
+/// A transform packed for an instance buffer.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    pub model: [[f32; 4]; 4],
+    /// The inverse transpose of the model matrix, used to transform normals.
+    pub normal: [[f32; 3]; 3],
+}
+
+impl From<&Transform> for InstanceRaw {
+    fn from(transform: &Transform) -> Self {
+        let model = transform.matrix();
+
+        Self {
+            model: model.to_cols_array_2d(),
+            normal: Mat3::from_mat4(model)
+                .inverse()
+                .transpose()
+                .to_cols_array_2d(),
+        }
+    }
+}
+
+impl InstanceRaw {
+    /// Instance layout at shader locations 3 to 9, following the locations of [`crate::vertex::Vertex::desc`].
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 19]>() as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 22]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+/// A transform with a color tint for an instance buffer, the shader multiplies the vertex
+/// color with the tint. Lets many copies of one mesh be recolored, e.g. to highlight a
+/// selected instance.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceData {
+    pub model: [[f32; 4]; 4],
+    pub color: [f32; 4],
+}
+
+impl InstanceData {
+    pub fn new(transform: &Transform, color: [f32; 4]) -> Self {
+        Self {
+            model: transform.to_cols_array_2d(),
+            color,
+        }
+    }
+
+    /// Instance layout at shader locations 3 to 7, following the locations of [`crate::vertex::Vertex::desc`].
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<InstanceData>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// Packs transforms for uploading them into an instance buffer.
+pub fn pack_transforms(transforms: &[Transform]) -> Vec<InstanceRaw> {
+    transforms.iter().map(InstanceRaw::from).collect()
 }
@@ -1,30 +1,59 @@
-use core::panic;
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use glam::Vec3;
 use parking_lot::RwLock;
 
 use crate::{
-    alloc::{AllocHandle, DynamicAllocHandle, ModifyAction, StaticAllocHandle},
-    model::{BufferLocation, Model, ModelState},
+    alloc::{
+        AllocHandle, BufferAllocationID, DynamicAllocHandle, FnModifyData, ModifyAction,
+        StaticAllocHandle,
+    },
+    model::{geometry::Geometry, BufferLocation, Expandable, Model, ModelState},
     vertex::{Vertex, VertexRotator, VertexScaler},
-    Rotate, Scale, Transform, Translate,
+    Aabb, RetherError, Rotate, Scale, SimpleGeometry, Transform, Translate,
 };
 
-use super::{RotateModel, ScaleModel, TranslateModel};
+use super::{
+    check_not_indexed, model_bounds, move_bounds, wake_bounds, wake_indexed, AwakeBounds,
+    IndexedModel, RotateModel, ScaleModel, TranslateModel, VisibilityModel,
+};
 // rethink tree cause usage is pretty complicated
 #[derive(Debug)]
 pub enum TreeModel<S, T, H: AllocHandle<T>> {
     Root {
         state: RwLock<ModelState<T, H>>,
         transform: RwLock<Transform>,
+        visible: AtomicBool,
+        bounds: AwakeBounds,
         sub_handles: Vec<S>,
     },
     Node {
         location: BufferLocation,
+        // the allocation the location lies in, None for the allocation of the root
+        allocation_id: Option<BufferAllocationID>,
+        // the handle of `allocation_id`, transforms are sent through it
+        external: RwLock<Option<Arc<H>>>,
+        // the world transform, including the transforms passed on by the parents
+        transform: RwLock<Transform>,
+        // the transform relative to the parent, see `node_transform`
+        local: RwLock<Transform>,
+        visible: AtomicBool,
         sub_handles: Vec<S>,
     },
     Leaf {
         location: BufferLocation,
+        allocation_id: Option<BufferAllocationID>,
+        external: RwLock<Option<Arc<H>>>,
+        transform: RwLock<Transform>,
+        local: RwLock<Transform>,
+        visible: AtomicBool,
     },
 }
 
@@ -36,6 +65,8 @@ where
         Self::Root {
             state: RwLock::new(geometry.into()),
             transform: RwLock::new(Transform::default()),
+            visible: AtomicBool::new(true),
+            bounds: RwLock::new(None),
             sub_handles: Vec::new(),
         }
     }
@@ -47,6 +78,8 @@ where
         Self::Root {
             state: RwLock::new(geometry.into()),
             transform: RwLock::new(Transform::default()),
+            visible: AtomicBool::new(true),
+            bounds: RwLock::new(None),
             sub_handles,
         }
     }
@@ -54,6 +87,11 @@ where
     pub fn create_node(location: BufferLocation) -> Self {
         Self::Node {
             location,
+            allocation_id: None,
+            external: RwLock::new(None),
+            transform: RwLock::new(Transform::default()),
+            local: RwLock::new(Transform::default()),
+            visible: AtomicBool::new(true),
             sub_handles: Vec::new(),
         }
     }
@@ -61,10 +99,177 @@ where
     pub fn create_node_with_models(location: BufferLocation, sub_handles: Vec<S>) -> Self {
         Self::Node {
             location,
+            allocation_id: None,
+            external: RwLock::new(None),
+            transform: RwLock::new(Transform::default()),
+            local: RwLock::new(Transform::default()),
+            visible: AtomicBool::new(true),
             sub_handles,
         }
     }
 
+    /// Creates a node whose location lies in the allocation `allocation_id` instead of the
+    /// allocation of the root, e.g. in another buffer of a [`crate::BufferPool`].
+    ///
+    /// Children without an allocation of their own lie in the same allocation. The root
+    /// doesn't reach that allocation, so the node has to be bound to its handle with
+    /// [`Self::bind_allocation`] before it or one of its parents can be transformed.
+    pub fn create_node_in(
+        allocation_id: &str,
+        location: BufferLocation,
+        sub_handles: Vec<S>,
+    ) -> Self {
+        Self::Node {
+            location,
+            allocation_id: Some(allocation_id.to_string()),
+            external: RwLock::new(None),
+            transform: RwLock::new(Transform::default()),
+            local: RwLock::new(Transform::default()),
+            visible: AtomicBool::new(true),
+            sub_handles,
+        }
+    }
+
+    pub fn create_leaf(location: BufferLocation) -> Self {
+        Self::Leaf {
+            location,
+            allocation_id: None,
+            external: RwLock::new(None),
+            transform: RwLock::new(Transform::default()),
+            local: RwLock::new(Transform::default()),
+            visible: AtomicBool::new(true),
+        }
+    }
+
+    /// Creates a leaf whose location lies in the allocation `allocation_id`, see
+    /// [`Self::create_node_in`].
+    pub fn create_leaf_in(allocation_id: &str, location: BufferLocation) -> Self {
+        Self::Leaf {
+            location,
+            allocation_id: Some(allocation_id.to_string()),
+            external: RwLock::new(None),
+            transform: RwLock::new(Transform::default()),
+            local: RwLock::new(Transform::default()),
+            visible: AtomicBool::new(true),
+        }
+    }
+
+    /// The allocation this model lies in.
+    ///
+    /// That's the allocation of the handle for an awake root, nodes and leaves only know it
+    /// if they were created in an external allocation, otherwise they share the one of
+    /// their root.
+    pub fn allocation_id(&self) -> Option<BufferAllocationID> {
+        match self {
+            Self::Root { state, .. } => match &*state.read() {
+                ModelState::Awake(handle) => Some(handle.id().clone()),
+                _ => None,
+            },
+            Self::Node { allocation_id, .. } | Self::Leaf { allocation_id, .. } => {
+                allocation_id.clone()
+            }
+        }
+    }
+
+    /// Binds a node or leaf created in an allocation of its own to the `handle` of that
+    /// allocation, transforms move its location through the handle.
+    ///
+    /// Fails for roots, for models lying in the allocation of their root, if the handle
+    /// belongs to another allocation or if the location doesn't fit into it.
+    pub fn bind_allocation(&self, handle: Arc<H>) -> Result<(), RetherError> {
+        match self {
+            Self::Root { .. } => Err(RetherError::InvalidState(
+                "A root is bound to its allocation by waking it",
+            )),
+            Self::Node {
+                location,
+                allocation_id,
+                external,
+                ..
+            }
+            | Self::Leaf {
+                location,
+                allocation_id,
+                external,
+                ..
+            } => {
+                match allocation_id {
+                    Some(id) if id == handle.id() => {}
+                    Some(_) => {
+                        return Err(RetherError::InvalidState(
+                            "The handle belongs to another allocation",
+                        ))
+                    }
+                    None => {
+                        return Err(RetherError::InvalidState(
+                            "The model lies in the allocation of its root",
+                        ))
+                    }
+                }
+
+                if location.offset + location.size > handle.size() {
+                    return Err(RetherError::ExceedsAllocation {
+                        id: handle.id().clone(),
+                        size: location.offset + location.size,
+                        allocation_size: handle.size(),
+                    });
+                }
+
+                *external.write() = Some(handle);
+
+                Ok(())
+            }
+        }
+    }
+
+    // sends the action over the location of a model in an allocation of its own, the
+    // locations in the allocation of the root are already moved by the root
+    fn send_external(
+        &self,
+        mod_action: impl FnOnce() -> FnModifyData<T>,
+    ) -> Result<(), RetherError> {
+        match self {
+            Self::Node {
+                location,
+                allocation_id: Some(_),
+                external,
+                ..
+            }
+            | Self::Leaf {
+                location,
+                allocation_id: Some(_),
+                external,
+                ..
+            } => match &*external.read() {
+                Some(handle) => handle.send_action(ModifyAction::new(
+                    location.offset,
+                    location.size,
+                    mod_action(),
+                )),
+                None => Err(RetherError::InvalidState(
+                    "Bind the allocation of the model before transforming it",
+                )),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn visible_flag(&self) -> &AtomicBool {
+        match self {
+            Self::Root { visible, .. }
+            | Self::Node { visible, .. }
+            | Self::Leaf { visible, .. } => visible,
+        }
+    }
+
+    pub(super) fn transform_lock(&self) -> &RwLock<Transform> {
+        match self {
+            Self::Root { transform, .. }
+            | Self::Node { transform, .. }
+            | Self::Leaf { transform, .. } => transform,
+        }
+    }
+
     pub fn sub_handles(&self) -> Option<&Vec<S>> {
         match self {
             Self::Root { sub_handles, .. } => Some(sub_handles),
@@ -74,85 +279,255 @@ where
     }
 }
 
+impl<S: VisibilityModel, T, H: AllocHandle<T>> VisibilityModel for TreeModel<S, T, H> {
+    fn set_visible_recursive(&self, visible: bool) {
+        self.visible_flag().store(visible, Ordering::Relaxed);
+
+        if let Some(sub_handles) = self.sub_handles() {
+            for handle in sub_handles.iter() {
+                handle.set_visible_recursive(visible);
+            }
+        }
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible_flag().load(Ordering::Relaxed)
+    }
+
+    /// The visible ranges in the allocation of this model, for nodes and leaves created in
+    /// an allocation of their own that's the one of their `allocation_id`.
+    fn visible_locations(&self) -> Vec<BufferLocation> {
+        let own = match self {
+            Self::Root { .. } => None,
+            Self::Node { allocation_id, .. } | Self::Leaf { allocation_id, .. } => {
+                allocation_id.clone()
+            }
+        };
+
+        self.visible_allocations().remove(&own).unwrap_or_default()
+    }
+
+    fn visible_allocations(&self) -> HashMap<Option<BufferAllocationID>, Vec<BufferLocation>> {
+        if !self.is_visible() {
+            return HashMap::new();
+        }
+
+        let (own, range) = self.own_range();
+
+        let mut allocations: HashMap<_, Vec<BufferLocation>> = HashMap::new();
+        let mut covered = Vec::new();
+
+        for handle in self.sub_handles().into_iter().flatten() {
+            // children without an allocation of their own share the one of this model
+            for (allocation_id, locations) in handle.occupied_allocations() {
+                if allocation_id.or_else(|| own.clone()) == own {
+                    covered.extend(locations);
+                }
+            }
+
+            for (allocation_id, locations) in handle.visible_allocations() {
+                allocations
+                    .entry(allocation_id.or_else(|| own.clone()))
+                    .or_default()
+                    .extend(locations);
+            }
+        }
+
+        // the part of the own range no child covers, hidden children included
+        allocations
+            .entry(own)
+            .or_default()
+            .extend(uncovered(range, covered));
+
+        allocations.retain(|_, locations| !locations.is_empty());
+
+        for locations in allocations.values_mut() {
+            locations.sort_by_key(|location| location.offset);
+
+            // merge adjacent ranges so they can be drawn at once
+            locations.dedup_by(|next, previous| {
+                if previous.offset + previous.size == next.offset {
+                    previous.size += next.size;
+                    true
+                } else {
+                    false
+                }
+            });
+        }
+
+        allocations
+    }
+
+    fn occupied_allocations(&self) -> HashMap<Option<BufferAllocationID>, Vec<BufferLocation>> {
+        let (own, range) = self.own_range();
+
+        HashMap::from([(own, vec![range])])
+    }
+}
+
+impl<S, T, H: AllocHandle<T>> TreeModel<S, T, H> {
+    // the allocation and the range of this model, the whole geometry for a root
+    fn own_range(&self) -> (Option<BufferAllocationID>, BufferLocation) {
+        match self {
+            Self::Root { state, .. } => (
+                None,
+                BufferLocation {
+                    offset: 0,
+                    size: state.read().vertex_count(),
+                },
+            ),
+            Self::Node {
+                location,
+                allocation_id,
+                ..
+            }
+            | Self::Leaf {
+                location,
+                allocation_id,
+                ..
+            } => (allocation_id.clone(), location.clone()),
+        }
+    }
+}
+
+// the parts of `range` that none of the `covered` locations overlap
+fn uncovered(range: BufferLocation, mut covered: Vec<BufferLocation>) -> Vec<BufferLocation> {
+    covered.sort_by_key(|location| location.offset);
+
+    let end = range.offset + range.size;
+    let mut start = range.offset;
+    let mut gaps = Vec::new();
+
+    for location in covered {
+        if location.offset > start {
+            gaps.push(BufferLocation {
+                offset: start,
+                size: location.offset.min(end) - start,
+            });
+        }
+
+        start = start.max(location.offset + location.size);
+
+        if start >= end {
+            return gaps;
+        }
+    }
+
+    gaps.push(BufferLocation {
+        offset: start,
+        size: end - start,
+    });
+
+    gaps
+}
+
 impl<S: TranslateModel + RotateModel + ScaleModel> Model<Vertex, StaticAllocHandle<Vertex>>
     for TreeModel<S, Vertex, StaticAllocHandle<Vertex>>
 {
-    fn wake(&self, handle: std::sync::Arc<StaticAllocHandle<Vertex>>) {
+    fn wake(&self, handle: std::sync::Arc<StaticAllocHandle<Vertex>>) -> Result<(), RetherError> {
         match self {
-            Self::Root { state, .. } => {
-                *state.write() = ModelState::Awake(handle);
+            Self::Root { state, bounds, .. } => {
+                let mut state = state.write();
+
+                check_not_indexed(&*state)?;
+                wake_bounds(&*state, bounds);
+                *state = ModelState::Awake(handle);
+
+                Ok(())
             }
             Self::Node { .. } | Self::Leaf { .. } => {
-                panic!("Cannot wake a node or leaf");
+                Err(RetherError::InvalidState("Cannot wake a node or leaf"))
             }
         }
     }
 
-    fn transform(&self) -> Transform {
+    /// The bounds of the whole tree, None for nodes and leaves.
+    fn bounds(&self) -> Option<Aabb> {
         match self {
-            Self::Root { transform, .. } => transform.read().clone(),
-            Self::Node { .. } | Self::Leaf { .. } => {
-                panic!("Cannot get transform from node or leaf")
-            }
+            Self::Root { state, bounds, .. } => model_bounds(&*state.read(), bounds),
+            Self::Node { .. } | Self::Leaf { .. } => None,
         }
     }
 
-    fn state(&self) -> &RwLock<ModelState<Vertex, StaticAllocHandle<Vertex>>> {
+    fn transform(&self) -> Transform {
+        self.transform_lock().read().clone()
+    }
+
+    fn state(&self) -> Result<&RwLock<ModelState<Vertex, StaticAllocHandle<Vertex>>>, RetherError> {
         match self {
-            Self::Root { state, .. } => state,
-            Self::Node { .. } | Self::Leaf { .. } => panic!("Cannot get state from node or leaf"),
+            Self::Root { state, .. } => Ok(state),
+            Self::Node { .. } | Self::Leaf { .. } => Err(RetherError::InvalidState(
+                "Cannot get state from node or leaf",
+            )),
         }
     }
 
-    fn destroy(&self) {
-        panic!("Static handle cannot be destroyed");
+    fn destroy(&self) -> Result<(), RetherError> {
+        Err(RetherError::InvalidState(
+            "Static handle cannot be destroyed",
+        ))
     }
 }
 
 impl<S: TranslateModel + RotateModel + ScaleModel> Model<Vertex, DynamicAllocHandle<Vertex>>
     for TreeModel<S, Vertex, DynamicAllocHandle<Vertex>>
 {
-    fn wake(&self, handle: std::sync::Arc<DynamicAllocHandle<Vertex>>) {
+    fn wake(&self, handle: std::sync::Arc<DynamicAllocHandle<Vertex>>) -> Result<(), RetherError> {
         match self {
-            Self::Root { state, .. } => {
-                *state.write() = ModelState::Awake(handle);
+            Self::Root { state, bounds, .. } => {
+                let mut state = state.write();
+
+                check_not_indexed(&*state)?;
+                wake_bounds(&*state, bounds);
+                *state = ModelState::Awake(handle);
+
+                Ok(())
             }
             Self::Node { .. } | Self::Leaf { .. } => {
-                panic!("Cannot wake a node or leaf");
+                Err(RetherError::InvalidState("Cannot wake a node or leaf"))
             }
         }
     }
 
-    fn transform(&self) -> Transform {
+    /// The bounds of the whole tree, None for nodes and leaves.
+    fn bounds(&self) -> Option<Aabb> {
         match self {
-            Self::Root { transform, .. } => transform.read().clone(),
-            Self::Node { .. } | Self::Leaf { .. } => {
-                panic!("Cannot get transform from node or leaf")
-            }
+            Self::Root { state, bounds, .. } => model_bounds(&*state.read(), bounds),
+            Self::Node { .. } | Self::Leaf { .. } => None,
         }
     }
 
-    fn state(&self) -> &RwLock<ModelState<Vertex, DynamicAllocHandle<Vertex>>> {
+    fn transform(&self) -> Transform {
+        self.transform_lock().read().clone()
+    }
+
+    fn state(
+        &self,
+    ) -> Result<&RwLock<ModelState<Vertex, DynamicAllocHandle<Vertex>>>, RetherError> {
         match self {
-            Self::Root { state, .. } => state,
-            Self::Node { .. } | Self::Leaf { .. } => panic!("Cannot get state from node or leaf"),
+            Self::Root { state, .. } => Ok(state),
+            Self::Node { .. } | Self::Leaf { .. } => Err(RetherError::InvalidState(
+                "Cannot get state from node or leaf",
+            )),
         }
     }
 
-    fn destroy(&self) {
+    fn destroy(&self) -> Result<(), RetherError> {
         match self {
             Self::Root { state, .. } => {
                 match &*state.read() {
                     ModelState::Awake(handle) => {
-                        handle.destroy();
+                        handle.destroy()?;
                     }
-                    _ => panic!("Cannot destroy a dead handle"),
+                    _ => return Err(RetherError::InvalidState("Cannot destroy a dead handle")),
                 }
 
                 *state.write() = ModelState::Destroyed;
+
+                Ok(())
             }
             Self::Node { .. } | Self::Leaf { .. } => {
-                panic!("Cannot destroy a node or leaf");
+                Err(RetherError::InvalidState("Cannot destroy a node or leaf"))
             }
         }
     }
@@ -160,163 +535,634 @@ impl<S: TranslateModel + RotateModel + ScaleModel> Model<Vertex, DynamicAllocHan
     fn is_destroyed(&self) -> bool {
         match self {
             Self::Root { state, .. } => state.read().is_destroyed(),
+            // a node is only destroyed along with the allocation of its own it's bound to
+            Self::Node { external, .. } | Self::Leaf { external, .. } => external
+                .read()
+                .as_ref()
+                .is_some_and(|handle| handle.is_destroyed()),
+        }
+    }
+}
+
+impl<S, H: AllocHandle<Vertex>> IndexedModel<Vertex, H> for TreeModel<S, Vertex, H>
+where
+    Self: Model<Vertex, H>,
+{
+    fn wake(
+        &self,
+        handle: std::sync::Arc<H>,
+        index_handle: std::sync::Arc<H>,
+    ) -> Result<(), RetherError> {
+        match self {
+            Self::Root { state, bounds, .. } => {
+                wake_indexed(state, handle, &index_handle, |state| {
+                    wake_bounds(state, bounds)
+                })
+            }
             Self::Node { .. } | Self::Leaf { .. } => {
-                panic!("Cannot check if a node or leaf is destroyed");
+                Err(RetherError::InvalidState("Cannot wake a node or leaf"))
             }
         }
     }
 }
 
 impl<S: TranslateModel, T: Translate, H: AllocHandle<T>> TranslateModel for TreeModel<S, T, H> {
-    fn translate(&self, translation: glam::Vec3) {
+    fn translate(&self, translation: glam::Vec3) -> Result<(), RetherError> {
         match self {
             Self::Root {
                 state,
                 sub_handles,
                 transform,
+                bounds,
+                ..
             } => {
-                transform.write().translate(translation);
-                match &mut *state.write() {
+                // the state stays locked until the children are moved, so concurrent
+                // transforms reach the allocation and the children in the same order
+                let mut state = state.write();
+
+                match &mut *state {
                     ModelState::Awake(handle) => {
                         let mod_action =
                             Box::new(move |data: &mut [T]| data.translate(translation));
 
-                        let action = ModifyAction::new(0, handle.size(), mod_action);
-
-                        handle.send_action(action).expect("Failed to send action");
-
-                        for handle in sub_handles.iter() {
-                            handle.translate(translation);
-                        }
+                        handle.send_action(ModifyAction::new(0, handle.size(), mod_action))?;
+                        move_bounds(bounds, |corner| corner + translation);
                     }
-                    ModelState::Dormant(geometry) => {
-                        geometry.translate(translation);
+                    ModelState::Dormant(geometry) => geometry.translate(translation),
+                    ModelState::DormantIndexed(geometry) => geometry.translate(translation),
+                    _ => return Err(RetherError::InvalidState("Cannot translate a dead handle")),
+                }
 
-                        for handle in sub_handles.iter() {
-                            handle.translate(translation);
-                        }
-                    }
-                    ModelState::DormantIndexed(geometry) => {
-                        geometry.translate(translation);
+                transform.write().translate(translation);
 
-                        for handle in sub_handles.iter() {
-                            handle.translate(translation);
-                        }
-                    }
-                    _ => panic!("Cannot translate a dead handle"),
+                for handle in sub_handles.iter() {
+                    handle.translate_with_parent(translation)?;
                 }
+
+                Ok(())
             }
-            Self::Node { sub_handles, .. } => {
-                for handle in sub_handles.iter() {
-                    handle.translate(translation);
+            Self::Node { local, .. } | Self::Leaf { local, .. } => {
+                let mut local = local.write();
+
+                self.translate_with_parent(translation)?;
+                local.translate(translation);
+
+                Ok(())
+            }
+        }
+    }
+
+    fn translate_with_parent(&self, translation: glam::Vec3) -> Result<(), RetherError> {
+        match self {
+            Self::Root { .. } => self.translate(translation),
+            Self::Node { transform, .. } | Self::Leaf { transform, .. } => {
+                {
+                    let mut transform = transform.write();
+
+                    self.send_external(|| {
+                        Box::new(move |data: &mut [T]| data.translate(translation))
+                    })?;
+                    transform.translate(translation);
+                }
+
+                for handle in self.sub_handles().into_iter().flatten() {
+                    handle.translate_with_parent(translation)?;
                 }
+
+                Ok(())
             }
-            _ => {}
         }
     }
 }
 
 impl<S: RotateModel, H: AllocHandle<Vertex>> RotateModel for TreeModel<S, Vertex, H> {
-    fn rotate(&self, rotation: glam::Quat, center: Option<glam::Vec3>) {
+    fn rotate(&self, rotation: glam::Quat, center: Option<glam::Vec3>) -> Result<(), RetherError> {
         match self {
             Self::Root {
                 state,
                 sub_handles,
                 transform,
+                bounds,
                 ..
             } => {
-                transform
-                    .write()
-                    .rotate(rotation, center.unwrap_or(Vec3::ZERO));
-                match &mut *state.write() {
-                    ModelState::Awake(handle) => {
-                        let mod_action = Box::new(move |data: &mut [Vertex]| {
-                            //data.rotate(rotation);
-                            VertexRotator::new(data).rotate(rotation, center.unwrap_or(Vec3::ZERO));
-                        });
+                // see `translate`
+                let mut state = state.write();
 
-                        let action = ModifyAction::new(0, handle.size(), mod_action);
+                match &mut *state {
+                    ModelState::Awake(handle) => {
+                        let action =
+                            ModifyAction::new(0, handle.size(), rotate_action(rotation, center));
 
-                        handle.send_action(action).expect("Failed to send action");
+                        handle.send_action(action)?;
+                        move_bounds(bounds, |corner| {
+                            let center = center.unwrap_or(Vec3::ZERO);
 
-                        for handle in sub_handles.iter() {
-                            handle.rotate(rotation, center);
-                        }
+                            rotation * (corner - center) + center
+                        });
                     }
                     ModelState::Dormant(geometry) => {
-                        geometry.rotate(rotation, center.unwrap_or(Vec3::ZERO));
-
-                        for handle in sub_handles.iter() {
-                            handle.rotate(rotation, center);
-                        }
+                        geometry.rotate(rotation, center.unwrap_or(Vec3::ZERO))
                     }
                     ModelState::DormantIndexed(geometry) => {
-                        geometry.rotate(rotation, center.unwrap_or(Vec3::ZERO));
-
-                        for handle in sub_handles.iter() {
-                            handle.rotate(rotation, center);
-                        }
+                        geometry.rotate(rotation, center.unwrap_or(Vec3::ZERO))
                     }
-                    _ => panic!("Cannot rotate a dead handle"),
+                    _ => return Err(RetherError::InvalidState("Cannot rotate a dead handle")),
                 }
-            }
-            Self::Node { sub_handles, .. } => {
+
+                transform
+                    .write()
+                    .rotate(rotation, center.unwrap_or(Vec3::ZERO));
+
                 for handle in sub_handles.iter() {
-                    handle.rotate(rotation, center);
+                    handle.rotate_with_parent(rotation, center)?;
                 }
+
+                Ok(())
+            }
+            Self::Node { local, .. } | Self::Leaf { local, .. } => {
+                let mut local = local.write();
+
+                self.rotate_with_parent(rotation, center)?;
+                local.rotate(rotation, center.unwrap_or(Vec3::ZERO));
+
+                Ok(())
+            }
+        }
+    }
+
+    fn rotate_with_parent(
+        &self,
+        rotation: glam::Quat,
+        center: Option<glam::Vec3>,
+    ) -> Result<(), RetherError> {
+        match self {
+            Self::Root { .. } => self.rotate(rotation, center),
+            Self::Node { transform, .. } | Self::Leaf { transform, .. } => {
+                {
+                    let mut transform = transform.write();
+
+                    self.send_external(|| rotate_action(rotation, center))?;
+                    transform.rotate(rotation, center.unwrap_or(Vec3::ZERO));
+                }
+
+                for handle in self.sub_handles().into_iter().flatten() {
+                    handle.rotate_with_parent(rotation, center)?;
+                }
+
+                Ok(())
             }
-            _ => {}
         }
     }
 }
 
 impl<S: ScaleModel, H: AllocHandle<Vertex>> ScaleModel for TreeModel<S, Vertex, H> {
-    fn scale(&self, scale: glam::Vec3, center: Option<glam::Vec3>) {
+    fn scale(&self, scale: glam::Vec3, center: Option<glam::Vec3>) -> Result<(), RetherError> {
         match self {
             Self::Root {
                 state,
                 sub_handles,
                 transform,
+                bounds,
                 ..
             } => {
-                transform.write().scale(scale);
-                match &mut *state.write() {
-                    ModelState::Awake(handle) => {
-                        let mod_action = Box::new(move |data: &mut [Vertex]| {
-                            VertexScaler::new(data, center.unwrap_or(Vec3::ZERO)).scale(scale);
-                        });
+                // see `translate`
+                let mut state = state.write();
 
-                        let action = ModifyAction::new(0, handle.size(), mod_action);
+                match &mut *state {
+                    ModelState::Awake(handle) => {
+                        let action =
+                            ModifyAction::new(0, handle.size(), scale_action(scale, center));
 
-                        handle.send_action(action).expect("Failed to send action");
+                        handle.send_action(action)?;
+                        move_bounds(bounds, |corner| {
+                            let center = center.unwrap_or(Vec3::ZERO);
 
-                        for handle in sub_handles.iter() {
-                            handle.scale(scale, center);
-                        }
+                            (corner - center) * scale + center
+                        });
                     }
-                    ModelState::Dormant(geometry) => {
-                        geometry.scale(scale);
+                    ModelState::Dormant(geometry) => geometry.scale(scale),
+                    ModelState::DormantIndexed(geometry) => geometry.scale(scale),
+                    _ => return Err(RetherError::InvalidState("Cannot scale a dead handle")),
+                }
 
-                        for handle in sub_handles.iter() {
-                            handle.scale(scale, center);
-                        }
-                    }
-                    ModelState::DormantIndexed(geometry) => {
-                        geometry.scale(scale);
+                transform.write().scale(scale);
 
-                        for handle in sub_handles.iter() {
-                            handle.scale(scale, center);
-                        }
-                    }
-                    _ => panic!("Cannot scale a dead handle"),
+                for handle in sub_handles.iter() {
+                    handle.scale_with_parent(scale, center)?;
                 }
+
+                Ok(())
             }
-            Self::Node { sub_handles, .. } => {
-                for handle in sub_handles.iter() {
-                    handle.scale(scale, center);
+            Self::Node { local, .. } | Self::Leaf { local, .. } => {
+                let mut local = local.write();
+
+                self.scale_with_parent(scale, center)?;
+                local.scale(scale);
+
+                Ok(())
+            }
+        }
+    }
+
+    fn scale_with_parent(
+        &self,
+        scale: glam::Vec3,
+        center: Option<glam::Vec3>,
+    ) -> Result<(), RetherError> {
+        match self {
+            Self::Root { .. } => self.scale(scale, center),
+            Self::Node { transform, .. } | Self::Leaf { transform, .. } => {
+                {
+                    let mut transform = transform.write();
+
+                    self.send_external(|| scale_action(scale, center))?;
+                    transform.scale(scale);
+                }
+
+                for handle in self.sub_handles().into_iter().flatten() {
+                    handle.scale_with_parent(scale, center)?;
                 }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+fn rotate_action(rotation: glam::Quat, center: Option<Vec3>) -> FnModifyData<Vertex> {
+    Box::new(move |data: &mut [Vertex]| {
+        VertexRotator::new(data).rotate(rotation, center.unwrap_or(Vec3::ZERO));
+    })
+}
+
+fn scale_action(scale: glam::Vec3, center: Option<Vec3>) -> FnModifyData<Vertex> {
+    Box::new(move |data: &mut [Vertex]| {
+        VertexScaler::new(data, center.unwrap_or(Vec3::ZERO)).scale(scale);
+    })
+}
+
+impl<T, H: AllocHandle<T>> TreeModel<SubTree<T, H>, T, H> {
+    /// The transform of the node reached by following the child indices of `path` relative
+    /// to its parent, the empty path is this model itself. None if an index doesn't exist.
+    ///
+    /// Only the transforms applied to the node itself are included, see
+    /// [`Self::world_transform`] for the transform including the ones of its parents.
+    pub fn node_transform(&self, path: &[usize]) -> Option<Transform> {
+        let node = self.node(path)?;
+
+        Some(
+            node.local_lock()
+                .unwrap_or(node.transform_lock())
+                .read()
+                .clone(),
+        )
+    }
+
+    /// Like [`Self::node_transform`], but including the transforms its parents passed on.
+    pub fn world_transform(&self, path: &[usize]) -> Option<Transform> {
+        Some(self.node(path)?.transform_lock().read().clone())
+    }
+
+    fn node(&self, path: &[usize]) -> Option<&Self> {
+        match path.split_first() {
+            None => Some(self),
+            Some((index, rest)) => self.sub_handles()?.get(*index)?.node(rest),
+        }
+    }
+
+    // the local transform of nodes and leaves, a root has no parent to be relative to
+    fn local_lock(&self) -> Option<&RwLock<Transform>> {
+        match self {
+            Self::Root { .. } => None,
+            Self::Node { local, .. } | Self::Leaf { local, .. } => Some(local),
+        }
+    }
+
+    // makes the local transforms of the subtree relative to `parent`, keeping the world
+    // transforms as they are
+    pub(super) fn relate_to(&self, parent: &Transform) {
+        let world = self.transform_lock().read().clone();
+
+        if let Some(local) = self.local_lock() {
+            *local.write() = world.relative_to(parent);
+        }
+
+        for handle in self.sub_handles().into_iter().flatten() {
+            handle.relate_to(&world);
+        }
+    }
+}
+
+impl<H: AllocHandle<Vertex>> TreeModel<SubTree<Vertex, H>, Vertex, H> {
+    /// Makes `child` a child of this root or node, so transforming this model transforms the
+    /// child as well.
+    ///
+    /// With `keep_world_transform` the child stays where it is. Otherwise its placement is
+    /// taken as relative to this model and it's moved by the transform of this model, which
+    /// is only possible for children created in an allocation of their own, see
+    /// [`Self::create_node_in`]. Children without one are part of the geometry of this
+    /// model and are already placed by it.
+    ///
+    /// The location of a child without an allocation of its own has to lie inside the
+    /// range of this model. The local transform of the child is made relative to this model.
+    pub fn attach_child(
+        &mut self,
+        child: SubTree<Vertex, H>,
+        keep_world_transform: bool,
+    ) -> Result<(), RetherError> {
+        let range = match self {
+            Self::Root { state, .. } => BufferLocation {
+                offset: 0,
+                size: state.read().vertex_count(),
+            },
+            Self::Node { location, .. } => location.clone(),
+            Self::Leaf { .. } => {
+                return Err(RetherError::InvalidState("Cannot attach a child to a leaf"))
+            }
+        };
+
+        let location = match &*child {
+            TreeModel::Node { location, .. } | TreeModel::Leaf { location, .. } => location,
+            TreeModel::Root { .. } => {
+                return Err(RetherError::InvalidState("Cannot attach a root as a child"))
+            }
+        };
+
+        match child.allocation_id() {
+            None if location.offset < range.offset
+                || location.offset + location.size > range.offset + range.size =>
+            {
+                return Err(RetherError::InvalidState(
+                    "The child has to lie inside the range of its parent",
+                ));
+            }
+            // its location would be moved by this model and by the child itself
+            Some(id) if self.allocation_id().as_ref() == Some(&id) => {
+                return Err(RetherError::InvalidState(
+                    "A child in the allocation of its parent is created without an allocation id",
+                ));
             }
             _ => {}
         }
+
+        if !keep_world_transform {
+            self.move_child(&child, false)?;
+        }
+
+        child.relate_to(&self.transform_lock().read());
+
+        match self {
+            Self::Root { sub_handles, .. } | Self::Node { sub_handles, .. } => {
+                sub_handles.push(child);
+            }
+            Self::Leaf { .. } => unreachable!("Checked above"),
+        }
+
+        Ok(())
+    }
+
+    /// Removes the child at `index` and hands it back, transforms of this model no longer
+    /// reach it.
+    ///
+    /// With `keep_world_transform` the child stays where it is, otherwise the transform of
+    /// this model is undone on it, see [`Self::attach_child`]. A child without an
+    /// allocation of its own stays part of the geometry of its root, so the root keeps
+    /// moving its location, only its transform stops following. The local transform of the
+    /// detached child is its world transform.
+    pub fn detach_child(
+        &mut self,
+        index: usize,
+        keep_world_transform: bool,
+    ) -> Result<SubTree<Vertex, H>, RetherError> {
+        let child = match self.sub_handles() {
+            Some(sub_handles) if index < sub_handles.len() => &sub_handles[index],
+            _ => return Err(RetherError::InvalidState("There is no child at this index")),
+        };
+
+        if !keep_world_transform {
+            self.move_child(child, true)?;
+        }
+
+        let child = match self {
+            Self::Root { sub_handles, .. } | Self::Node { sub_handles, .. } => {
+                sub_handles.remove(index)
+            }
+            Self::Leaf { .. } => unreachable!("Leaves have no children"),
+        };
+
+        // without a parent the world transform is the local one
+        child.relate_to(&Transform::default());
+
+        Ok(child)
+    }
+
+    // moves `child` by the transform of this model, or by its inverse
+    fn move_child(&self, child: &SubTree<Vertex, H>, inverse: bool) -> Result<(), RetherError> {
+        if child.allocation_id().is_none() {
+            return Err(RetherError::InvalidState(
+                "A child in the allocation of its parent is placed by it, keep its world transform",
+            ));
+        }
+
+        let transform = self.transform_lock().read().clone();
+
+        if inverse {
+            if transform.translation != Vec3::ZERO {
+                child.translate_with_parent(-transform.translation)?;
+            }
+
+            if transform.rotation != glam::Quat::IDENTITY {
+                child.rotate_with_parent(transform.rotation.inverse(), None)?;
+            }
+
+            if transform.scale != Vec3::ONE {
+                child.scale_with_parent(transform.scale.recip(), None)?;
+            }
+        } else {
+            if transform.scale != Vec3::ONE {
+                child.scale_with_parent(transform.scale, None)?;
+            }
+
+            if transform.rotation != glam::Quat::IDENTITY {
+                child.rotate_with_parent(transform.rotation, None)?;
+            }
+
+            if transform.translation != Vec3::ZERO {
+                child.translate_with_parent(transform.translation)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A node or leaf of a tree built by [`TreeModelBuilder`].
+///
+/// Wraps the tree so its children can have the same type as the tree itself.
+#[derive(Debug)]
+pub struct SubTree<T, H: AllocHandle<T>>(TreeModel<SubTree<T, H>, T, H>);
+
+impl<T, H: AllocHandle<T>> From<TreeModel<SubTree<T, H>, T, H>> for SubTree<T, H> {
+    fn from(tree: TreeModel<SubTree<T, H>, T, H>) -> Self {
+        Self(tree)
+    }
+}
+
+impl<T, H: AllocHandle<T>> Deref for SubTree<T, H> {
+    type Target = TreeModel<SubTree<T, H>, T, H>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: Translate, H: AllocHandle<T>> TranslateModel for SubTree<T, H> {
+    fn translate(&self, translation: glam::Vec3) -> Result<(), RetherError> {
+        self.0.translate(translation)
+    }
+
+    fn translate_with_parent(&self, translation: glam::Vec3) -> Result<(), RetherError> {
+        self.0.translate_with_parent(translation)
+    }
+}
+
+impl<H: AllocHandle<Vertex>> RotateModel for SubTree<Vertex, H> {
+    fn rotate(&self, rotation: glam::Quat, center: Option<glam::Vec3>) -> Result<(), RetherError> {
+        self.0.rotate(rotation, center)
+    }
+
+    fn rotate_with_parent(
+        &self,
+        rotation: glam::Quat,
+        center: Option<glam::Vec3>,
+    ) -> Result<(), RetherError> {
+        self.0.rotate_with_parent(rotation, center)
+    }
+}
+
+impl<H: AllocHandle<Vertex>> ScaleModel for SubTree<Vertex, H> {
+    fn scale(&self, scale: glam::Vec3, center: Option<glam::Vec3>) -> Result<(), RetherError> {
+        self.0.scale(scale, center)
+    }
+
+    fn scale_with_parent(
+        &self,
+        scale: glam::Vec3,
+        center: Option<glam::Vec3>,
+    ) -> Result<(), RetherError> {
+        self.0.scale_with_parent(scale, center)
+    }
+}
+
+impl<T, H: AllocHandle<T>> VisibilityModel for SubTree<T, H> {
+    fn set_visible_recursive(&self, visible: bool) {
+        self.0.set_visible_recursive(visible)
+    }
+
+    fn is_visible(&self) -> bool {
+        self.0.is_visible()
+    }
+
+    fn visible_locations(&self) -> Vec<BufferLocation> {
+        self.0.visible_locations()
+    }
+
+    fn visible_allocations(&self) -> HashMap<Option<BufferAllocationID>, Vec<BufferLocation>> {
+        self.0.visible_allocations()
+    }
+
+    fn occupied_allocations(&self) -> HashMap<Option<BufferAllocationID>, Vec<BufferLocation>> {
+        self.0.occupied_allocations()
+    }
+}
+
+/// Builds a [`TreeModel`] by appending geometry, the buffer locations of the nodes
+/// are computed from the order the geometry is added in.
+#[derive(Debug)]
+pub struct TreeModelBuilder<T, H: AllocHandle<T>> {
+    geometry: SimpleGeometry<T>,
+    // start offset and children of every open group, the first one holds the children of the root
+    groups: Vec<(usize, Vec<SubTree<T, H>>)>,
+}
+
+impl<T: Clone, H: AllocHandle<T>> TreeModelBuilder<T, H> {
+    pub fn root(geometry: SimpleGeometry<T>) -> Self {
+        Self {
+            geometry,
+            groups: vec![(0, Vec::new())],
+        }
+    }
+
+    /// Appends the geometry as a leaf of the current group.
+    pub fn add_child(mut self, geometry: &SimpleGeometry<T>) -> Self {
+        let location = BufferLocation {
+            offset: self.geometry.data_len(),
+            size: geometry.data_len(),
+        };
+
+        self.geometry.expand(geometry);
+        self.current_group()
+            .push(SubTree(TreeModel::create_leaf(location)));
+
+        self
+    }
+
+    /// Opens a new group, children added until the matching [`Self::pop_group`] become
+    /// its children.
+    pub fn push_group(mut self) -> Self {
+        self.groups.push((self.geometry.data_len(), Vec::new()));
+
+        self
+    }
+
+    /// Closes the current group and adds it as a node to its parent, does nothing if
+    /// no group is open.
+    pub fn pop_group(mut self) -> Self {
+        if self.groups.len() > 1 {
+            let (offset, children) = self.groups.pop().expect("Checked above");
+
+            let location = BufferLocation {
+                offset,
+                size: self.geometry.data_len() - offset,
+            };
+
+            self.current_group()
+                .push(SubTree(TreeModel::create_node_with_models(
+                    location, children,
+                )));
+        }
+
+        self
+    }
+
+    /// Closes all open groups and creates the root.
+    pub fn build(mut self) -> TreeModel<SubTree<T, H>, T, H> {
+        while self.groups.len() > 1 {
+            self = self.pop_group();
+        }
+
+        let (_, children) = self.groups.pop().expect("The root group is never popped");
+
+        TreeModel::create_root_with_models(self.geometry, children)
+    }
+
+    fn current_group(&mut self) -> &mut Vec<SubTree<T, H>> {
+        &mut self
+            .groups
+            .last_mut()
+            .expect("The root group is never popped")
+            .1
+    }
+}
+
+impl<H: AllocHandle<Vertex>> TreeModelBuilder<Vertex, H> {
+    /// Like [`Self::add_child`], but bakes `transform` into a copy of the geometry first,
+    /// so the flattened tree keeps the placement of the child.
+    pub fn add_transformed_child(
+        self,
+        geometry: &SimpleGeometry<Vertex>,
+        transform: &Transform,
+    ) -> Self {
+        self.add_child(&geometry.transformed(transform))
     }
 }
@@ -1,7 +1,9 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use glam::Vec3;
 
+use crate::{alloc::BufferAllocationID, Aabb};
+
 use super::{
     queue::{HitBoxQueueEntry, HitboxQueue},
     ray::Ray,
@@ -12,22 +14,63 @@ pub trait Hitbox: std::fmt::Debug + Send + Sync {
     fn expand_hitbox(&mut self, _box: &dyn Hitbox);
     fn set_enabled(&mut self, enabled: bool);
     fn enabled(&self) -> bool;
-    fn get_min(&self) -> Vec3;
-    fn get_max(&self) -> Vec3;
+    fn aabb(&self) -> Aabb;
 }
 
 pub trait HitboxNode<M: HitboxNode<M>> {
     fn check_hit(&self, ray: &Ray) -> Option<f32>;
     fn inner_nodes(&self) -> &[M];
-    fn get_min(&self) -> Vec3;
-    fn get_max(&self) -> Vec3;
+    fn aabb(&self) -> Aabb;
+
+    /// Distance from `point` to the node, zero if the point lies inside its bounds.
+    ///
+    /// Defaults to the distance to the bounding box, leaves with an exact shape should
+    /// override it.
+    fn distance_to_point(&self, point: Vec3) -> f32 {
+        let aabb = self.aabb();
+
+        point.clamp(aabb.min, aabb.max).distance(point)
+    }
+
+    /// The bounds of the node unioned with the bounds of its whole subtree.
+    ///
+    /// Parent nodes can use this to make sure their broad phase box encloses all children,
+    /// otherwise a ray could miss the parent while hitting one of its children.
+    fn subtree_aabb(&self) -> Aabb {
+        self.inner_nodes()
+            .iter()
+            .fold(self.aabb(), |aabb, node| aabb.union(&node.subtree_aabb()))
+    }
+
+    /// The allocation the node belongs to, used by [`HitboxRoot::raycast_subset`].
+    fn allocation_id(&self) -> Option<&BufferAllocationID> {
+        None
+    }
+
+    /// Whether the bounds are stale, e.g. because the model of the node moved.
+    ///
+    /// Nodes that never move can keep the default, see [`HitboxRoot::refit_dirty`].
+    fn is_dirty(&self) -> bool {
+        false
+    }
+
+    /// Recomputes the bounds of the node and clears the dirty flag.
+    ///
+    /// `children` is the union of the already refit bounds of the inner nodes, None for
+    /// leaves. Nodes have to use interior mutability, they are shared through `Arc`s.
+    fn refit(&self, _children: Option<Aabb>) {}
 }
 
 // Importing the Ray struct from the ray module in the super namespace
 // Function to check if a ray hits a hitbox node, returning an optional usize
 
 // Definition of the HitboxNode enum with Debug trait
+/// The entry point of a hitbox tree.
+///
+/// With the `serde` feature the root can be saved and reloaded to skip rebuilding a large
+/// tree, nodes shared between several `Arc`s are duplicated when deserializing.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HitboxRoot<M: HitboxNode<M>> {
     inner_hitboxes: Vec<Arc<M>>,
 }
@@ -41,22 +84,65 @@ impl<M: HitboxNode<M>> HitboxRoot<M> {
     }
 
     pub fn check_hit(&self, ray: &Ray) -> Option<&M> {
+        self.raycast(ray, None)
+    }
+
+    /// Finds the closest leaf hit by the ray.
+    ///
+    /// Hits farther away than `max_distance` are treated as misses, so their subtrees
+    /// are never visited.
+    pub fn raycast(&self, ray: &Ray, max_distance: Option<f32>) -> Option<&M> {
+        self.raycast_with(ray, max_distance, |_| true)
+    }
+
+    /// Finds the closest leaf hit by the ray that passes `predicate`.
+    ///
+    /// Leaves failing the predicate are skipped, so the traversal continues with the next
+    /// closest hit. Lets one tree serve different interaction modes, e.g. only picking
+    /// editable objects.
+    pub fn raycast_filtered(&self, ray: &Ray, predicate: impl Fn(&M) -> bool) -> Option<&M> {
+        self.raycast_with(ray, None, predicate)
+    }
+
+    /// Finds the closest leaf hit by the ray whose allocation is one of `ids`, e.g. to
+    /// snap only to the selected objects.
+    ///
+    /// Leaves without an [`HitboxNode::allocation_id`] are never hit.
+    pub fn raycast_subset(&self, ray: &Ray, ids: &HashSet<BufferAllocationID>) -> Option<&M> {
+        self.raycast_with(ray, None, |node| {
+            node.allocation_id().is_some_and(|id| ids.contains(id))
+        })
+    }
+
+    fn raycast_with(
+        &self,
+        ray: &Ray,
+        max_distance: Option<f32>,
+        predicate: impl Fn(&M) -> bool,
+    ) -> Option<&M> {
+        let in_range = |distance: f32| match max_distance {
+            Some(max_distance) => distance <= max_distance,
+            None => true,
+        };
+
         let mut queue = HitboxQueue::<M>::new(); // Creating a new HitboxQueue
 
         for hitbox in self.inner_hitboxes.iter() {
             let distance = hitbox.check_hit(ray);
-            if let Some(distance) = distance {
+            if let Some(distance) = distance.filter(|distance| in_range(*distance)) {
                 queue.push(HitBoxQueueEntry { hitbox, distance });
             }
         }
 
         while let Some(HitBoxQueueEntry { hitbox, .. }) = queue.pop() {
             if hitbox.inner_nodes().is_empty() {
-                return Some(hitbox);
+                if predicate(hitbox) {
+                    return Some(hitbox);
+                }
             } else {
                 for inner_hitbox in hitbox.inner_nodes() {
                     let distance = inner_hitbox.check_hit(ray);
-                    if let Some(distance) = distance {
+                    if let Some(distance) = distance.filter(|distance| in_range(*distance)) {
                         queue.push(HitBoxQueueEntry {
                             hitbox: inner_hitbox,
                             distance,
@@ -69,9 +155,79 @@ impl<M: HitboxNode<M>> HitboxRoot<M> {
         None
     }
 
+    /// Finds the leaf closest to `point` together with its distance.
+    pub fn nearest_to_point(&self, point: Vec3) -> Option<(&M, f32)> {
+        let mut queue = HitboxQueue::<M>::new();
+
+        for hitbox in self.inner_hitboxes.iter() {
+            queue.push(HitBoxQueueEntry {
+                hitbox,
+                distance: hitbox.distance_to_point(point),
+            });
+        }
+
+        // the distance of a node never exceeds the distance of its leaves,
+        // so the first leaf popped is the nearest one
+        while let Some(HitBoxQueueEntry { hitbox, distance }) = queue.pop() {
+            if hitbox.inner_nodes().is_empty() {
+                return Some((hitbox, distance));
+            } else {
+                for inner_hitbox in hitbox.inner_nodes() {
+                    queue.push(HitBoxQueueEntry {
+                        hitbox: inner_hitbox,
+                        distance: inner_hitbox.distance_to_point(point),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The combined bounds of all nodes, None if the root is empty.
+    pub fn aabb(&self) -> Option<Aabb> {
+        self.inner_hitboxes
+            .iter()
+            .map(|node| node.subtree_aabb())
+            .reduce(|a, b| a.union(&b))
+    }
+
     pub fn add_node(&mut self, node: Arc<M>) {
         self.inner_hitboxes.push(node);
     }
+
+    /// Refits the dirty nodes and all their parents bottom up, meant to be called once
+    /// per frame, e.g. after `Buffer::update`, instead of refitting on every transform.
+    ///
+    /// Returns the number of refit nodes.
+    pub fn refit_dirty(&self) -> usize {
+        self.inner_hitboxes
+            .iter()
+            .map(|node| refit_node(node.as_ref()))
+            .sum()
+    }
+}
+
+fn refit_node<M: HitboxNode<M>>(node: &M) -> usize {
+    let refit_children = node
+        .inner_nodes()
+        .iter()
+        .map(|inner| refit_node(inner))
+        .sum::<usize>();
+
+    if refit_children == 0 && !node.is_dirty() {
+        return 0;
+    }
+
+    let children = node
+        .inner_nodes()
+        .iter()
+        .map(|inner| inner.aabb())
+        .reduce(|a, b| a.union(&b));
+
+    node.refit(children);
+
+    refit_children + 1
 }
 
 /*
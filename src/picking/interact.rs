@@ -1,10 +1,59 @@
+use std::collections::HashMap;
+
 use glam::Vec2;
-use winit::{event::MouseButton, keyboard::KeyCode};
+
+/// A mouse or pointer button, independent of the windowing library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PointerButton {
+    Left,
+    Right,
+    Middle,
+    Back,
+    Forward,
+    Other(u16),
+}
+
+/// A keyboard key, independent of the windowing library.
+///
+/// Only the keys commonly used for interaction have their own variant, letters and digits
+/// are mapped to lowercase characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Character(char),
+    Shift,
+    Control,
+    Alt,
+    Super,
+    Escape,
+    Enter,
+    Space,
+    Tab,
+    Backspace,
+    Delete,
+    ArrowUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    Other,
+}
+
+/// The phase of a touch, independent of the windowing library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TouchPhase {
+    Started,
+    Moved,
+    Ended,
+    Cancelled,
+}
 
 #[derive(Debug, Clone)]
 pub enum Action {
-    Mouse(MouseButton),
-    Keyboard(KeyCode),
+    Mouse(PointerButton),
+    Keyboard(Key),
+    /// A single finger, identified by its touch id.
+    Touch(u64),
+    /// Two fingers moving towards or away from each other.
+    Pinch,
 }
 
 #[derive(Debug, Clone)]
@@ -29,3 +78,185 @@ pub trait InteractiveModel {
     fn drag(&self, event: DragEvent);
     fn scroll(&self, event: ScrollEvent);
 }
+
+/// A gesture recognized by [`TouchState`].
+#[derive(Debug, Clone)]
+pub enum TouchGesture {
+    Drag(DragEvent),
+    Scroll(ScrollEvent),
+}
+
+/// Tracks the active touches to turn touch events into gestures.
+///
+/// A single finger pans with [`DragEvent`]s, two fingers pinch to zoom with [`ScrollEvent`]s
+/// whose delta is the change of the distance between the fingers in pixels.
+#[derive(Debug, Clone, Default)]
+pub struct TouchState {
+    touches: HashMap<u64, Vec2>,
+}
+
+impl TouchState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a winit touch event, see [`Self::handle_touch`].
+    #[cfg(feature = "winit")]
+    pub fn handle(&mut self, touch: &winit::event::Touch) -> Option<TouchGesture> {
+        let location = Vec2::new(touch.location.x as f32, touch.location.y as f32);
+
+        self.handle_touch(touch.id, touch.phase.into(), location)
+    }
+
+    /// Feeds the touch `id` at `location` in pixels, returns the gesture it completes.
+    pub fn handle_touch(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        location: Vec2,
+    ) -> Option<TouchGesture> {
+        match phase {
+            TouchPhase::Started => {
+                self.touches.insert(id, location);
+
+                None
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touches.remove(&id);
+
+                None
+            }
+            TouchPhase::Moved => {
+                let previous = self.touches.insert(id, location)?;
+
+                match self.touches.len() {
+                    1 => Some(TouchGesture::Drag(DragEvent {
+                        delta: location - previous,
+                        action: Action::Touch(id),
+                    })),
+                    2 => {
+                        let other = self
+                            .touches
+                            .iter()
+                            .find(|(other, _)| **other != id)
+                            .map(|(_, other)| *other)?;
+
+                        Some(TouchGesture::Scroll(ScrollEvent {
+                            delta: location.distance(other) - previous.distance(other),
+                            action: Action::Pinch,
+                        }))
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// The number of fingers currently touching.
+    pub fn active_touches(&self) -> usize {
+        self.touches.len()
+    }
+}
+
+#[cfg(feature = "winit")]
+impl From<winit::event::MouseButton> for PointerButton {
+    fn from(button: winit::event::MouseButton) -> Self {
+        use winit::event::MouseButton;
+
+        match button {
+            MouseButton::Left => Self::Left,
+            MouseButton::Right => Self::Right,
+            MouseButton::Middle => Self::Middle,
+            MouseButton::Back => Self::Back,
+            MouseButton::Forward => Self::Forward,
+            MouseButton::Other(button) => Self::Other(button),
+        }
+    }
+}
+
+#[cfg(feature = "winit")]
+impl From<winit::keyboard::KeyCode> for Key {
+    fn from(code: winit::keyboard::KeyCode) -> Self {
+        use winit::keyboard::KeyCode;
+
+        const LETTERS: [KeyCode; 26] = [
+            KeyCode::KeyA,
+            KeyCode::KeyB,
+            KeyCode::KeyC,
+            KeyCode::KeyD,
+            KeyCode::KeyE,
+            KeyCode::KeyF,
+            KeyCode::KeyG,
+            KeyCode::KeyH,
+            KeyCode::KeyI,
+            KeyCode::KeyJ,
+            KeyCode::KeyK,
+            KeyCode::KeyL,
+            KeyCode::KeyM,
+            KeyCode::KeyN,
+            KeyCode::KeyO,
+            KeyCode::KeyP,
+            KeyCode::KeyQ,
+            KeyCode::KeyR,
+            KeyCode::KeyS,
+            KeyCode::KeyT,
+            KeyCode::KeyU,
+            KeyCode::KeyV,
+            KeyCode::KeyW,
+            KeyCode::KeyX,
+            KeyCode::KeyY,
+            KeyCode::KeyZ,
+        ];
+
+        const DIGITS: [KeyCode; 10] = [
+            KeyCode::Digit0,
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+            KeyCode::Digit6,
+            KeyCode::Digit7,
+            KeyCode::Digit8,
+            KeyCode::Digit9,
+        ];
+
+        if let Some(index) = LETTERS.iter().position(|letter| *letter == code) {
+            return Self::Character((b'a' + index as u8) as char);
+        }
+
+        if let Some(index) = DIGITS.iter().position(|digit| *digit == code) {
+            return Self::Character((b'0' + index as u8) as char);
+        }
+
+        match code {
+            KeyCode::ShiftLeft | KeyCode::ShiftRight => Self::Shift,
+            KeyCode::ControlLeft | KeyCode::ControlRight => Self::Control,
+            KeyCode::AltLeft | KeyCode::AltRight => Self::Alt,
+            KeyCode::SuperLeft | KeyCode::SuperRight => Self::Super,
+            KeyCode::Escape => Self::Escape,
+            KeyCode::Enter | KeyCode::NumpadEnter => Self::Enter,
+            KeyCode::Space => Self::Space,
+            KeyCode::Tab => Self::Tab,
+            KeyCode::Backspace => Self::Backspace,
+            KeyCode::Delete => Self::Delete,
+            KeyCode::ArrowUp => Self::ArrowUp,
+            KeyCode::ArrowDown => Self::ArrowDown,
+            KeyCode::ArrowLeft => Self::ArrowLeft,
+            KeyCode::ArrowRight => Self::ArrowRight,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[cfg(feature = "winit")]
+impl From<winit::event::TouchPhase> for TouchPhase {
+    fn from(phase: winit::event::TouchPhase) -> Self {
+        match phase {
+            winit::event::TouchPhase::Started => Self::Started,
+            winit::event::TouchPhase::Moved => Self::Moved,
+            winit::event::TouchPhase::Ended => Self::Ended,
+            winit::event::TouchPhase::Cancelled => Self::Cancelled,
+        }
+    }
+}
@@ -1,7 +1,15 @@
 pub mod hitbox;
 pub mod interact;
+mod plane;
 mod queue;
 mod ray;
+mod transformed;
+mod triangle;
 
 pub use hitbox::{Hitbox, HitboxNode, HitboxRoot};
+pub use plane::PlaneHitbox;
 pub use ray::Ray;
+pub use transformed::TransformedBox;
+pub use triangle::TriangleHitbox;
+
+pub(crate) use triangle::intersect_triangle;
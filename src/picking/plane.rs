@@ -0,0 +1,57 @@
+use glam::Vec3;
+
+use crate::Aabb;
+
+use super::{hitbox::Hitbox, ray::Ray};
+
+/// An infinite plane of all points `p` with `normal.dot(p) == distance`, e.g. a ground plane
+/// to place objects on.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlaneHitbox {
+    pub normal: Vec3,
+    pub distance: f32,
+    enabled: bool,
+}
+
+impl PlaneHitbox {
+    pub fn new(normal: Vec3, distance: f32) -> Self {
+        Self {
+            normal: normal.normalize(),
+            distance,
+            enabled: true,
+        }
+    }
+
+    /// The plane through the origin with the y axis as normal.
+    pub fn ground() -> Self {
+        Self::new(Vec3::Y, 0.0)
+    }
+}
+
+impl Hitbox for PlaneHitbox {
+    fn check_hit(&self, ray: &Ray) -> Option<f32> {
+        if !self.enabled {
+            return None;
+        }
+
+        ray.intersect_plane(self.normal, self.normal * self.distance)
+    }
+
+    // a plane is already unbounded
+    fn expand_hitbox(&mut self, _box: &dyn Hitbox) {}
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// An infinite box, check the plane separately instead of unioning it into
+    /// the bounds of other hitboxes.
+    fn aabb(&self) -> Aabb {
+        Aabb::new(Vec3::NEG_INFINITY, Vec3::INFINITY)
+    }
+}
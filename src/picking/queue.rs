@@ -10,9 +10,20 @@ pub struct HitBoxQueueEntry<'a, M> {
     pub distance: f32,
 }
 
+impl<M> HitBoxQueueEntry<'_, M> {
+    // NaN distances are treated as the farthest possible hit
+    fn ordered_distance(&self) -> f32 {
+        if self.distance.is_nan() {
+            f32::INFINITY
+        } else {
+            self.distance
+        }
+    }
+}
+
 impl<M: HitboxNode<M>> PartialEq for HitBoxQueueEntry<'_, M> {
     fn eq(&self, other: &Self) -> bool {
-        self.distance == other.distance
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
@@ -26,9 +37,8 @@ impl<M: HitboxNode<M>> PartialOrd for HitBoxQueueEntry<'_, M> {
 
 impl<M: HitboxNode<M>> Ord for HitBoxQueueEntry<'_, M> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.distance
-            .partial_cmp(&other.distance)
-            .unwrap()
+        self.ordered_distance()
+            .total_cmp(&other.ordered_distance())
             .reverse()
     }
 }
@@ -1,11 +1,75 @@
 use glam::Vec3;
 
+use crate::{vertex::Vertex, SimpleGeometry};
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
 }
 
 impl Ray {
+    /// Creates a ray, the direction is normalized.
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// The point at distance `t` along the ray.
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// Intersects the ray with an axis aligned box using the slab method.
+    ///
+    /// Returns the distance to the entry point, or zero if the origin lies inside the box.
+    pub fn intersect_aabb(&self, min: Vec3, max: Vec3) -> Option<f32> {
+        let inv_direction = self.direction.recip();
+
+        let t1 = (min - self.origin) * inv_direction;
+        let t2 = (max - self.origin) * inv_direction;
+
+        let t_near = t1.min(t2).max_element();
+        let t_far = t1.max(t2).min_element();
+
+        if t_far < 0.0 || t_near > t_far {
+            None
+        } else {
+            Some(t_near.max(0.0))
+        }
+    }
+
+    /// Intersects the ray with the plane through `point` with the given `normal`.
+    ///
+    /// Returns the distance to the intersection, or None if the ray is parallel to the plane
+    /// or the plane lies behind the origin.
+    pub fn intersect_plane(&self, normal: Vec3, point: Vec3) -> Option<f32> {
+        let d = normal.dot(self.direction);
+
+        if d.abs() > f32::EPSILON {
+            let t = (point - self.origin).dot(normal) / d;
+
+            (t >= 0.0).then_some(t)
+        } else {
+            None
+        }
+    }
+
+    /// A line from the origin to `length` along the ray, to be drawn with
+    /// `PrimitiveTopology::LineList` when debugging picking.
+    pub fn to_debug_geometry(&self, length: f32) -> SimpleGeometry<Vertex> {
+        let vertex = |position: Vec3| Vertex {
+            position: position.into(),
+            normal: (-self.direction).into(),
+            color: [1.0, 0.0, 0.0, 1.0],
+        };
+
+        SimpleGeometry::init(vec![vertex(self.origin), vertex(self.at(length))])
+    }
+
     pub fn from_view(
         viewport: (f32, f32, f32, f32),
         position: (f32, f32),
@@ -0,0 +1,63 @@
+use crate::{Aabb, Transform};
+
+use super::{hitbox::Hitbox, ray::Ray};
+
+/// A box in local space placed by a [`Transform`], e.g. the bounds of a rotated model.
+///
+/// Rays are tested in local space, so rotated or scaled boxes stay exact instead of being
+/// refitted into a larger axis aligned box.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransformedBox {
+    pub local: Aabb,
+    pub transform: Transform,
+    enabled: bool,
+}
+
+impl TransformedBox {
+    pub fn new(local: Aabb, transform: Transform) -> Self {
+        Self {
+            local,
+            transform,
+            enabled: true,
+        }
+    }
+}
+
+impl Hitbox for TransformedBox {
+    fn check_hit(&self, ray: &Ray) -> Option<f32> {
+        if !self.enabled {
+            return None;
+        }
+
+        let inverse = self.transform.matrix().inverse();
+
+        // the direction isn't normalized again, so the distance stays in world units
+        let local_ray = Ray {
+            origin: inverse.transform_point3(ray.origin),
+            direction: inverse.transform_vector3(ray.direction),
+        };
+
+        local_ray.intersect_aabb(self.local.min, self.local.max)
+    }
+
+    /// Grows the local box to enclose the world bounds of `hitbox`.
+    fn expand_hitbox(&mut self, hitbox: &dyn Hitbox) {
+        let inverse = self.transform.matrix().inverse();
+
+        self.local = self.local.union(&hitbox.aabb().transformed(&inverse));
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The world bounds of the transformed corners.
+    fn aabb(&self) -> Aabb {
+        self.local.transformed(&self.transform.matrix())
+    }
+}
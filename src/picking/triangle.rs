@@ -0,0 +1,171 @@
+use glam::Vec3;
+
+use crate::Aabb;
+
+use super::{hitbox::HitboxNode, ray::Ray};
+
+/// Intersects the ray with a triangle using the Möller–Trumbore algorithm.
+///
+/// Faces are counter clockwise like wgpu's default `FrontFace::Ccw`, `cull_mode` skips
+/// triangles facing the ray with that side the same way the pipeline would.
+/// Returns the distance along the ray to the hit point.
+pub(crate) fn intersect_triangle(
+    ray: &Ray,
+    [a, b, c]: &[Vec3; 3],
+    cull_mode: Option<wgpu::Face>,
+) -> Option<f32> {
+    let edge1 = *b - *a;
+    let edge2 = *c - *a;
+
+    let p = ray.direction.cross(edge2);
+    let det = edge1.dot(p);
+
+    // the ray is parallel to the triangle or hits a culled side
+    match cull_mode {
+        None if det.abs() < f32::EPSILON => return None,
+        Some(wgpu::Face::Back) if det < f32::EPSILON => return None,
+        Some(wgpu::Face::Front) if det > -f32::EPSILON => return None,
+        _ => {}
+    }
+
+    let inv_det = 1.0 / det;
+    let s = ray.origin - *a;
+
+    let u = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+
+    let v = ray.direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inv_det;
+
+    (t > f32::EPSILON).then_some(t)
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriangleHitbox {
+    vertices: [Vec3; 3],
+    cull_mode: Option<wgpu::Face>,
+}
+
+impl TriangleHitbox {
+    /// Creates a double sided triangle.
+    pub fn new(a: Vec3, b: Vec3, c: Vec3) -> Self {
+        Self {
+            vertices: [a, b, c],
+            cull_mode: None,
+        }
+    }
+
+    /// Ignores hits on the given side, should match the `cull_mode` of the render pipeline.
+    pub fn with_cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn cull_mode(&self) -> Option<wgpu::Face> {
+        self.cull_mode
+    }
+
+    pub fn vertices(&self) -> &[Vec3; 3] {
+        &self.vertices
+    }
+
+    /// Returns the index of the vertex closest to the ray, if the ray hits the triangle.
+    ///
+    /// `pixel_radius` is the tolerance around the ray in world units, callers convert their
+    /// screen space tolerance with the camera at the hit distance. Vertices farther away from
+    /// the ray are not picked.
+    pub fn closest_vertex(&self, ray: &Ray, pixel_radius: f32) -> Option<usize> {
+        intersect_triangle(ray, &self.vertices, self.cull_mode)?;
+
+        let direction = ray.direction.normalize();
+
+        self.vertices
+            .iter()
+            .map(|vertex| {
+                let to_vertex = *vertex - ray.origin;
+
+                (to_vertex - direction * to_vertex.dot(direction)).length()
+            })
+            .enumerate()
+            .filter(|(_, distance)| *distance <= pixel_radius)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// The point on the triangle closest to `point`.
+    pub fn closest_point(&self, point: Vec3) -> Vec3 {
+        let [a, b, c] = self.vertices;
+
+        let ab = b - a;
+        let ac = c - a;
+        let ap = point - a;
+
+        let d1 = ab.dot(ap);
+        let d2 = ac.dot(ap);
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return a;
+        }
+
+        let bp = point - b;
+        let d3 = ab.dot(bp);
+        let d4 = ac.dot(bp);
+        if d3 >= 0.0 && d4 <= d3 {
+            return b;
+        }
+
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            return a + ab * (d1 / (d1 - d3));
+        }
+
+        let cp = point - c;
+        let d5 = ab.dot(cp);
+        let d6 = ac.dot(cp);
+        if d6 >= 0.0 && d5 <= d6 {
+            return c;
+        }
+
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            return a + ac * (d2 / (d2 - d6));
+        }
+
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+            return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+        }
+
+        let denom = 1.0 / (va + vb + vc);
+
+        a + ab * (vb * denom) + ac * (vc * denom)
+    }
+}
+
+impl HitboxNode<TriangleHitbox> for TriangleHitbox {
+    fn check_hit(&self, ray: &Ray) -> Option<f32> {
+        intersect_triangle(ray, &self.vertices, self.cull_mode)
+    }
+
+    fn inner_nodes(&self) -> &[TriangleHitbox] {
+        &[]
+    }
+
+    fn aabb(&self) -> Aabb {
+        let [a, b, c] = self.vertices;
+
+        Aabb::new(a.min(b).min(c), a.max(b).max(c))
+    }
+
+    fn distance_to_point(&self, point: Vec3) -> f32 {
+        self.closest_point(point).distance(point)
+    }
+}
@@ -0,0 +1,15 @@
+//! The commonly used types and traits, `use rether::prelude::*;` brings the transform methods
+//! of models and geometry into scope.
+
+pub use crate::{
+    alloc::{AllocHandle, BufferAlloc, BufferDynamicAlloc, ModifyAction},
+    model::{
+        geometry::{Geometry, IndexedGeometry},
+        BaseModel, Expandable, IndexedModel, Model, ModelState, RotateModel, ScaleModel,
+        TransformModel, TranslateModel, TreeModel, TreeModelBuilder, VisibilityModel,
+    },
+    picking::{Hitbox, HitboxNode, HitboxRoot, Ray},
+    vertex::Vertex,
+    Aabb, Buffer, BufferUpdate, IndexedBuffer, RetherError, Rotate, Scale, SimpleGeometry,
+    Transform, Translate,
+};
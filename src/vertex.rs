@@ -1,11 +1,12 @@
 /// A vertex is a single point. A geometry is typically composed of multiple vertecies.
 use bytemuck::Zeroable;
-use glam::Vec3;
+use glam::{Vec2, Vec3, Vec4};
 
-use crate::{model::transform::Translate, Rotate, Scale};
+use crate::{model::transform::Translate, Aabb, Affine, Rotate, Scale};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
@@ -43,6 +44,333 @@ impl Vertex {
             ],
         }
     }
+
+    /// [`Vertex::desc`] without the color, for buffers drawing their colors from a separate
+    /// stream bound with [`Vertex::color_stream_desc`].
+    pub fn desc_without_color<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            attributes: &Self::desc().attributes[..2],
+            ..Self::desc()
+        }
+    }
+
+    /// Layout for a deinterleaved position stream (`[f32; 3]`), matching location 0 of [`Vertex::desc`].
+    pub fn position_stream_desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Float32x3,
+            }],
+        }
+    }
+
+    /// Layout for a deinterleaved normal stream (`[f32; 3]`), matching location 1 of [`Vertex::desc`].
+    pub fn normal_stream_desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 1,
+                format: wgpu::VertexFormat::Float32x3,
+            }],
+        }
+    }
+
+    /// Layout for a deinterleaved color stream (`[f32; 4]`), matching location 2 of [`Vertex::desc`].
+    pub fn color_stream_desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 2,
+                format: wgpu::VertexFormat::Float32x4,
+            }],
+        }
+    }
+
+    pub fn position_vec3(&self) -> Vec3 {
+        Vec3::from(self.position)
+    }
+
+    pub fn normal_vec3(&self) -> Vec3 {
+        Vec3::from(self.normal)
+    }
+
+    pub fn color_vec4(&self) -> Vec4 {
+        Vec4::from(self.color)
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position.into();
+    }
+
+    pub fn set_normal(&mut self, normal: Vec3) {
+        self.normal = normal.into();
+    }
+
+    pub fn set_color(&mut self, color: Vec4) {
+        self.color = color.into();
+    }
+
+    /// Interpolates all attributes between `self` at `t == 0` and `other` at `t == 1`.
+    ///
+    /// The normal is normalized again, it stays zero if both normals are zero.
+    pub fn lerp(&self, other: &Vertex, t: f32) -> Vertex {
+        let mut vertex = *self;
+
+        vertex.set_position(self.position_vec3().lerp(other.position_vec3(), t));
+        vertex.set_normal(
+            self.normal_vec3()
+                .lerp(other.normal_vec3(), t)
+                .normalize_or_zero(),
+        );
+        vertex.set_color(self.color_vec4().lerp(other.color_vec4(), t));
+
+        vertex
+    }
+}
+
+/// An attribute of a [`VertexLayout`].
+///
+/// Every attribute keeps its shader location whichever others are enabled, so shaders can
+/// share their inputs with [`Vertex::desc`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VertexAttribute {
+    /// `[f32; 3]` at location 0.
+    Position,
+    /// `[f32; 3]` at location 1.
+    Normal,
+    /// `[f32; 4]` at location 2.
+    Color,
+    /// `[f32; 2]` at location 3.
+    Uv,
+}
+
+impl VertexAttribute {
+    pub fn format(&self) -> wgpu::VertexFormat {
+        match self {
+            Self::Position | Self::Normal => wgpu::VertexFormat::Float32x3,
+            Self::Color => wgpu::VertexFormat::Float32x4,
+            Self::Uv => wgpu::VertexFormat::Float32x2,
+        }
+    }
+
+    pub fn shader_location(&self) -> wgpu::ShaderLocation {
+        match self {
+            Self::Position => 0,
+            Self::Normal => 1,
+            Self::Color => 2,
+            Self::Uv => 3,
+        }
+    }
+}
+
+/// An interleaved vertex layout built from a set of attributes, e.g. positions and colors
+/// only for a point cloud.
+///
+/// Attributes are packed in the order they are added.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VertexLayout {
+    attributes: Vec<wgpu::VertexAttribute>,
+    stride: wgpu::BufferAddress,
+}
+
+impl VertexLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The layout of [`Vertex`].
+    pub fn vertex() -> Self {
+        Self::new()
+            .with(VertexAttribute::Position)
+            .with(VertexAttribute::Normal)
+            .with(VertexAttribute::Color)
+    }
+
+    /// Appends `attribute`, adding an attribute twice does nothing.
+    pub fn with(mut self, attribute: VertexAttribute) -> Self {
+        if self.contains(attribute) {
+            return self;
+        }
+
+        self.attributes.push(wgpu::VertexAttribute {
+            format: attribute.format(),
+            offset: self.stride,
+            shader_location: attribute.shader_location(),
+        });
+        self.stride += attribute.format().size();
+
+        self
+    }
+
+    pub fn contains(&self, attribute: VertexAttribute) -> bool {
+        self.attributes
+            .iter()
+            .any(|enabled| enabled.shader_location == attribute.shader_location())
+    }
+
+    /// The size of a single vertex in bytes.
+    pub fn stride(&self) -> wgpu::BufferAddress {
+        self.stride
+    }
+
+    pub fn attributes(&self) -> &[wgpu::VertexAttribute] {
+        &self.attributes
+    }
+
+    pub fn desc(&self) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.stride,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &self.attributes,
+        }
+    }
+}
+
+/// A compressed [`Vertex`] of 16 instead of 40 bytes.
+///
+/// The position is quantized to normalized `i16`s inside some bounds, the normal is
+/// octahedron encoded into two normalized `i16`s and the color is stored as `u8`s.
+/// Unpacking in the shader is left to the user, the position has to be mapped back
+/// with `min + (position * 0.5 + 0.5) * (max - min)` of the bounds used for packing.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PackedVertex {
+    // the fourth component is padding, there is no three component snorm16 format
+    pub position: [i16; 4],
+    pub normal: [i16; 2],
+    pub color: [u8; 4],
+}
+
+impl PackedVertex {
+    /// Packs the vertex with its position quantized inside `bounds`.
+    pub fn new(vertex: &Vertex, bounds: &Aabb) -> Self {
+        let extents = bounds.extents();
+        let position = (vertex.position_vec3() - bounds.min) / extents * 2.0 - 1.0;
+        // flat axes have no extent, every position on them maps to the minimum
+        let position = Vec3::select(extents.cmpeq(Vec3::ZERO), Vec3::NEG_ONE, position);
+
+        let normal = oct_encode(vertex.normal_vec3());
+
+        Self {
+            position: [
+                encode_snorm(position.x),
+                encode_snorm(position.y),
+                encode_snorm(position.z),
+                0,
+            ],
+            normal: [encode_snorm(normal.x), encode_snorm(normal.y)],
+            color: vertex
+                .color
+                .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8),
+        }
+    }
+
+    /// Unpacks the vertex, `bounds` must be the bounds it was packed with.
+    pub fn unpack(&self, bounds: &Aabb) -> Vertex {
+        let position = Vec3::new(
+            decode_snorm(self.position[0]),
+            decode_snorm(self.position[1]),
+            decode_snorm(self.position[2]),
+        );
+
+        let normal = oct_decode(Vec2::new(
+            decode_snorm(self.normal[0]),
+            decode_snorm(self.normal[1]),
+        ));
+
+        Vertex {
+            position: (bounds.min + (position * 0.5 + 0.5) * bounds.extents()).into(),
+            normal: normal.into(),
+            color: self.color.map(|channel| channel as f32 / 255.0),
+        }
+    }
+
+    /// Unpacks a vertex packed with `PackedVertex::from`, inside the `-1..1` cube.
+    pub fn to_vertex(&self) -> Vertex {
+        self.unpack(&UNIT_BOUNDS)
+    }
+
+    /// Layout matching the locations of [`Vertex::desc`].
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<PackedVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Snorm16x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[i16; 4]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Snorm16x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[i16; 6]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Unorm8x4,
+                },
+            ],
+        }
+    }
+}
+
+const UNIT_BOUNDS: Aabb = Aabb {
+    min: Vec3::NEG_ONE,
+    max: Vec3::ONE,
+};
+
+/// Packs a vertex whose position lies inside the `-1..1` cube, positions outside are clamped.
+impl From<&Vertex> for PackedVertex {
+    fn from(vertex: &Vertex) -> Self {
+        Self::new(vertex, &UNIT_BOUNDS)
+    }
+}
+
+fn encode_snorm(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+fn decode_snorm(value: i16) -> f32 {
+    (value as f32 / i16::MAX as f32).max(-1.0)
+}
+
+// maps the unit sphere onto the octahedron and unfolds its lower half onto the square
+fn oct_encode(normal: Vec3) -> Vec2 {
+    let length = normal.x.abs() + normal.y.abs() + normal.z.abs();
+
+    if length == 0.0 {
+        return Vec2::ZERO;
+    }
+
+    let normal = normal / length;
+
+    if normal.z < 0.0 {
+        (Vec2::ONE - Vec2::new(normal.y, normal.x).abs()) * Vec2::new(normal.x, normal.y).signum()
+    } else {
+        Vec2::new(normal.x, normal.y)
+    }
+}
+
+fn oct_decode(encoded: Vec2) -> Vec3 {
+    let z = 1.0 - encoded.x.abs() - encoded.y.abs();
+
+    let xy = if z < 0.0 {
+        (Vec2::ONE - Vec2::new(encoded.y, encoded.x).abs()) * encoded.signum()
+    } else {
+        encoded
+    };
+
+    Vec3::new(xy.x, xy.y, z).normalize()
 }
 
 impl Translate for Vertex {
@@ -55,11 +383,8 @@ impl Translate for Vertex {
 
 impl Rotate for Vertex {
     fn rotate(&mut self, rotation: glam::Quat, _center: Vec3) {
-        let position = glam::Vec3::from(self.position);
-        let normal = glam::Vec3::from(self.normal);
-
-        self.position = (rotation * position).into();
-        self.normal = (rotation * normal).into();
+        self.set_position(rotation * self.position_vec3());
+        self.set_normal(rotation * self.normal_vec3());
     }
 }
 
@@ -71,6 +396,17 @@ impl Scale for Vertex {
     }
 }
 
+impl Affine for Vertex {
+    fn apply_matrix(data: &mut [Self], matrix: &glam::Mat4) {
+        let normal_matrix = glam::Mat3::from_mat4(*matrix).inverse().transpose();
+
+        for vertex in data.iter_mut() {
+            vertex.set_position(matrix.transform_point3(vertex.position_vec3()));
+            vertex.set_normal((normal_matrix * vertex.normal_vec3()).normalize_or_zero());
+        }
+    }
+}
+
 pub struct VertexRotator<'a, T> {
     data: &'a mut [T],
 }
@@ -84,15 +420,25 @@ impl<'a, T> VertexRotator<'a, T> {
 impl<'a> Rotate for VertexRotator<'a, Vertex> {
     fn rotate(&mut self, rotation: glam::Quat, center: Vec3) {
         for vertex in self.data.iter_mut() {
-            let position = glam::Vec3::from(vertex.position);
-            let normal = glam::Vec3::from(vertex.normal);
-
-            vertex.position = (rotation * (position - center) + center).into();
-            vertex.normal = (rotation * normal).into();
+            vertex.set_position(rotation * (vertex.position_vec3() - center) + center);
+            vertex.set_normal(rotation * vertex.normal_vec3());
         }
     }
 }
 
+impl<'a> VertexRotator<'a, Vertex> {
+    /// Rotates by `angle` radians around the line through `axis_point` along `axis_dir`,
+    /// following the right hand rule. Nothing happens for a zero direction.
+    pub fn rotate_about_axis(&mut self, axis_point: Vec3, axis_dir: Vec3, angle: f32) {
+        let Some(axis) = axis_dir.try_normalize() else {
+            return;
+        };
+
+        // any point on the line works as the center
+        self.rotate(glam::Quat::from_axis_angle(axis, angle), axis_point);
+    }
+}
+
 pub struct VertexScaler<'a, T> {
     data: &'a mut [T],
     center: glam::Vec3,
@@ -107,9 +453,7 @@ impl<'a, T> VertexScaler<'a, T> {
 impl<'a> Scale for VertexScaler<'a, Vertex> {
     fn scale(&mut self, scale: glam::Vec3) {
         for vertex in self.data.iter_mut() {
-            let position = glam::Vec3::from(vertex.position);
-
-            vertex.position = ((position - self.center) * scale + self.center).into();
+            vertex.set_position((vertex.position_vec3() - self.center) * scale + self.center);
         }
     }
 }
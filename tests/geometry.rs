@@ -0,0 +1,430 @@
+use glam::Vec3;
+use rether::{
+    model::geometry::{weld, weld_with, Geometry, IndexedGeometry, WeldConfig},
+    vertex::{PackedVertex, Vertex, VertexAttribute, VertexLayout, VertexRotator},
+    SimpleGeometry, Transform, Translate,
+};
+
+fn unit_cube() -> IndexedGeometry<Vertex> {
+    let vertices = (0..8)
+        .map(|i| Vertex {
+            position: [(i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32],
+            ..Default::default()
+        })
+        .collect();
+
+    let indices = vec![
+        0, 2, 1, 1, 2, 3, // z = 0
+        4, 5, 6, 5, 7, 6, // z = 1
+        0, 1, 4, 1, 5, 4, // y = 0
+        2, 6, 3, 3, 6, 7, // y = 1
+        0, 4, 2, 2, 4, 6, // x = 0
+        1, 3, 5, 3, 7, 5, // x = 1
+    ];
+
+    IndexedGeometry::init(vertices, indices)
+}
+
+#[test]
+fn slice_plane_interpolates_attributes() {
+    let cube = unit_cube();
+    let vertices = cube
+        .vertices()
+        .iter()
+        .map(|vertex| Vertex {
+            normal: [0.0, 0.0, 1.0],
+            color: [vertex.position[2], 0.0, 0.0, 1.0],
+            ..*vertex
+        })
+        .collect();
+    let cube = IndexedGeometry::init(vertices, cube.indices().to_vec());
+
+    let segments = cube.slice_plane(Vec3::Z, 0.25);
+
+    // two triangles on each of the four sides
+    assert_eq!(segments.len(), 8);
+    assert!(segments.iter().flatten().all(|vertex| {
+        (vertex.position[2] - 0.25).abs() < 1e-6
+            && (vertex.color[0] - 0.25).abs() < 1e-6
+            && vertex.normal == [0.0, 0.0, 1.0]
+    }));
+
+    assert!(cube.slice_plane(Vec3::Z, 2.0).is_empty());
+}
+
+#[test]
+fn contains_point_inside_and_outside() {
+    let cube = unit_cube();
+
+    assert!(cube.contains_point(Vec3::splat(0.5)));
+    // rays from here would run exactly along the diagonals of the faces
+    assert!(cube.contains_point(Vec3::new(0.5, 0.5, 0.25)));
+
+    assert!(!cube.contains_point(Vec3::splat(1.5)));
+    assert!(!cube.contains_point(Vec3::new(-0.5, 0.5, 0.5)));
+}
+
+#[test]
+fn iter_triangles_resolves_indices() {
+    let cube = unit_cube();
+
+    let triangles = cube.iter_triangles().collect::<Vec<_>>();
+    assert_eq!(triangles.len(), 12);
+    assert_eq!(
+        triangles[0].map(|vertex| vertex.position),
+        [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]]
+    );
+}
+
+#[test]
+fn counts_follow_topology() {
+    let cube = unit_cube();
+
+    assert_eq!(cube.vertex_count(), 8);
+    assert_eq!(cube.index_count(), 36);
+    assert_eq!(cube.triangle_count(), 12);
+
+    assert_eq!(cube.stripify().triangle_count(), 12);
+    assert_eq!(cube.clone().into_simple().triangle_count(), 2);
+}
+
+#[test]
+fn weld_merges_identical_vertices() {
+    let vertex = |x: f32| Vertex {
+        position: [x, 0.0, 0.0],
+        ..Default::default()
+    };
+
+    let vertices = vec![
+        vertex(0.0),
+        vertex(1.0),
+        vertex(2.0),
+        vertex(1.0),
+        vertex(2.0),
+    ];
+    let indices = vec![0, 1, 2, 0, 3, 4];
+
+    let (welded, indices) = weld(&vertices, &indices);
+
+    assert_eq!(welded.len(), 3);
+    assert_eq!(indices, vec![0, 1, 2, 0, 1, 2]);
+}
+
+#[test]
+fn weld_with_respects_tolerances() {
+    let vertex = |x: f32, normal: [f32; 3], color: [f32; 4]| Vertex {
+        position: [x, 0.0, 0.0],
+        normal,
+        color,
+    };
+
+    let vertices = vec![
+        vertex(0.0, [0.0, 1.0, 0.0], [1.0; 4]),
+        vertex(1e-6, [1.0, 0.0, 0.0], [1.0; 4]),
+        vertex(2e-6, [0.0, 1.0, 0.0], [0.0; 4]),
+        vertex(1.0, [0.0, 1.0, 0.0], [1.0; 4]),
+    ];
+    let indices = vec![0, 1, 2, 1, 2, 3];
+
+    let (loose, loose_indices) = weld_with(&vertices, &indices, &WeldConfig::default());
+    assert_eq!(loose.len(), 2);
+    assert_eq!(loose_indices, vec![0, 0, 0, 0, 0, 1]);
+
+    let (strict, _) = weld_with(&vertices, &indices, &WeldConfig::strict(1e-5));
+    assert_eq!(strict.len(), 4);
+
+    let normals_only = WeldConfig {
+        normal_angle_deg: Some(10.0),
+        ..Default::default()
+    };
+    let (welded, welded_indices) = weld_with(&vertices, &indices, &normals_only);
+    assert_eq!(welded.len(), 3);
+    assert_eq!(welded_indices, vec![0, 1, 0, 1, 0, 2]);
+
+    let cube = unit_cube();
+    let mut nudged = unit_cube();
+    nudged.translate(Vec3::splat(1e-3));
+
+    assert!(!cube.approx_eq(&nudged, &WeldConfig::default()));
+    assert!(cube.approx_eq(&nudged, &WeldConfig::strict(1e-2)));
+}
+
+#[test]
+fn adjacency_of_closed_cube() {
+    let cube = unit_cube();
+    let adjacency = cube.build_adjacency();
+
+    // every corner of the cube is shared by 3 to 6 triangles
+    assert!((0..8).all(|vertex| (3..=6).contains(&adjacency.vertex_faces(vertex).len())));
+
+    // 12 edges of the cube plus one diagonal per side
+    assert_eq!(adjacency.edges().count(), 18);
+    assert!(adjacency
+        .edges()
+        .all(|(a, b)| adjacency.edge_faces(a, b).len() == 2));
+    assert_eq!(adjacency.boundary_edges().count(), 0);
+
+    assert_eq!(adjacency.edge_faces(1, 0), adjacency.edge_faces(0, 1));
+    assert!(adjacency.edge_faces(0, 7).is_empty());
+}
+
+#[test]
+fn pack_round_trips_within_precision() {
+    let vertices = vec![
+        Vertex {
+            position: [-10.0, 0.0, 3.0],
+            normal: [0.0, 0.0, -1.0],
+            color: [1.0, 0.5, 0.0, 1.0],
+        },
+        Vertex {
+            position: [20.0, 0.0, 4.5],
+            normal: Vec3::new(1.0, -2.0, 0.5).normalize().into(),
+            color: [0.0, 0.25, 1.0, 0.5],
+        },
+    ];
+
+    let (packed, bounds) = SimpleGeometry::init(vertices.clone()).pack().unwrap();
+
+    for (original, packed) in vertices.iter().zip(packed.iter()) {
+        let unpacked = packed.unpack(&bounds);
+
+        let close = |a: &[f32], b: &[f32], tolerance: f32| {
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() <= tolerance)
+        };
+
+        assert!(close(&unpacked.position, &original.position, 1e-3));
+        assert!(close(&unpacked.normal, &original.normal, 1e-3));
+        assert!(close(&unpacked.color, &original.color, 1.0 / 255.0));
+    }
+
+    let unit = Vertex {
+        position: [0.5, -0.25, 1.0],
+        normal: [0.0, 1.0, 0.0],
+        ..Default::default()
+    };
+    let position = PackedVertex::from(&unit).to_vertex().position;
+    assert!((Vec3::from(position) - Vec3::from(unit.position)).length() < 1e-4);
+}
+
+#[test]
+fn transformed_bakes_matrix() {
+    let geometry = SimpleGeometry::init(vec![Vertex {
+        position: [1.0, 0.0, 0.0],
+        normal: [0.0, 1.0, 0.0],
+        ..Default::default()
+    }]);
+
+    let transform = Transform {
+        translation: Vec3::new(0.0, 0.0, 3.0),
+        rotation: glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+        scale: Vec3::new(2.0, 1.0, 1.0),
+    };
+
+    let transformed = geometry.transformed(&transform);
+    let vertex = transformed.vertices()[0];
+
+    assert!(Vec3::from(vertex.position).abs_diff_eq(Vec3::new(0.0, 2.0, 3.0), 1e-5));
+    assert!(Vec3::from(vertex.normal).abs_diff_eq(Vec3::new(-1.0, 0.0, 0.0), 1e-5));
+
+    // the source is left untouched
+    assert_eq!(geometry.vertices()[0].position, [1.0, 0.0, 0.0]);
+}
+
+#[test]
+fn expand_with_color_recolors_appended_vertices() {
+    let mut geometry = SimpleGeometry::init(vec![Vertex::default()]);
+    let other = SimpleGeometry::init(vec![
+        Vertex {
+            position: [1.0, 2.0, 3.0],
+            ..Default::default()
+        };
+        2
+    ]);
+
+    geometry.expand_with_color(&other, [1.0, 0.0, 0.0, 1.0]);
+
+    let vertices = geometry.vertices();
+    assert_eq!(vertices.len(), 3);
+    assert_eq!(vertices[0].color, [0.0; 4]);
+    assert!(vertices[1..]
+        .iter()
+        .all(|vertex| vertex.color == [1.0, 0.0, 0.0, 1.0] && vertex.position == [1.0, 2.0, 3.0]));
+}
+
+#[test]
+fn from_arrays_zips_attributes() {
+    let positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]];
+    let colors = [[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0]];
+
+    let geometry = SimpleGeometry::from_arrays(&positions, None, Some(&colors)).unwrap();
+
+    let vertices = geometry.vertices();
+    assert_eq!(vertices[1].position, [1.0, 0.0, 0.0]);
+    assert_eq!(vertices[1].normal, [0.0; 3]);
+    assert_eq!(vertices[1].color, [0.0, 1.0, 0.0, 1.0]);
+
+    assert!(SimpleGeometry::from_arrays(&positions, Some(&[[0.0; 3]]), None).is_err());
+}
+
+#[test]
+fn stripify_keeps_triangles_and_winding() {
+    let cube = unit_cube();
+
+    // rotated so the smallest index comes first, which keeps the winding
+    let canonical = |triangle: [u32; 3]| {
+        let start = (0..3).min_by_key(|corner| triangle[*corner]).unwrap();
+        [0, 1, 2].map(|corner| triangle[(corner + start) % 3])
+    };
+
+    let mut expected = cube
+        .indices()
+        .chunks_exact(3)
+        .map(|triangle| canonical([triangle[0], triangle[1], triangle[2]]))
+        .collect::<Vec<_>>();
+
+    let strips = cube.stripify();
+
+    let mut triangles = strips
+        .iter_triangle_indices()
+        .map(canonical)
+        .collect::<Vec<_>>();
+
+    expected.sort();
+    triangles.sort();
+
+    assert_eq!(triangles, expected);
+    assert!(strips.indices().len() < cube.indices().len());
+}
+
+#[test]
+fn partition_grid_buckets_triangles() {
+    let cube = unit_cube();
+
+    // centroids of the triangles lie on both sides of the middle of every axis
+    let chunks = cube.partition_grid(0.5);
+
+    assert!(chunks.len() > 1);
+    assert_eq!(
+        chunks
+            .iter()
+            .map(|chunk| chunk.triangle_count())
+            .sum::<usize>(),
+        12
+    );
+
+    for chunk in chunks.iter() {
+        assert!(chunk
+            .indices()
+            .iter()
+            .all(|index| (*index as usize) < chunk.vertices().len()));
+    }
+
+    assert_eq!(cube.partition_grid(10.0).len(), 1);
+    assert_eq!(cube.partition_grid(0.0)[0].index_count(), 36);
+}
+
+#[test]
+fn vertex_layout_packs_enabled_attributes() {
+    let layout = VertexLayout::vertex();
+    let desc = Vertex::desc();
+
+    assert_eq!(layout.stride(), desc.array_stride);
+    assert_eq!(layout.attributes(), desc.attributes);
+
+    let points = VertexLayout::new()
+        .with(VertexAttribute::Position)
+        .with(VertexAttribute::Color)
+        .with(VertexAttribute::Position);
+
+    assert_eq!(points.stride(), 28);
+    assert_eq!(points.attributes().len(), 2);
+    assert_eq!(points.attributes()[1].offset, 12);
+    assert_eq!(points.attributes()[1].shader_location, 2);
+    assert!(!points.contains(VertexAttribute::Normal));
+}
+
+#[test]
+fn rotate_about_axis_pivots_around_line() {
+    let mut vertices = vec![Vertex {
+        position: [2.0, 0.0, 3.0],
+        normal: [1.0, 0.0, 0.0],
+        ..Default::default()
+    }];
+
+    VertexRotator::new(&mut vertices).rotate_about_axis(
+        Vec3::new(1.0, 0.0, -5.0),
+        Vec3::Z * 2.0,
+        std::f32::consts::FRAC_PI_2,
+    );
+
+    let position = Vec3::from(vertices[0].position);
+    let normal = Vec3::from(vertices[0].normal);
+
+    assert!(position.abs_diff_eq(Vec3::new(1.0, 1.0, 3.0), 1e-5));
+    assert!(normal.abs_diff_eq(Vec3::Y, 1e-5));
+}
+
+#[test]
+fn content_hash_matches_equal_geometry() {
+    let cube = unit_cube();
+
+    let mut nudged = unit_cube();
+    nudged.translate(Vec3::splat(1e-7));
+
+    assert!(cube == nudged);
+    assert_eq!(cube.content_hash(), nudged.content_hash());
+
+    let mut moved = unit_cube();
+    moved.translate(Vec3::X);
+
+    assert!(cube != moved);
+    assert_ne!(cube.content_hash(), moved.content_hash());
+}
+
+#[test]
+fn vertex_glam_accessors_round_trip() {
+    let mut vertex = Vertex::default();
+
+    vertex.set_position(Vec3::new(1.0, 2.0, 3.0));
+    vertex.set_normal(Vec3::Y);
+    vertex.set_color(glam::Vec4::new(0.1, 0.2, 0.3, 1.0));
+
+    assert_eq!(vertex.position, [1.0, 2.0, 3.0]);
+    assert_eq!(vertex.normal_vec3(), Vec3::Y);
+    assert_eq!(vertex.color_vec4().to_array(), vertex.color);
+    assert_eq!(vertex.position_vec3(), Vec3::from(vertex.position));
+}
+
+#[test]
+fn remove_vertices_drops_and_remaps_triangles() {
+    let cube = unit_cube();
+
+    let mut removed = cube.clone();
+    removed.remove_vertices(&[7, 0, 0, 42]);
+
+    assert_eq!(removed.vertices().len(), 6);
+    assert_eq!(removed.index_count(), 6 * 3);
+    assert!(removed
+        .indices()
+        .iter()
+        .all(|index| (*index as usize) < removed.vertices().len()));
+
+    // every kept triangle still points at the same positions
+    let positions = |geometry: &IndexedGeometry<Vertex>| {
+        geometry
+            .indices()
+            .iter()
+            .map(|index| geometry.vertices()[*index as usize].position)
+            .collect::<Vec<_>>()
+    };
+
+    let expected = cube
+        .indices()
+        .chunks_exact(3)
+        .filter(|triangle| !triangle.contains(&0) && !triangle.contains(&7))
+        .flatten()
+        .map(|index| cube.vertices()[*index as usize].position)
+        .collect::<Vec<_>>();
+
+    assert_eq!(positions(&removed), expected);
+}
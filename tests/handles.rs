@@ -0,0 +1,266 @@
+use rether::{
+    alloc::{
+        AllocHandle, BufferAlloc, BufferDynamicAlloc, BufferDynamicAllocator, BufferResizeAlloc,
+        DynamicAllocHandle, ModifyAction, StaticAllocHandle,
+    },
+    vertex::Vertex,
+};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn handles_are_send_and_sync() {
+    assert_send_sync::<StaticAllocHandle<Vertex>>();
+    assert_send_sync::<DynamicAllocHandle<Vertex>>();
+
+    // the action owns a Send closure, so it doesn't depend on the element type
+    assert_send::<ModifyAction<std::rc::Rc<Vertex>>>();
+}
+
+#[test]
+fn rebound_handle_sends_to_new_allocator() {
+    let mut old = BufferDynamicAllocator::<Vertex>::default();
+    let mut new = BufferDynamicAllocator::<Vertex>::default();
+
+    let handle = old.allocate("a", 3);
+    handle.rebind(new.action_sender(), new.destroy_sender());
+
+    handle
+        .send_action(ModifyAction::new(3, 3, Box::new(|_: &mut [Vertex]| {})))
+        .unwrap();
+
+    let mut received = Vec::new();
+    new.update(|action| received.push(action.id().cloned()));
+    assert_eq!(received, vec![Some("a".to_string())]);
+
+    let mut stale = 0;
+    old.update(|_| stale += 1);
+    assert_eq!(stale, 0);
+
+    handle.destroy().unwrap();
+    assert!(old.get_destroyed_handles().is_empty());
+    assert_eq!(new.get_destroyed_handles(), vec!["a".to_string()]);
+}
+
+#[test]
+fn peek_destroyed_doesnt_drain() {
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+
+    let a = allocator.allocate("a", 3);
+    allocator.allocate("b", 3);
+
+    a.destroy().unwrap();
+
+    assert_eq!(allocator.peek_destroyed(), ["a".to_string()]);
+    assert_eq!(allocator.peek_destroyed(), ["a".to_string()]);
+
+    assert_eq!(allocator.get_destroyed_handles(), vec!["a".to_string()]);
+    assert!(allocator.peek_destroyed().is_empty());
+}
+
+#[test]
+fn repeated_destroy_requests_are_drained_once() {
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+
+    allocator.allocate("a", 3);
+    allocator.allocate("b", 3);
+
+    let destroy = allocator.destroy_sender();
+    destroy.send("b".to_string()).unwrap();
+    destroy.send("a".to_string()).unwrap();
+    destroy.send("b".to_string()).unwrap();
+
+    assert_eq!(
+        allocator.peek_destroyed(),
+        ["b".to_string(), "a".to_string()]
+    );
+    assert_eq!(
+        allocator.get_destroyed_handles(),
+        vec!["b".to_string(), "a".to_string()]
+    );
+
+    // draining forgets the ids, a later request is reported again
+    destroy.send("b".to_string()).unwrap();
+    assert_eq!(allocator.get_destroyed_handles(), vec!["b".to_string()]);
+}
+
+#[test]
+fn allocations_consume_reservation() {
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+
+    allocator.reserve(10);
+    allocator.allocate("a", 4);
+    assert_eq!(allocator.reserved(), 6);
+
+    allocator.allocate("b", 8);
+    assert_eq!(allocator.reserved(), 0);
+}
+
+#[test]
+fn resize_moves_following_allocations() {
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+
+    let a = allocator.allocate("a", 4);
+    let b = allocator.allocate("b", 2);
+
+    let previous = allocator.resize("a", 7).unwrap();
+    assert_eq!((previous.offset, previous.size), (0, 4));
+    assert_eq!((a.size(), b.offset()), (7, 7));
+
+    allocator.resize("a", 1);
+    assert_eq!((b.offset(), BufferAlloc::size(&allocator)), (1, 3));
+
+    assert!(allocator.resize("c", 1).is_none());
+}
+
+#[test]
+fn resize_moves_empty_neighbours_behind_it() {
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+
+    let a = allocator.allocate("a", 2);
+    let m = allocator.allocate("m", 0);
+    let n = allocator.allocate("n", 0);
+    let b = allocator.allocate("b", 3);
+
+    // the empty "n" sorts after "m", the data of "b" starts where "m" is
+    allocator.resize("m", 4).unwrap();
+
+    assert_eq!((a.offset(), m.offset(), m.size()), (0, 2, 4));
+    assert_eq!((n.offset(), b.offset()), (6, 6));
+
+    // "n" sorts after "b" but lies at its start, so it stays in front of it
+    allocator.resize("b", 1).unwrap();
+    assert_eq!((n.offset(), b.offset(), b.size()), (6, 6, 1));
+
+    let c = allocator.allocate("c", 2);
+    assert_eq!(c.offset(), 7);
+
+    allocator.resize("b", 5).unwrap();
+    assert_eq!((n.offset(), b.offset(), c.offset()), (6, 6, 11));
+    assert_eq!(allocator.size(), 13);
+}
+
+#[test]
+fn free_many_compacts_once() {
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+
+    let a = allocator.allocate("a", 2);
+    let b = allocator.allocate("b", 3);
+    let c = allocator.allocate("c", 4);
+    let d = allocator.allocate("d", 5);
+
+    let freed = allocator.free_many(&["c".to_string(), "a".to_string(), "a".to_string()]);
+
+    assert_eq!(
+        freed
+            .iter()
+            .map(|allocation| (allocation.offset, allocation.size))
+            .collect::<Vec<_>>(),
+        vec![(0, 2), (5, 4)]
+    );
+    assert!(a.is_destroyed() && c.is_destroyed());
+    assert_eq!((b.offset(), d.offset()), (0, 3));
+    assert_eq!(BufferAlloc::size(&allocator), 8);
+
+    // the allocator removed them itself, nothing is left to free
+    assert!(allocator.get_destroyed_handles().is_empty());
+}
+
+#[test]
+fn allocation_copies_the_range() {
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+
+    allocator.allocate("a", 3);
+    allocator.allocate("b", 5);
+
+    let b = allocator.allocation("b").unwrap();
+    assert_eq!((b.offset, b.size), (3, 5));
+
+    allocator.free("a");
+
+    // the copy doesn't follow the allocator
+    assert_eq!(b.offset, 3);
+    assert_eq!(allocator.allocation("b").unwrap().offset, 0);
+    assert!(allocator.allocation("a").is_none());
+}
+
+fn translate_x(data: &mut [Vertex]) {
+    for vertex in data.iter_mut() {
+        vertex.position[0] += 1.0;
+    }
+}
+
+#[test]
+fn consecutive_actions_are_coalesced() {
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+    let handle = allocator.allocate("a", 2);
+
+    for _ in 0..3 {
+        handle
+            .send_action(ModifyAction::new(0, 2, Box::new(translate_x)))
+            .unwrap();
+    }
+
+    let mut received = Vec::new();
+    allocator.update(|action| received.push(action));
+    assert_eq!(received.len(), 1);
+
+    let mut data = [Vertex::default(); 2];
+    received[0].act(&mut data);
+    assert_eq!(data[1].position, [3.0, 0.0, 0.0]);
+}
+
+#[test]
+fn transforms_merge_into_one_matrix() {
+    let translate = glam::Mat4::from_translation(glam::Vec3::X);
+    let scale = glam::Mat4::from_scale(glam::Vec3::splat(2.0));
+
+    let mut action = ModifyAction::<Vertex>::transform(0, 1, translate);
+    assert!(action.merge(ModifyAction::transform(0, 1, scale)).is_ok());
+    assert_eq!(action.matrix(), Some(scale * translate));
+
+    let mut data = [Vertex {
+        position: [1.0, 0.0, 0.0],
+        ..Default::default()
+    }];
+    action.act(&mut data);
+    assert_eq!(data[0].position, [4.0, 0.0, 0.0]);
+
+    // a closure can't be folded into the matrix, so it's chained behind it
+    assert!(action
+        .merge(ModifyAction::new(0, 1, Box::new(translate_x)))
+        .is_ok());
+    assert_eq!(action.matrix(), None);
+}
+
+#[test]
+fn merge_refuses_other_ranges_and_generations() {
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+    let handle = allocator.allocate("a", 3);
+
+    // receive the actions as sent, before the allocator skips cancelled ones
+    let (sender, receiver) = std::sync::mpsc::channel();
+    handle.rebind(sender, allocator.destroy_sender());
+
+    let noop = || Box::new(|_: &mut [Vertex]| {});
+
+    handle.send_action(ModifyAction::new(0, 3, noop())).unwrap();
+    handle.cancel_pending();
+    handle.send_action(ModifyAction::new(0, 3, noop())).unwrap();
+    handle.send_action(ModifyAction::new(1, 2, noop())).unwrap();
+    handle.send_action(ModifyAction::new(0, 3, noop())).unwrap();
+
+    let mut actions = receiver.try_iter();
+    let mut stale = actions.next().unwrap();
+    let current = actions.next().unwrap();
+    let shifted = actions.next().unwrap();
+    let next = actions.next().unwrap();
+
+    let Err(mut current) = stale.merge(current) else {
+        panic!("actions of different generations were merged");
+    };
+    assert!(current.merge(shifted).is_err());
+    assert!(current.merge(next).is_ok());
+}
@@ -0,0 +1,1438 @@
+use std::sync::Arc;
+
+use rether::{
+    alloc::{
+        AllocHandle, BufferAlloc, BufferAllocation, BufferAllocationID, BufferDynamicAlloc,
+        BufferDynamicAllocator, DynamicAllocHandle, ModifyAction,
+    },
+    model::{
+        geometry::{Geometry, IndexedGeometry},
+        BufferLocation,
+    },
+    vertex::Vertex,
+    Buffer, BufferPool, IdPicker, IndexedBuffer, IndirectBuffer, RenderLayer, RetherError,
+    SimpleGeometry, Transform, TransformBuffer, Translate,
+};
+
+type VertexBuffer = Buffer<Vertex, BufferDynamicAllocator<Vertex>>;
+
+// Requests a device without a surface, returns None if the machine has no usable adapter.
+fn headless() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()
+}
+
+// Like `headless`, with lowered limits to exercise code paths that depend on them.
+fn headless_with_limits(limits: wgpu::Limits) -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+
+    let descriptor = wgpu::DeviceDescriptor {
+        required_limits: limits.using_resolution(adapter.limits()),
+        ..Default::default()
+    };
+
+    pollster::block_on(adapter.request_device(&descriptor, None)).ok()
+}
+
+fn vertices(count: usize, start: f32) -> Vec<Vertex> {
+    (0..count)
+        .map(|i| Vertex {
+            position: [start + i as f32, 0.0, 0.0],
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn positions(vertices: &[Vertex]) -> Vec<[f32; 3]> {
+    vertices.iter().map(|vertex| vertex.position).collect()
+}
+
+#[test]
+fn append_reads_back() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("append", &device);
+
+    let a = SimpleGeometry::init(vertices(3, 0.0));
+    let b = SimpleGeometry::init(vertices(2, 10.0));
+
+    buffer
+        .allocate_init("a", a.build_data(), &device, &queue)
+        .unwrap();
+    buffer
+        .allocate_init("b", b.build_data(), &device, &queue)
+        .unwrap();
+
+    let snapshot = buffer.snapshot("a", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(3, 0.0)));
+
+    let snapshot = buffer.snapshot("b", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(2, 10.0)));
+    assert_eq!(buffer.allocation_size("a"), Some(3));
+    assert_eq!(buffer.allocation_size("c"), None);
+}
+
+#[test]
+fn modify_applies_action() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("modify", &device);
+
+    let a = SimpleGeometry::init(vertices(3, 0.0));
+    let b = SimpleGeometry::init(vertices(2, 10.0));
+
+    buffer
+        .allocate_init("a", a.build_data(), &device, &queue)
+        .unwrap();
+    let handle = buffer
+        .allocate_init("b", b.build_data(), &device, &queue)
+        .unwrap();
+
+    let action = ModifyAction::new(
+        0,
+        handle.size(),
+        Box::new(|data: &mut [Vertex]| data.translate(glam::Vec3::Y)),
+    );
+    handle.send_action(action).unwrap();
+
+    let changes = buffer.update(&device, &queue).unwrap();
+    assert_eq!(changes.modified, vec!["b".to_string()]);
+
+    let geometry = buffer.read_geometry("b", &device, &queue).unwrap();
+    assert_eq!(
+        positions(geometry.vertices()),
+        vec![[10.0, 1.0, 0.0], [11.0, 1.0, 0.0]]
+    );
+
+    let snapshot = buffer.snapshot("a", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(3, 0.0)));
+
+    let snapshot = buffer.snapshot("b", &device, &queue).unwrap();
+    assert_eq!(
+        positions(snapshot.data()),
+        vec![[10.0, 1.0, 0.0], [11.0, 1.0, 0.0]]
+    );
+}
+
+#[test]
+fn oversized_write_keeps_neighbours() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("oversized write", &device);
+
+    let a = SimpleGeometry::init(vertices(3, 0.0));
+    let b = SimpleGeometry::init(vertices(2, 10.0));
+
+    buffer
+        .allocate_init("a", a.build_data(), &device, &queue)
+        .unwrap();
+    buffer
+        .allocate_init("b", b.build_data(), &device, &queue)
+        .unwrap();
+
+    let oversized = SimpleGeometry::init(vertices(4, 100.0));
+    assert_eq!(
+        buffer.write("a", oversized.build_data(), &queue),
+        Err(RetherError::ExceedsAllocation {
+            id: "a".to_string(),
+            size: 4,
+            allocation_size: 3,
+        })
+    );
+
+    let snapshot = buffer.snapshot("a", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(3, 0.0)));
+
+    let snapshot = buffer.snapshot("b", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(2, 10.0)));
+}
+
+#[test]
+fn free_shifts_following_allocations() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("free", &device);
+
+    let a = SimpleGeometry::init(vertices(3, 0.0));
+    let b = SimpleGeometry::init(vertices(2, 10.0));
+    let c = SimpleGeometry::init(vertices(4, 20.0));
+
+    buffer
+        .allocate_init("a", a.build_data(), &device, &queue)
+        .unwrap();
+    buffer
+        .allocate_init("b", b.build_data(), &device, &queue)
+        .unwrap();
+    buffer
+        .allocate_init("c", c.build_data(), &device, &queue)
+        .unwrap();
+
+    buffer.free("b", &device, &queue).unwrap();
+
+    assert!(buffer.get("b").is_none());
+    assert_eq!(buffer.get("c").unwrap().offset(), 3);
+
+    let snapshot = buffer.snapshot("a", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(3, 0.0)));
+
+    let snapshot = buffer.snapshot("c", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(4, 20.0)));
+}
+
+#[test]
+fn rapid_appends_keep_data() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("stress", &device);
+
+    for i in 0..256 {
+        let geometry = SimpleGeometry::init(vertices(3, i as f32 * 10.0));
+
+        buffer
+            .allocate_init(&i.to_string(), geometry.build_data(), &device, &queue)
+            .unwrap();
+    }
+
+    for i in [0, 127, 255] {
+        let snapshot = buffer.snapshot(&i.to_string(), &device, &queue).unwrap();
+
+        assert_eq!(
+            positions(snapshot.data()),
+            positions(&vertices(3, i as f32 * 10.0))
+        );
+    }
+}
+
+#[test]
+fn large_readbacks_keep_data() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("large readback", &device);
+
+    // big enough that the copy usually isn't mapped within the first polls
+    let data = vertices(1 << 18, 0.0);
+
+    buffer
+        .allocate_init(
+            "a",
+            SimpleGeometry::init(data.clone()).build_data(),
+            &device,
+            &queue,
+        )
+        .unwrap();
+
+    for _ in 0..3 {
+        let snapshot = buffer.snapshot("a", &device, &queue).unwrap();
+        assert_eq!(positions(snapshot.data()), positions(&data));
+    }
+}
+
+#[test]
+fn migrate_moves_data() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut source = VertexBuffer::new("source", &device);
+    let mut dest = VertexBuffer::new("dest", &device);
+
+    let a = SimpleGeometry::init(vertices(3, 0.0));
+    let b = SimpleGeometry::init(vertices(2, 10.0));
+
+    source
+        .allocate_init("a", a.build_data(), &device, &queue)
+        .unwrap();
+    dest.allocate_init("b", b.build_data(), &device, &queue)
+        .unwrap();
+
+    let handle = source.migrate("a", &mut dest, &device, &queue).unwrap();
+
+    assert!(source.get("a").is_none());
+    assert_eq!(handle.offset(), 2);
+
+    let snapshot = dest.snapshot("a", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(3, 0.0)));
+}
+
+#[test]
+fn migrate_refuses_used_ids() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut source = VertexBuffer::new("source", &device);
+    let mut dest = VertexBuffer::new("dest", &device);
+
+    let a = SimpleGeometry::init(vertices(3, 0.0));
+    let b = SimpleGeometry::init(vertices(2, 10.0));
+
+    source
+        .allocate_init("a", a.build_data(), &device, &queue)
+        .unwrap();
+    dest.allocate_init("a", b.build_data(), &device, &queue)
+        .unwrap();
+
+    let result = source.migrate("a", &mut dest, &device, &queue);
+    assert_eq!(
+        result.err(),
+        Some(RetherError::InvalidState("Allocation id already in use"))
+    );
+
+    let snapshot = source.snapshot("a", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(3, 0.0)));
+
+    let snapshot = dest.snapshot("a", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(2, 10.0)));
+}
+
+#[test]
+fn batch_allocation_keeps_order() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("batch", &device);
+
+    let a = SimpleGeometry::init(vertices(3, 0.0));
+    let b = SimpleGeometry::init(vertices(4, 10.0));
+
+    let handles = buffer
+        .allocate_batch(
+            &[("a", a.build_data()), ("b", b.build_data())],
+            &device,
+            &queue,
+        )
+        .unwrap();
+
+    assert_eq!(handles.len(), 2);
+    assert_eq!((handles[0].offset(), handles[0].size()), (0, 3));
+    assert_eq!((handles[1].offset(), handles[1].size()), (3, 4));
+
+    let snapshot = buffer.snapshot("b", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(4, 10.0)));
+}
+
+#[test]
+fn compute_aabb_matches_cpu() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new_with_usage("bounds", wgpu::BufferUsages::STORAGE, &device);
+
+    let mut geometry = SimpleGeometry::init(vertices(100_000, -50_000.0));
+    geometry.translate(glam::Vec3::new(0.0, 2.0, -3.0));
+
+    buffer
+        .allocate_init("mesh", geometry.build_data(), &device, &queue)
+        .unwrap();
+
+    let aabb = buffer.compute_aabb(&device, &queue).unwrap();
+
+    assert_eq!(Some(aabb), geometry.aabb());
+
+    // the pipeline is cached, a second run gives the same result
+    assert_eq!(buffer.compute_aabb(&device, &queue), Ok(aabb));
+}
+
+#[test]
+fn compute_aabb_needs_storage_usage() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("no storage", &device);
+
+    buffer
+        .allocate_init(
+            "mesh",
+            SimpleGeometry::init(vertices(4, 0.0)).build_data(),
+            &device,
+            &queue,
+        )
+        .unwrap();
+
+    assert!(matches!(
+        buffer.compute_aabb(&device, &queue),
+        Err(RetherError::InvalidState(_))
+    ));
+}
+
+#[test]
+fn compute_aabb_binds_large_buffers_in_chunks() {
+    // a small binding limit forces the 100k vertices into many chunks
+    let limits = wgpu::Limits {
+        max_storage_buffer_binding_size: 64 * 1024,
+        ..wgpu::Limits::downlevel_defaults()
+    };
+
+    let Some((device, queue)) = headless_with_limits(limits) else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer =
+        VertexBuffer::new_with_usage("chunked bounds", wgpu::BufferUsages::STORAGE, &device);
+
+    let mut geometry = SimpleGeometry::init(vertices(100_000, -50_000.0));
+    geometry.translate(glam::Vec3::new(0.0, 2.0, -3.0));
+
+    buffer
+        .allocate_init("mesh", geometry.build_data(), &device, &queue)
+        .unwrap();
+
+    assert_eq!(buffer.compute_aabb(&device, &queue).ok(), geometry.aabb());
+}
+
+#[test]
+fn layers_follow_allocations() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("layers", &device);
+
+    let geometry = SimpleGeometry::init(vertices(3, 0.0));
+    buffer
+        .allocate_init("glass", geometry.build_data(), &device, &queue)
+        .unwrap();
+
+    assert_eq!(buffer.layer("glass"), RenderLayer::Opaque);
+    assert!(buffer.set_layer("missing", RenderLayer::Overlay).is_err());
+
+    buffer.set_layer("glass", RenderLayer::Transparent).unwrap();
+    assert_eq!(buffer.layer("glass"), RenderLayer::Transparent);
+
+    buffer.free("glass", &device, &queue).unwrap();
+    assert_eq!(buffer.layer("glass"), RenderLayer::Opaque);
+}
+
+#[test]
+fn id_ranges_merge_neighbours() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("selection", &device);
+
+    for (id, count) in [("a", 2), ("b", 3), ("c", 1), ("d", 3)] {
+        let geometry = SimpleGeometry::init(vertices(count, 0.0));
+        buffer
+            .allocate_init(id, geometry.build_data(), &device, &queue)
+            .unwrap();
+    }
+
+    assert!(buffer.id_ranges(&["missing"]).is_empty());
+
+    // adjacent allocations become one draw, repeated ids overlap themselves
+    assert_eq!(
+        buffer.id_ranges(&["d", "b", "missing", "a", "b"]),
+        vec![0..5, 6..9]
+    );
+    assert_eq!(buffer.id_ranges(&["c", "b", "d"]), vec![2..9]);
+
+    buffer.set_layer("b", RenderLayer::Overlay).unwrap();
+    buffer.set_layer("c", RenderLayer::Overlay).unwrap();
+
+    assert_eq!(buffer.layer_ranges(RenderLayer::Overlay), vec![2..6]);
+    assert_eq!(buffer.layer_ranges(RenderLayer::Opaque), vec![0..2, 6..9]);
+    assert!(buffer.layer_ranges(RenderLayer::Transparent).is_empty());
+}
+
+#[test]
+fn location_ranges_are_relative_to_the_allocation() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("locations", &device);
+
+    for (id, count) in [("a", 3), ("tree", 6)] {
+        let geometry = SimpleGeometry::init(vertices(count, 0.0));
+        buffer
+            .allocate_init(id, geometry.build_data(), &device, &queue)
+            .unwrap();
+    }
+
+    let locations = [
+        BufferLocation { offset: 0, size: 2 },
+        BufferLocation { offset: 4, size: 4 },
+        BufferLocation { offset: 8, size: 1 },
+    ];
+
+    // cut off at the end of the allocation, past it nothing is drawn
+    assert_eq!(buffer.location_ranges("tree", &locations), vec![3..5, 7..9]);
+    assert!(buffer.location_ranges("missing", &locations).is_empty());
+}
+
+#[test]
+fn appends_within_reserved_capacity_dont_grow() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("reserved", &device);
+
+    buffer.reserve(30, &device, &queue).unwrap();
+    assert_eq!(buffer.capacity(), 30);
+
+    for i in 0..10 {
+        let geometry = SimpleGeometry::init(vertices(3, i as f32 * 10.0));
+
+        buffer
+            .allocate_init(&i.to_string(), geometry.build_data(), &device, &queue)
+            .unwrap();
+    }
+
+    assert_eq!(buffer.capacity(), 30);
+
+    let snapshot = buffer.snapshot("9", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(3, 90.0)));
+}
+
+#[test]
+fn cancelled_actions_are_skipped() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("cancel", &device);
+
+    let geometry = SimpleGeometry::init(vertices(2, 0.0));
+    let handle = buffer
+        .allocate_init("a", geometry.build_data(), &device, &queue)
+        .unwrap();
+
+    let translate = |translation: glam::Vec3| {
+        ModifyAction::new(
+            0,
+            handle.size(),
+            Box::new(move |data: &mut [Vertex]| data.translate(translation)),
+        )
+    };
+
+    handle.send_action(translate(glam::Vec3::Y)).unwrap();
+    handle.cancel_pending();
+    handle.send_action(translate(glam::Vec3::Z)).unwrap();
+
+    buffer.update(&device, &queue).unwrap();
+
+    let snapshot = buffer.snapshot("a", &device, &queue).unwrap();
+    assert_eq!(
+        positions(snapshot.data()),
+        vec![[0.0, 0.0, 1.0], [1.0, 0.0, 1.0]]
+    );
+}
+
+#[test]
+fn growing_past_device_limit_fails() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("limit", &device);
+
+    let limit = device.limits().max_buffer_size;
+    let elements = (limit / std::mem::size_of::<Vertex>() as u64 + 1) as usize;
+
+    assert!(matches!(
+        buffer.reserve(elements, &device, &queue),
+        Err(RetherError::ExceedsDeviceLimit { .. })
+    ));
+    assert_eq!(buffer.capacity(), 0);
+}
+
+#[test]
+fn pick_id_returns_drawn_allocation() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("camera"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    });
+
+    // view position followed by an identity view projection
+    let mut camera_data = vec![0.0f32; 4];
+    camera_data.extend(glam::Mat4::IDENTITY.to_cols_array());
+
+    let camera_buffer = wgpu::util::DeviceExt::create_buffer_init(
+        &device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("camera"),
+            contents: bytemuck::cast_slice(&camera_data),
+            usage: wgpu::BufferUsages::UNIFORM,
+        },
+    );
+
+    let camera = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("camera"),
+        layout: &camera_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: camera_buffer.as_entire_binding(),
+        }],
+    });
+
+    let mut buffer = VertexBuffer::new("pick", &device);
+
+    let triangle = |x: f32| {
+        SimpleGeometry::init(
+            [[x - 0.2, -0.5, 0.5], [x + 0.2, -0.5, 0.5], [x, 0.5, 0.5]]
+                .into_iter()
+                .map(|position| Vertex {
+                    position,
+                    ..Default::default()
+                })
+                .collect(),
+        )
+    };
+
+    buffer
+        .allocate_init("left", triangle(-0.5).build_data(), &device, &queue)
+        .unwrap();
+    buffer
+        .allocate_init("right", triangle(0.5).build_data(), &device, &queue)
+        .unwrap();
+
+    let picker = IdPicker::new(&device, &camera_layout, 64, 64);
+
+    let pick = |x, y| {
+        buffer
+            .pick_id(&picker, &camera, x, y, &device, &queue)
+            .unwrap()
+    };
+
+    assert_eq!(pick(16, 40), Some("left".to_string()));
+    assert_eq!(pick(48, 40), Some("right".to_string()));
+    assert_eq!(pick(32, 2), None);
+    assert_eq!(pick(64, 0), None);
+}
+
+#[test]
+fn write_only_action_overwrites_range() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("write only", &device);
+
+    let geometry = SimpleGeometry::init(vertices(3, 0.0));
+    let handle = buffer
+        .allocate_init("a", geometry.build_data(), &device, &queue)
+        .unwrap();
+
+    let action = ModifyAction::write_only(
+        1,
+        2,
+        Box::new(|data: &mut [Vertex]| {
+            assert!(data.iter().all(|vertex| vertex.position == [0.0; 3]));
+
+            for vertex in data.iter_mut() {
+                vertex.position = [5.0, 5.0, 5.0];
+            }
+        }),
+    );
+    handle.send_action(action).unwrap();
+
+    buffer.update(&device, &queue).unwrap();
+
+    let snapshot = buffer.snapshot("a", &device, &queue).unwrap();
+    assert_eq!(
+        positions(snapshot.data()),
+        vec![[0.0, 0.0, 0.0], [5.0, 5.0, 5.0], [5.0, 5.0, 5.0]]
+    );
+}
+
+#[test]
+fn pool_spreads_allocations_over_buffers() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut pool = BufferPool::<Vertex>::new("pool").with_max_elements(5);
+
+    for (id, start) in [("a", 0.0), ("b", 10.0), ("c", 20.0)] {
+        let geometry = SimpleGeometry::init(vertices(3, start));
+
+        pool.allocate_init(id, geometry.build_data(), &device, &queue)
+            .unwrap();
+    }
+
+    assert_eq!(pool.buffers().len(), 3);
+    assert!(pool
+        .allocate_init(
+            "a",
+            SimpleGeometry::init(vertices(1, 0.0)).build_data(),
+            &device,
+            &queue
+        )
+        .is_err());
+
+    // fits into the free space of the first buffer
+    let geometry = SimpleGeometry::init(vertices(2, 30.0));
+    pool.allocate_init("d", geometry.build_data(), &device, &queue)
+        .unwrap();
+    assert_eq!(pool.buffers().len(), 3);
+    assert_eq!(pool.get("d").unwrap().offset(), 3);
+
+    pool.free("b", &device, &queue).unwrap();
+    assert!(pool.get("b").is_none());
+
+    let snapshot = pool
+        .buffer_of("d")
+        .unwrap()
+        .snapshot("d", &device, &queue)
+        .unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(2, 30.0)));
+}
+
+#[test]
+fn failed_pool_allocation_adds_no_buffer() {
+    let limits = wgpu::Limits {
+        max_buffer_size: 1024 * std::mem::size_of::<Vertex>() as u64,
+        ..wgpu::Limits::downlevel_defaults()
+    };
+
+    let Some((device, queue)) = headless_with_limits(limits) else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut pool = BufferPool::<Vertex>::new("limited pool");
+
+    pool.allocate_init(
+        "a",
+        SimpleGeometry::init(vertices(3, 0.0)).build_data(),
+        &device,
+        &queue,
+    )
+    .unwrap();
+
+    // doesn't fit next to "a", and a buffer of its own would exceed the device limit
+    let oversized = SimpleGeometry::init(vertices(2048, 0.0));
+    assert!(matches!(
+        pool.allocate_init("b", oversized.build_data(), &device, &queue),
+        Err(RetherError::ExceedsDeviceLimit { .. })
+    ));
+
+    assert_eq!(pool.buffers().len(), 1);
+    assert!(pool.get("b").is_none());
+}
+
+#[test]
+fn indexed_allocation_returns_handles() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = IndexedBuffer::<
+        Vertex,
+        BufferDynamicAllocator<Vertex>,
+        BufferDynamicAllocator<Vertex>,
+    >::new("indexed", &device);
+
+    let geometry = IndexedGeometry::init(vertices(4, 0.0), vec![0, 1, 2, 2, 1, 3]);
+
+    let (vertex_handle, index_handle) = buffer
+        .allocate_init("quad", geometry.build_data(), &device, &queue)
+        .unwrap();
+
+    assert_eq!((vertex_handle.offset(), vertex_handle.size()), (0, 4));
+    assert_eq!((index_handle.offset(), index_handle.size()), (0, 6));
+}
+
+#[test]
+fn index_allocation_resizes_independently() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = IndexedBuffer::<
+        Vertex,
+        BufferDynamicAllocator<Vertex>,
+        BufferDynamicAllocator<Vertex>,
+    >::new("retriangulated", &device);
+
+    let quad = IndexedGeometry::init(vertices(4, 0.0), vec![0, 1, 2, 2, 1, 3]);
+
+    let (a_vertices, a_indices) = buffer
+        .allocate_init("a", quad.build_data(), &device, &queue)
+        .unwrap();
+    let (b_vertices, b_indices) = buffer
+        .allocate_init("b", quad.build_data(), &device, &queue)
+        .unwrap();
+
+    buffer.resize_indices("a", 9, &device, &queue).unwrap();
+    buffer
+        .write_indices("a", &[0, 1, 2, 2, 1, 3, 0, 2, 3], &queue)
+        .unwrap();
+
+    assert_eq!((a_indices.offset(), a_indices.size()), (0, 9));
+    assert_eq!((b_indices.offset(), b_indices.size()), (9, 6));
+    assert_eq!((a_vertices.size(), b_vertices.offset()), (4, 4));
+
+    buffer.resize_indices("a", 3, &device, &queue).unwrap();
+    assert_eq!(b_indices.offset(), 3);
+
+    assert!(buffer.write_indices("a", &[0, 1, 2, 3], &queue).is_err());
+    assert!(buffer.resize_indices("c", 3, &device, &queue).is_err());
+
+    // growing back fits into the capacity left by the shrink, the indices behind move along
+    buffer.resize_indices("a", 6, &device, &queue).unwrap();
+    assert_eq!(b_indices.offset(), 6);
+
+    assert_eq!(
+        buffer.read_indices("a", &device, &queue).unwrap(),
+        vec![0, 1, 2, 0, 0, 0]
+    );
+    assert_eq!(
+        buffer.read_indices("b", &device, &queue).unwrap(),
+        vec![0, 1, 2, 2, 1, 3]
+    );
+}
+
+#[test]
+fn indirect_buffer_holds_draws() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("indirect source", &device);
+
+    let a = SimpleGeometry::init(vertices(3, 0.0));
+    let b = SimpleGeometry::init(vertices(6, 10.0));
+
+    buffer
+        .allocate_init("a", a.build_data(), &device, &queue)
+        .unwrap();
+    buffer
+        .allocate_init("b", b.build_data(), &device, &queue)
+        .unwrap();
+
+    let args = buffer.indirect_args("b").unwrap();
+    assert_eq!((args.first_vertex, args.vertex_count), (3, 6));
+    assert!(buffer.indirect_args("missing").is_none());
+
+    let mut indirect = IndirectBuffer::new("indirect", &device);
+    assert!(indirect.is_empty());
+
+    for id in ["a", "b"] {
+        let args = buffer.indirect_args(id).unwrap();
+        indirect.push(&args, &device, &queue).unwrap();
+    }
+
+    assert_eq!(indirect.len(), 2);
+    assert_eq!(indirect.offset(1), 16);
+
+    assert!(indirect.write(1, &args, &queue).is_ok());
+    assert!(indirect.write(2, &args, &queue).is_err());
+}
+
+// hands out every allocation larger than the data written for it
+#[derive(Debug, Default)]
+struct OversizedAlloc(BufferDynamicAllocator<Vertex>);
+
+impl BufferAlloc<Vertex> for OversizedAlloc {
+    type Handle = DynamicAllocHandle<Vertex>;
+
+    fn get(&self, id: &str) -> Option<&Arc<Self::Handle>> {
+        self.0.get(id)
+    }
+
+    fn handles<'a>(&'a self) -> impl Iterator<Item = &'a Arc<Self::Handle>>
+    where
+        Self::Handle: 'a,
+    {
+        self.0.handles()
+    }
+
+    fn size(&self) -> usize {
+        BufferAlloc::size(&self.0)
+    }
+
+    fn update(&self, modify: impl FnMut(ModifyAction<Vertex>)) {
+        self.0.update(modify)
+    }
+}
+
+impl BufferDynamicAlloc<Vertex> for OversizedAlloc {
+    fn allocate(&mut self, id: &str, size: usize) -> Arc<Self::Handle> {
+        self.0.allocate(id, size + 100)
+    }
+
+    fn free(&mut self, id: &str) -> Option<BufferAllocation> {
+        self.0.free(id)
+    }
+
+    fn get_destroyed_handles(&mut self) -> Vec<BufferAllocationID> {
+        self.0.get_destroyed_handles()
+    }
+
+    fn peek_destroyed(&mut self) -> &[BufferAllocationID] {
+        self.0.peek_destroyed()
+    }
+}
+
+#[test]
+fn free_past_buffer_end_fails() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = Buffer::<Vertex, OversizedAlloc>::new("oversized", &device);
+
+    let geometry = SimpleGeometry::init(vertices(3, 0.0));
+    buffer
+        .allocate_init("a", geometry.build_data(), &device, &queue)
+        .unwrap();
+
+    assert_eq!(
+        buffer.free("a", &device, &queue),
+        Err(RetherError::OutOfBounds {
+            offset: 0,
+            size: 103,
+            buffer_size: 3,
+        })
+    );
+
+    // the allocator is left untouched, so it still matches the data
+    assert_eq!(buffer.get("a").unwrap().size(), 103);
+    assert_eq!(
+        buffer.free_many(&["a".to_string()], &device, &queue),
+        Err(RetherError::OutOfBounds {
+            offset: 0,
+            size: 103,
+            buffer_size: 3,
+        })
+    );
+    assert!(buffer.get("a").is_some());
+}
+
+#[test]
+fn failed_free_of_destroyed_handles_stays_pending() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = Buffer::<Vertex, OversizedAlloc>::new("oversized", &device);
+
+    let geometry = SimpleGeometry::init(vertices(3, 0.0));
+    let handle = buffer
+        .allocate_init("a", geometry.build_data(), &device, &queue)
+        .unwrap();
+
+    handle.destroy().unwrap();
+
+    let error = RetherError::OutOfBounds {
+        offset: 0,
+        size: 103,
+        buffer_size: 3,
+    };
+
+    assert_eq!(buffer.free_destroyed(&device, &queue), Err(error.clone()));
+    assert_eq!(buffer.peek_destroyed(), ["a".to_string()]);
+
+    assert_eq!(buffer.update(&device, &queue).map(|_| ()), Err(error));
+    assert_eq!(buffer.peek_destroyed(), ["a".to_string()]);
+    assert!(buffer.get("a").is_some());
+}
+
+#[test]
+fn free_many_keeps_remaining_data() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("free many", &device);
+
+    for (index, id) in ["a", "b", "c", "d"].iter().enumerate() {
+        buffer
+            .allocate_init(
+                id,
+                SimpleGeometry::init(vertices(index + 1, index as f32 * 10.0)).build_data(),
+                &device,
+                &queue,
+            )
+            .unwrap();
+    }
+
+    buffer
+        .free_many(&["a".to_string(), "c".to_string()], &device, &queue)
+        .unwrap();
+
+    assert_eq!(buffer.get("b").unwrap().offset(), 0);
+    assert_eq!(buffer.get("d").unwrap().offset(), 2);
+
+    let b = buffer.snapshot("b", &device, &queue).unwrap();
+    let d = buffer.snapshot("d", &device, &queue).unwrap();
+
+    assert_eq!(positions(b.data()), positions(&vertices(2, 10.0)));
+    assert_eq!(positions(d.data()), positions(&vertices(4, 30.0)));
+}
+
+#[test]
+fn mirror_follows_allocations() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("mirrored", &device);
+    let mut shadow = VertexBuffer::new("shadow", &device);
+
+    buffer
+        .allocate_init(
+            "a",
+            SimpleGeometry::init(vertices(2, 0.0)).build_data(),
+            &device,
+            &queue,
+        )
+        .unwrap();
+    buffer
+        .allocate_init(
+            "b",
+            SimpleGeometry::init(vertices(3, 10.0)).build_data(),
+            &device,
+            &queue,
+        )
+        .unwrap();
+
+    buffer.mirror_to(&mut shadow, &device, &queue).unwrap();
+
+    assert_eq!(shadow.get("b").unwrap().offset(), 2);
+    let b = shadow.snapshot("b", &device, &queue).unwrap();
+    assert_eq!(positions(b.data()), positions(&vertices(3, 10.0)));
+
+    buffer.free("a", &device, &queue).unwrap();
+    buffer.mirror_to(&mut shadow, &device, &queue).unwrap();
+
+    assert!(shadow.get("a").is_none());
+    assert_eq!(shadow.get("b").unwrap().offset(), 0);
+    let b = shadow.snapshot("b", &device, &queue).unwrap();
+    assert_eq!(positions(b.data()), positions(&vertices(3, 10.0)));
+}
+
+#[test]
+fn sync_makes_writes_visible() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("sync", &device);
+
+    buffer
+        .allocate_init(
+            "a",
+            SimpleGeometry::init(vertices(2, 0.0)).build_data(),
+            &device,
+            &queue,
+        )
+        .unwrap();
+
+    buffer
+        .write(
+            "a",
+            SimpleGeometry::init(vertices(2, 5.0)).build_data(),
+            &queue,
+        )
+        .unwrap();
+    buffer.sync(&device, &queue);
+
+    let geometry = buffer.read_geometry("a", &device, &queue).unwrap();
+    assert_eq!(positions(geometry.vertices()), positions(&vertices(2, 5.0)));
+}
+
+#[test]
+fn new_init_uploads_mapped_data() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let geometry = SimpleGeometry::init(vertices(3, 2.0));
+
+    let (mut buffer, handle) = VertexBuffer::new_init("init", "a", geometry.build_data(), &device);
+    assert_eq!((handle.offset(), handle.size()), (0, 3));
+
+    let snapshot = buffer.snapshot("a", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(3, 2.0)));
+
+    buffer
+        .allocate_init(
+            "b",
+            SimpleGeometry::init(vertices(1, 9.0)).build_data(),
+            &device,
+            &queue,
+        )
+        .unwrap();
+
+    let snapshot = buffer.snapshot("b", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(1, 9.0)));
+}
+
+#[test]
+fn encoder_variants_record_into_one_submission() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("encoder", &device);
+
+    for (id, start) in [("a", 0.0), ("b", 10.0)] {
+        buffer
+            .allocate_init(
+                id,
+                SimpleGeometry::init(vertices(2, start)).build_data(),
+                &device,
+                &queue,
+            )
+            .unwrap();
+    }
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Frame Encoder"),
+    });
+
+    buffer
+        .free_with_encoder("a", &device, &mut encoder)
+        .unwrap();
+    buffer
+        .allocate_init_with_encoder(
+            "c",
+            SimpleGeometry::init(vertices(3, 20.0)).build_data(),
+            &device,
+            &queue,
+            &mut encoder,
+        )
+        .unwrap();
+
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let snapshot = buffer.snapshot("b", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(2, 10.0)));
+
+    let snapshot = buffer.snapshot("c", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(3, 20.0)));
+}
+
+#[test]
+fn color_stream_follows_allocations() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("colors", &device);
+
+    buffer
+        .allocate_init(
+            "a",
+            SimpleGeometry::init(vertices(2, 0.0)).build_data(),
+            &device,
+            &queue,
+        )
+        .unwrap();
+
+    assert!(buffer.color_stream().is_none());
+    assert!(buffer.write_colors("a", &[[1.0; 4]], &queue).is_err());
+
+    buffer
+        .enable_color_stream(|vertex: &Vertex| vertex.color, &device, &queue)
+        .unwrap();
+
+    let colored = vertices(3, 10.0)
+        .into_iter()
+        .map(|vertex| Vertex {
+            color: [0.25; 4],
+            ..vertex
+        })
+        .collect::<Vec<Vertex>>();
+
+    buffer
+        .allocate_init(
+            "b",
+            SimpleGeometry::init(colored).build_data(),
+            &device,
+            &queue,
+        )
+        .unwrap();
+    buffer.free("a", &device, &queue).unwrap();
+
+    assert!(buffer.color_stream().is_some());
+
+    // the colors of "b" moved left with its data when "a" was freed
+    assert_eq!(
+        buffer.read_colors("b", &device, &queue).unwrap(),
+        vec![[0.25; 4]; 3]
+    );
+
+    buffer.write_colors("b", &[[1.0; 4]; 3], &queue).unwrap();
+    assert!(buffer.write_colors("b", &[[1.0; 4]; 4], &queue).is_err());
+    assert_eq!(
+        buffer.read_colors("b", &device, &queue).unwrap(),
+        vec![[1.0; 4]; 3]
+    );
+
+    // a free moves the written colors along with the elements behind it
+    buffer
+        .allocate_init(
+            "c",
+            SimpleGeometry::init(vertices(2, 20.0)).build_data(),
+            &device,
+            &queue,
+        )
+        .unwrap();
+    buffer.write_colors("c", &[[0.5; 4]; 2], &queue).unwrap();
+    buffer.free("b", &device, &queue).unwrap();
+
+    assert_eq!(buffer.get("c").unwrap().offset(), 0);
+    assert_eq!(
+        buffer.read_colors("c", &device, &queue).unwrap(),
+        vec![[0.5; 4]; 2]
+    );
+    assert!(buffer.read_colors("b", &device, &queue).is_err());
+
+    let snapshot = buffer.snapshot("c", &device, &queue).unwrap();
+    assert_eq!(positions(snapshot.data()), positions(&vertices(2, 20.0)));
+}
+
+#[test]
+fn scene_aabb_follows_refit() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("scene bounds", &device);
+
+    assert_eq!(buffer.scene_aabb(), None);
+
+    let a = SimpleGeometry::init(vertices(4, -10.0));
+    let mut b = SimpleGeometry::init(vertices(4, 5.0));
+    b.translate(glam::Vec3::new(0.0, 3.0, 0.0));
+
+    buffer
+        .allocate_init("a", a.build_data(), &device, &queue)
+        .unwrap();
+    buffer
+        .allocate_init("b", b.build_data(), &device, &queue)
+        .unwrap();
+
+    assert_eq!(buffer.scene_aabb(), None);
+
+    buffer.refit_bounds(&device, &queue).unwrap();
+
+    let union = a.aabb().unwrap().union(&b.aabb().unwrap());
+    assert_eq!(buffer.scene_aabb(), Some(union));
+
+    buffer.free("a", &device, &queue).unwrap();
+    buffer.refit_bounds(&device, &queue).unwrap();
+
+    assert_eq!(buffer.scene_aabb(), b.aabb());
+
+    buffer.free("b", &device, &queue).unwrap();
+
+    assert_eq!(buffer.scene_aabb(), None);
+}
+
+#[test]
+fn refit_reads_only_stale_allocations() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = VertexBuffer::new("stale bounds", &device);
+
+    for (id, start) in [("a", 0.0), ("b", 10.0), ("c", 20.0)] {
+        let geometry = SimpleGeometry::init(vertices(2, start));
+        buffer
+            .allocate_init(id, geometry.build_data(), &device, &queue)
+            .unwrap();
+    }
+
+    buffer.refit_bounds(&device, &queue).unwrap();
+
+    // two separate ranges are read back, the untouched allocation between them keeps
+    // its bounds
+    let a = SimpleGeometry::init(vertices(2, -5.0));
+    let c = SimpleGeometry::init(vertices(2, 30.0));
+    buffer.write("a", a.build_data(), &queue).unwrap();
+    buffer.write("c", c.build_data(), &queue).unwrap();
+
+    buffer.refit_bounds(&device, &queue).unwrap();
+
+    let scene = buffer.scene_aabb().unwrap();
+    assert_eq!(scene.min.x, -5.0);
+    assert_eq!(scene.max.x, 31.0);
+
+    buffer.free("c", &device, &queue).unwrap();
+    buffer.refit_bounds(&device, &queue).unwrap();
+    assert_eq!(buffer.scene_aabb().unwrap().max.x, 11.0);
+}
+
+#[test]
+fn transform_slots_are_reused() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut transforms = TransformBuffer::new("transforms", &device, &queue);
+
+    assert_eq!(transforms.offset("a"), 0);
+
+    let moved = Transform {
+        translation: glam::Vec3::new(1.0, 2.0, 3.0),
+        ..Default::default()
+    };
+
+    transforms.set("a", &moved, &device, &queue);
+    transforms.set("b", &moved, &device, &queue);
+    transforms.set("c", &moved, &device, &queue);
+
+    let a = transforms.offset("a");
+    let b = transforms.offset("b");
+
+    assert_ne!(a, 0);
+    assert_ne!(a, b);
+    assert_eq!(a % device.limits().min_uniform_buffer_offset_alignment, 0);
+
+    transforms.remove("a");
+    assert_eq!(transforms.offset("a"), 0);
+
+    transforms.set("d", &moved, &device, &queue);
+    assert_eq!(transforms.offset("d"), a);
+
+    transforms.retain(|id| id != "b");
+    assert_eq!(transforms.offset("b"), 0);
+}
+
+#[test]
+fn buffers_expose_their_label() {
+    let Some((device, _queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let buffer = VertexBuffer::new("terrain", &device);
+    assert_eq!(buffer.label(), "terrain");
+
+    let indexed = IndexedBuffer::<
+        Vertex,
+        BufferDynamicAllocator<Vertex>,
+        BufferDynamicAllocator<Vertex>,
+    >::new("mesh", &device);
+    assert_eq!(indexed.label(), "mesh");
+}
+
+#[test]
+fn write_welded_rebases_global_indices() {
+    let Some((device, queue)) = headless() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut buffer = IndexedBuffer::<
+        Vertex,
+        BufferDynamicAllocator<Vertex>,
+        BufferDynamicAllocator<Vertex>,
+    >::new("welded", &device);
+
+    let first = IndexedGeometry::init(vertices(3, 0.0), vec![0, 1, 2]);
+    buffer
+        .allocate_init("a", first.build_data(), &device, &queue)
+        .unwrap();
+
+    // two copies of the same vertices, the global indices start after "a"
+    let mut duplicated = vertices(2, 10.0);
+    duplicated.extend(vertices(2, 10.0));
+    let second = IndexedGeometry::init(duplicated, vec![3, 4, 5, 5, 4, 6]);
+    buffer
+        .allocate_init("b", second.build_data(), &device, &queue)
+        .unwrap();
+
+    buffer
+        .write_welded("b", second.build_data(), &queue)
+        .unwrap();
+
+    assert_eq!(
+        buffer.read_indices("b", &device, &queue).unwrap(),
+        vec![3, 4, 3, 3, 4, 4]
+    );
+    assert_eq!(
+        buffer.read_indices("a", &device, &queue).unwrap(),
+        vec![0, 1, 2]
+    );
+
+    // indices below the allocation can't be welded
+    let outside = IndexedGeometry::init(second.vertices().to_vec(), vec![0, 1, 2]);
+    assert!(matches!(
+        buffer.write_welded("b", outside.build_data(), &queue),
+        Err(RetherError::InvalidState(_))
+    ));
+}
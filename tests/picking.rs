@@ -0,0 +1,331 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use glam::{Vec2, Vec3};
+use rether::{
+    picking::{
+        interact::{TouchGesture, TouchPhase, TouchState},
+        Hitbox, HitboxNode, HitboxRoot, PlaneHitbox, Ray, TransformedBox,
+    },
+    vertex::Vertex,
+    Aabb, SimpleGeometry, Transform,
+};
+
+#[derive(Debug)]
+struct DistanceBox {
+    id: usize,
+    distance: f32,
+}
+
+impl HitboxNode<DistanceBox> for DistanceBox {
+    fn check_hit(&self, _ray: &Ray) -> Option<f32> {
+        Some(self.distance)
+    }
+
+    fn inner_nodes(&self) -> &[DistanceBox] {
+        &[]
+    }
+
+    fn aabb(&self) -> Aabb {
+        Aabb::new(Vec3::ZERO, Vec3::ONE)
+    }
+}
+
+#[test]
+fn nan_distance_is_farthest() {
+    let mut root = HitboxRoot::root();
+
+    root.add_node(Arc::new(DistanceBox {
+        id: 0,
+        distance: f32::NAN,
+    }));
+    root.add_node(Arc::new(DistanceBox {
+        id: 1,
+        distance: 2.0,
+    }));
+    root.add_node(Arc::new(DistanceBox {
+        id: 2,
+        distance: 1.0,
+    }));
+    root.add_node(Arc::new(DistanceBox {
+        id: 3,
+        distance: f32::NAN,
+    }));
+
+    let ray = Ray::new(Vec3::ZERO, Vec3::X);
+
+    assert_eq!(root.check_hit(&ray).map(|hit| hit.id), Some(2));
+}
+
+#[test]
+fn raycast_ignores_hits_beyond_max_distance() {
+    let mut root = HitboxRoot::root();
+
+    root.add_node(Arc::new(DistanceBox {
+        id: 0,
+        distance: 5.0,
+    }));
+    root.add_node(Arc::new(DistanceBox {
+        id: 1,
+        distance: 2.0,
+    }));
+
+    let ray = Ray::new(Vec3::ZERO, Vec3::X);
+
+    assert_eq!(root.raycast(&ray, Some(3.0)).map(|hit| hit.id), Some(1));
+    assert_eq!(root.raycast(&ray, Some(1.0)).map(|hit| hit.id), None);
+    assert_eq!(root.raycast(&ray, None).map(|hit| hit.id), Some(1));
+}
+
+#[test]
+fn raycast_filtered_skips_rejected_leaves() {
+    let mut root = HitboxRoot::root();
+
+    root.add_node(Arc::new(DistanceBox {
+        id: 0,
+        distance: 1.0,
+    }));
+    root.add_node(Arc::new(DistanceBox {
+        id: 1,
+        distance: 2.0,
+    }));
+
+    let ray = Ray::new(Vec3::ZERO, Vec3::X);
+
+    let hit = root.raycast_filtered(&ray, |node| node.id != 0);
+    assert_eq!(hit.map(|hit| hit.id), Some(1));
+
+    assert!(root.raycast_filtered(&ray, |_| false).is_none());
+}
+
+#[test]
+fn ray_hits_ground_plane() {
+    let ground = PlaneHitbox::ground();
+
+    let down = Ray::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(0.0, -1.0, 0.0));
+    assert_eq!(ground.check_hit(&down), Some(2.0));
+
+    let up = Ray::new(Vec3::new(1.0, 2.0, 3.0), Vec3::Y);
+    assert_eq!(ground.check_hit(&up), None);
+
+    let parallel = Ray::new(Vec3::new(1.0, 2.0, 3.0), Vec3::X);
+    assert_eq!(ground.check_hit(&parallel), None);
+}
+
+#[test]
+fn touch_state_recognizes_drag_and_pinch() {
+    let mut touches = TouchState::new();
+
+    assert!(touches
+        .handle_touch(0, TouchPhase::Started, Vec2::new(0.0, 0.0))
+        .is_none());
+
+    let Some(TouchGesture::Drag(drag)) =
+        touches.handle_touch(0, TouchPhase::Moved, Vec2::new(3.0, 4.0))
+    else {
+        panic!("Expected a drag");
+    };
+    assert_eq!(drag.delta, Vec2::new(3.0, 4.0));
+
+    touches.handle_touch(1, TouchPhase::Started, Vec2::new(13.0, 4.0));
+
+    let Some(TouchGesture::Scroll(pinch)) =
+        touches.handle_touch(1, TouchPhase::Moved, Vec2::new(23.0, 4.0))
+    else {
+        panic!("Expected a pinch");
+    };
+    assert_eq!(pinch.delta, 10.0);
+
+    touches.handle_touch(0, TouchPhase::Ended, Vec2::ZERO);
+    assert_eq!(touches.active_touches(), 1);
+}
+
+#[test]
+fn transformed_box_tests_in_local_space() {
+    let local = Aabb::new(Vec3::splat(-1.0), Vec3::ONE);
+
+    let rotated = TransformedBox::new(
+        local,
+        Transform {
+            rotation: glam::Quat::from_rotation_y(std::f32::consts::FRAC_PI_4),
+            ..Default::default()
+        },
+    );
+
+    // misses the unrotated box, but passes the corner turned towards +x
+    let ray = Ray::new(Vec3::new(1.2, 0.0, -10.0), Vec3::Z);
+    assert!(ray.intersect_aabb(local.min, local.max).is_none());
+    assert!(rotated.check_hit(&ray).is_some());
+
+    // inside the world bounds, but outside the rotated box
+    let ray = Ray::new(Vec3::new(1.2, 10.0, 1.2), Vec3::NEG_Y);
+    assert!(ray
+        .intersect_aabb(rotated.aabb().min, rotated.aabb().max)
+        .is_some());
+    assert!(rotated.check_hit(&ray).is_none());
+
+    let scaled = TransformedBox::new(
+        local,
+        Transform {
+            scale: Vec3::splat(2.0),
+            ..Default::default()
+        },
+    );
+
+    let distance = scaled
+        .check_hit(&Ray::new(Vec3::new(0.0, 0.0, -10.0), Vec3::Z))
+        .unwrap();
+    assert!((distance - 8.0).abs() < 1e-4);
+}
+
+#[derive(Debug)]
+struct MovingBox {
+    bounds: Mutex<Aabb>,
+    dirty: AtomicBool,
+    children: Vec<MovingBox>,
+}
+
+impl MovingBox {
+    fn new(bounds: Aabb, children: Vec<MovingBox>) -> Self {
+        Self {
+            bounds: Mutex::new(bounds),
+            dirty: AtomicBool::new(false),
+            children,
+        }
+    }
+
+    fn move_to(&self, bounds: Aabb) {
+        *self.bounds.lock().unwrap() = bounds;
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+}
+
+impl HitboxNode<MovingBox> for MovingBox {
+    fn check_hit(&self, ray: &Ray) -> Option<f32> {
+        let bounds = self.aabb();
+
+        ray.intersect_aabb(bounds.min, bounds.max)
+    }
+
+    fn inner_nodes(&self) -> &[MovingBox] {
+        &self.children
+    }
+
+    fn aabb(&self) -> Aabb {
+        *self.bounds.lock().unwrap()
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    fn refit(&self, children: Option<Aabb>) {
+        if let Some(children) = children {
+            *self.bounds.lock().unwrap() = children;
+        }
+
+        self.dirty.store(false, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn refit_dirty_updates_moved_branches() {
+    let unit = Aabb::new(Vec3::ZERO, Vec3::ONE);
+
+    let parent = Arc::new(MovingBox::new(
+        unit,
+        vec![
+            MovingBox::new(unit, Vec::new()),
+            MovingBox::new(unit, Vec::new()),
+        ],
+    ));
+
+    let mut root = HitboxRoot::root();
+    root.add_node(parent.clone());
+    root.add_node(Arc::new(MovingBox::new(unit, Vec::new())));
+
+    assert_eq!(root.refit_dirty(), 0);
+
+    parent.inner_nodes()[1].move_to(Aabb::new(Vec3::splat(4.0), Vec3::splat(5.0)));
+
+    // the ray only reaches the moved leaf once its parent encloses it
+    let ray = Ray::new(Vec3::new(4.5, 10.0, 4.5), Vec3::NEG_Y);
+    assert!(root.check_hit(&ray).is_none());
+
+    assert_eq!(root.refit_dirty(), 2);
+    assert_eq!(root.refit_dirty(), 0);
+
+    assert_eq!(root.aabb(), Some(Aabb::new(Vec3::ZERO, Vec3::splat(5.0))));
+    assert!(root.check_hit(&ray).is_some());
+}
+
+#[derive(Debug)]
+struct AllocationBox {
+    id: String,
+    distance: f32,
+}
+
+impl HitboxNode<AllocationBox> for AllocationBox {
+    fn check_hit(&self, _ray: &Ray) -> Option<f32> {
+        Some(self.distance)
+    }
+
+    fn inner_nodes(&self) -> &[AllocationBox] {
+        &[]
+    }
+
+    fn aabb(&self) -> Aabb {
+        Aabb::new(Vec3::ZERO, Vec3::ONE)
+    }
+
+    fn allocation_id(&self) -> Option<&String> {
+        Some(&self.id)
+    }
+}
+
+#[test]
+fn raycast_subset_only_hits_given_ids() {
+    let mut root = HitboxRoot::root();
+
+    for (id, distance) in [("a", 1.0), ("b", 2.0), ("c", 3.0)] {
+        root.add_node(Arc::new(AllocationBox {
+            id: id.to_string(),
+            distance,
+        }));
+    }
+
+    let ray = Ray::new(Vec3::ZERO, Vec3::X);
+
+    let ids = HashSet::from(["b".to_string(), "c".to_string()]);
+    let hit = root.raycast_subset(&ray, &ids);
+    assert_eq!(hit.map(|hit| hit.id.as_str()), Some("b"));
+
+    assert!(root.raycast_subset(&ray, &HashSet::new()).is_none());
+}
+
+#[test]
+fn simple_geometry_raycast_returns_nearest_triangle() {
+    let triangle = |z: f32| {
+        [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]].map(|[x, y]| Vertex {
+            position: [x, y, z],
+            ..Default::default()
+        })
+    };
+
+    // the far triangle comes first, the nearest hit has to win
+    let geometry = SimpleGeometry::init([triangle(-2.0), triangle(-1.0)].concat());
+
+    let ray = Ray::new(Vec3::new(0.25, 0.25, 1.0), Vec3::NEG_Z);
+    let (index, distance) = geometry.raycast(&ray).unwrap();
+
+    assert_eq!(index, 1);
+    assert!((distance - 2.0).abs() < 1e-5);
+
+    let miss = Ray::new(Vec3::new(2.0, 2.0, 1.0), Vec3::NEG_Z);
+    assert!(geometry.raycast(&miss).is_none());
+}
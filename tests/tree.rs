@@ -0,0 +1,630 @@
+use rether::{
+    alloc::{BufferAlloc, BufferDynamicAlloc, BufferDynamicAllocator, DynamicAllocHandle},
+    model::{
+        geometry::IndexedGeometry, BaseModel, BufferLocation, IndexedModel, Model, ModelState,
+        NodeKind, RotateModel, SubTree, TranslateModel, TreeModel, TreeModelBuilder,
+        VisibilityModel,
+    },
+    vertex::Vertex,
+    SimpleGeometry, Transform, Translate,
+};
+
+fn geometry(count: usize) -> SimpleGeometry<Vertex> {
+    SimpleGeometry::init(vec![Vertex::default(); count])
+}
+
+type Tree =
+    TreeModel<SubTree<Vertex, DynamicAllocHandle<Vertex>>, Vertex, DynamicAllocHandle<Vertex>>;
+
+fn location(tree: &Tree) -> (usize, usize) {
+    match tree {
+        TreeModel::Node { location, .. } | TreeModel::Leaf { location, .. } => {
+            (location.offset, location.size)
+        }
+        TreeModel::Root { .. } => panic!("Root has no location"),
+    }
+}
+
+#[test]
+fn builder_computes_locations() {
+    let tree = TreeModelBuilder::<Vertex, DynamicAllocHandle<Vertex>>::root(geometry(2))
+        .add_child(&geometry(3))
+        .push_group()
+        .add_child(&geometry(4))
+        .add_child(&geometry(5))
+        .pop_group()
+        .add_child(&geometry(1))
+        .build();
+
+    let children = tree.sub_handles().unwrap();
+    assert_eq!(children.len(), 3);
+
+    assert_eq!(location(&children[0]), (2, 3));
+    assert_eq!(location(&children[1]), (5, 9));
+    assert_eq!(location(&children[2]), (14, 1));
+
+    let group = children[1].sub_handles().unwrap();
+    assert_eq!(location(&group[0]), (5, 4));
+    assert_eq!(location(&group[1]), (9, 5));
+
+    children[1].set_visible_recursive(false);
+
+    let visible = tree
+        .visible_locations()
+        .iter()
+        .map(|BufferLocation { offset, size }| (*offset, *size))
+        .collect::<Vec<_>>();
+    // the root geometry in front of the children is drawn along with them
+    assert_eq!(visible, vec![(0, 5), (14, 1)]);
+}
+
+#[test]
+fn parents_draw_what_no_child_covers() {
+    let child = |offset| SubTree::from(Tree::create_leaf(BufferLocation { offset, size: 2 }));
+
+    let tree = Tree::create_root_with_models(
+        geometry(12),
+        vec![SubTree::from(Tree::create_node_with_models(
+            BufferLocation { offset: 2, size: 8 },
+            vec![child(4), child(6)],
+        ))],
+    );
+
+    let visible = |tree: &Tree| {
+        tree.visible_locations()
+            .iter()
+            .map(|location| (location.offset, location.size))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(visible(&tree), vec![(0, 12)]);
+
+    // hidden children leave a gap, the parents don't draw over them
+    let node = &tree.sub_handles().unwrap()[0];
+    node.sub_handles().unwrap()[0].set_visible_recursive(false);
+    assert_eq!(visible(&tree), vec![(0, 4), (6, 6)]);
+
+    node.set_visible_recursive(false);
+    assert_eq!(visible(&tree), vec![(0, 2), (10, 2)]);
+}
+
+#[test]
+fn descriptor_round_trips() {
+    let tree = TreeModelBuilder::<Vertex, DynamicAllocHandle<Vertex>>::root(geometry(2))
+        .push_group()
+        .add_child(&geometry(4))
+        .pop_group()
+        .add_child(&geometry(1))
+        .build();
+
+    tree.sub_handles().unwrap()[1].set_visible_recursive(false);
+    if let TreeModel::Root { transform, .. } = &tree {
+        transform.write().translate(glam::Vec3::X);
+    }
+
+    let descriptor = tree.to_descriptor().unwrap();
+    let loaded = Tree::from_descriptor(descriptor.clone());
+
+    let reloaded = loaded.to_descriptor().unwrap();
+    assert_eq!(reloaded.vertices.len(), 7);
+    assert_eq!(reloaded.transform, descriptor.transform);
+    assert_eq!(reloaded.children, descriptor.children);
+    assert!(!reloaded.children[1].visible);
+}
+
+#[test]
+fn childless_node_stays_a_node() {
+    let tree = TreeModelBuilder::<Vertex, DynamicAllocHandle<Vertex>>::root(geometry(2))
+        .push_group()
+        .pop_group()
+        .add_child(&geometry(1))
+        .build();
+
+    let descriptor = tree.to_descriptor().unwrap();
+    assert_eq!(descriptor.children[0].kind, NodeKind::Node);
+    assert_eq!(descriptor.children[1].kind, NodeKind::Leaf);
+
+    let loaded = Tree::from_descriptor(descriptor);
+    let children = loaded.sub_handles().unwrap();
+
+    assert!(matches!(&*children[0], TreeModel::Node { .. }));
+    assert!(matches!(&*children[1], TreeModel::Leaf { .. }));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn descriptor_round_trips_through_serde() {
+    use rether::model::SceneDescriptor;
+
+    let tree = TreeModelBuilder::<Vertex, DynamicAllocHandle<Vertex>>::root(geometry(2))
+        .push_group()
+        .add_child(&geometry(4))
+        .pop_group()
+        .push_group()
+        .pop_group()
+        .add_child(&geometry(1))
+        .build();
+
+    tree.sub_handles().unwrap()[2].set_visible_recursive(false);
+    if let TreeModel::Root { transform, .. } = &tree {
+        transform.write().translate(glam::Vec3::X);
+    }
+
+    let descriptor = tree.to_descriptor().unwrap();
+
+    let json = serde_json::to_string(&descriptor).unwrap();
+    let deserialized: SceneDescriptor<Vertex> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(
+        positions(&deserialized.vertices),
+        positions(&descriptor.vertices)
+    );
+    assert_eq!(deserialized.indices, descriptor.indices);
+    assert_eq!(deserialized.transform, descriptor.transform);
+    assert_eq!(deserialized.visible, descriptor.visible);
+    assert_eq!(deserialized.children, descriptor.children);
+
+    let loaded = Tree::from_descriptor(deserialized);
+    assert_eq!(
+        loaded.to_descriptor().unwrap().children,
+        descriptor.children
+    );
+}
+
+#[cfg(feature = "serde")]
+fn positions(vertices: &[Vertex]) -> Vec<[f32; 3]> {
+    vertices.iter().map(|vertex| vertex.position).collect()
+}
+
+#[test]
+fn transformed_child_is_baked() {
+    let child = SimpleGeometry::init(vec![
+        Vertex {
+            position: [1.0, 0.0, 0.0],
+            ..Default::default()
+        };
+        2
+    ]);
+
+    let transform = Transform {
+        translation: glam::Vec3::new(0.0, 5.0, 0.0),
+        scale: glam::Vec3::splat(2.0),
+        ..Default::default()
+    };
+
+    let tree = TreeModelBuilder::<Vertex, DynamicAllocHandle<Vertex>>::root(geometry(1))
+        .add_transformed_child(&child, &transform)
+        .build();
+
+    let TreeModel::Root { state, .. } = &tree else {
+        panic!("Expected a root");
+    };
+
+    let ModelState::Dormant(geometry) = &*state.read() else {
+        panic!("Expected dormant geometry");
+    };
+
+    let positions = geometry
+        .vertices()
+        .iter()
+        .map(|vertex| vertex.position)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        positions,
+        vec![[0.0, 0.0, 0.0], [2.0, 5.0, 0.0], [2.0, 5.0, 0.0]]
+    );
+}
+
+#[test]
+fn attach_and_detach_children() {
+    let mut tree = TreeModelBuilder::<Vertex, DynamicAllocHandle<Vertex>>::root(geometry(2))
+        .add_child(&geometry(3))
+        .build();
+
+    let leaf = |offset| Tree::create_leaf(BufferLocation { offset, size: 1 });
+
+    let mut child = leaf(0);
+    assert!(child.attach_child(SubTree::from(leaf(1)), true).is_err());
+
+    // past the end of the root's five vertices
+    assert!(tree.attach_child(SubTree::from(leaf(5)), true).is_err());
+
+    // a child in the allocation of the root is already placed by it
+    assert!(tree.attach_child(SubTree::from(leaf(0)), false).is_err());
+
+    tree.attach_child(SubTree::from(child), true).unwrap();
+    assert_eq!(tree.sub_handles().unwrap().len(), 2);
+
+    let detached = tree.detach_child(0, true).unwrap();
+    assert_eq!(location(&detached), (2, 3));
+    assert_eq!(tree.sub_handles().unwrap().len(), 1);
+
+    assert!(tree.detach_child(1, true).is_err());
+}
+
+#[test]
+fn reparenting_moves_external_children() {
+    let mut tree = Tree::create_root(geometry(2));
+    tree.translate(glam::Vec3::X).unwrap();
+
+    let mut other = BufferDynamicAllocator::<Vertex>::default();
+
+    let child = Tree::create_leaf_in("other", BufferLocation { offset: 1, size: 2 });
+    child.bind_allocation(other.allocate("other", 3)).unwrap();
+
+    // the child of an allocation of its own can't reuse the allocation of its parent
+    Model::wake(&tree, other.allocate("tree", 2)).unwrap();
+    assert!(tree
+        .attach_child(
+            SubTree::from(Tree::create_leaf_in(
+                "tree",
+                BufferLocation { offset: 0, size: 1 }
+            )),
+            true
+        )
+        .is_err());
+
+    let offsets = || {
+        let mut offsets = Vec::new();
+        other.update(|action| offsets.push((action.id().cloned(), action.offset, action.size)));
+        offsets
+    };
+
+    // taken as relative to the root, so it's moved by the transform of the root
+    tree.attach_child(SubTree::from(child), false).unwrap();
+    assert_eq!(
+        tree.world_transform(&[0]).unwrap().translation,
+        glam::Vec3::X
+    );
+    assert_eq!(
+        tree.node_transform(&[0]).unwrap().translation,
+        glam::Vec3::ZERO
+    );
+    assert_eq!(offsets(), vec![(Some("other".to_string()), 1, 2)]);
+
+    // moving the root moves the child through its own allocation
+    tree.translate(glam::Vec3::Y).unwrap();
+    assert_eq!(
+        offsets(),
+        vec![
+            (Some("tree".to_string()), 3, 2),
+            (Some("other".to_string()), 1, 2)
+        ]
+    );
+
+    let detached = tree.detach_child(0, false).unwrap();
+    assert_eq!(detached.transform().translation, glam::Vec3::ZERO);
+    assert_eq!(offsets(), vec![(Some("other".to_string()), 1, 2)]);
+
+    tree.translate(glam::Vec3::Z).unwrap();
+    assert_eq!(offsets(), vec![(Some("tree".to_string()), 3, 2)]);
+}
+
+#[test]
+fn external_leaves_keep_their_allocation() {
+    let mut tree = TreeModelBuilder::<Vertex, DynamicAllocHandle<Vertex>>::root(geometry(2))
+        .add_child(&geometry(3))
+        .build();
+
+    let external = |offset| BufferLocation { offset, size: 2 };
+
+    tree.attach_child(
+        SubTree::from(Tree::create_node_in(
+            "other",
+            external(4),
+            vec![
+                SubTree::from(Tree::create_leaf(external(4))),
+                SubTree::from(Tree::create_leaf_in("third", external(0))),
+            ],
+        )),
+        true,
+    )
+    .unwrap();
+
+    let children = tree.sub_handles().unwrap();
+    assert_eq!(children[0].allocation_id(), None);
+    assert_eq!(children[1].allocation_id().as_deref(), Some("other"));
+
+    let ranges = |id: Option<&str>| {
+        tree.visible_allocations()
+            .get(&id.map(str::to_string))
+            .map(|locations| {
+                locations
+                    .iter()
+                    .map(|location| (location.offset, location.size))
+                    .collect::<Vec<_>>()
+            })
+    };
+
+    // ranges of different allocations are never merged
+    assert_eq!(ranges(None), Some(vec![(0, 5)]));
+    assert_eq!(ranges(Some("other")), Some(vec![(4, 2)]));
+    assert_eq!(ranges(Some("third")), Some(vec![(0, 2)]));
+    assert_eq!(tree.visible_locations().len(), 1);
+
+    let descriptor = tree.to_descriptor().unwrap();
+    let loaded = Tree::from_descriptor(descriptor.clone());
+    assert_eq!(
+        loaded.to_descriptor().unwrap().children,
+        descriptor.children
+    );
+    assert_eq!(
+        loaded.sub_handles().unwrap()[1].allocation_id().as_deref(),
+        Some("other")
+    );
+}
+
+#[test]
+fn external_models_transform_their_allocation() {
+    let location = BufferLocation { offset: 4, size: 2 };
+
+    let node = Tree::create_node_in(
+        "other",
+        location.clone(),
+        vec![SubTree::from(Tree::create_leaf(location.clone()))],
+    );
+
+    let visible = node
+        .visible_locations()
+        .iter()
+        .map(|location| (location.offset, location.size))
+        .collect::<Vec<_>>();
+    assert_eq!(visible, vec![(4, 2)]);
+
+    // the root doesn't reach the allocation, so an unbound node can't move
+    assert!(node.translate(glam::Vec3::X).is_err());
+    assert_eq!(node.transform().translation, glam::Vec3::ZERO);
+
+    let mut other = BufferDynamicAllocator::<Vertex>::default();
+    other.allocate("padding", 1);
+
+    assert!(node.bind_allocation(other.allocate("wrong", 6)).is_err());
+    assert!(node.sub_handles().unwrap()[0]
+        .bind_allocation(other.allocate("leaf", 6))
+        .is_err());
+
+    node.bind_allocation(other.allocate("other", 6)).unwrap();
+    node.translate(glam::Vec3::X).unwrap();
+    assert_eq!(node.transform().translation, glam::Vec3::X);
+
+    let mut received = Vec::new();
+    other.update(|action| received.push((action.id().cloned(), action.offset, action.size)));
+
+    // the child shares the allocation of the node and is moved by its action
+    assert_eq!(received, vec![(Some("other".to_string()), 17, 2)]);
+}
+
+#[test]
+fn nodes_report_state_without_panicking() {
+    let tree = TreeModelBuilder::<Vertex, DynamicAllocHandle<Vertex>>::root(geometry(2))
+        .add_child(&geometry(3))
+        .build();
+    let leaf = &tree.sub_handles().unwrap()[0];
+
+    assert!(tree.state().is_ok());
+    assert!(leaf.state().is_err());
+    assert!(!leaf.is_destroyed());
+
+    // a node in an allocation of its own is destroyed with it
+    let mut other = BufferDynamicAllocator::<Vertex>::default();
+    let handle = other.allocate("other", 2);
+
+    let node = Tree::create_leaf_in("other", BufferLocation { offset: 0, size: 2 });
+    node.bind_allocation(handle.clone()).unwrap();
+    assert!(!node.is_destroyed());
+
+    handle.destroy().unwrap();
+    assert!(node.is_destroyed());
+}
+
+#[test]
+fn take_geometry_leaves_empty_dormant_state() {
+    let mut state = ModelState::<Vertex, DynamicAllocHandle<Vertex>>::from(geometry(3));
+
+    let taken = state.take_geometry().unwrap();
+    assert_eq!(taken.vertices().len(), 3);
+
+    let ModelState::Dormant(left) = &state else {
+        panic!("Expected a dormant state");
+    };
+    assert!(left.vertices().is_empty());
+
+    assert!(state.take_indexed_geometry().is_none());
+}
+
+#[test]
+fn node_transform_follows_path() {
+    let tree = TreeModelBuilder::<Vertex, DynamicAllocHandle<Vertex>>::root(geometry(2))
+        .push_group()
+        .add_child(&geometry(4))
+        .pop_group()
+        .add_child(&geometry(1))
+        .build();
+
+    tree.translate(glam::Vec3::X).unwrap();
+    tree.sub_handles().unwrap()[0]
+        .translate(glam::Vec3::Y)
+        .unwrap();
+
+    let local = |path: &[usize]| tree.node_transform(path).unwrap().translation;
+    let world = |path: &[usize]| tree.world_transform(path).unwrap().translation;
+
+    assert_eq!(local(&[]), glam::Vec3::X);
+    assert_eq!(world(&[]), glam::Vec3::X);
+
+    // the group only holds its own translation, its world transform adds the root's
+    assert_eq!(local(&[0]), glam::Vec3::Y);
+    assert_eq!(world(&[0]), glam::Vec3::new(1.0, 1.0, 0.0));
+
+    assert_eq!(local(&[0, 0]), glam::Vec3::ZERO);
+    assert_eq!(world(&[0, 0]), glam::Vec3::new(1.0, 1.0, 0.0));
+
+    assert_eq!(local(&[1]), glam::Vec3::ZERO);
+    assert_eq!(world(&[1]), glam::Vec3::X);
+
+    assert!(tree.node_transform(&[2]).is_none());
+    assert!(tree.node_transform(&[1, 0]).is_none());
+    assert!(tree.world_transform(&[1, 0]).is_none());
+
+    // descriptors hold world transforms, the local ones are derived again when loading
+    let loaded = Tree::from_descriptor(tree.to_descriptor().unwrap());
+    assert_eq!(
+        loaded.node_transform(&[0]).unwrap().translation,
+        glam::Vec3::Y
+    );
+    assert_eq!(
+        loaded.world_transform(&[0, 0]).unwrap().translation,
+        glam::Vec3::new(1.0, 1.0, 0.0)
+    );
+}
+
+#[test]
+fn bounds_follow_dormant_and_awake_transforms() {
+    let geometry = SimpleGeometry::init(
+        (0..2)
+            .map(|i| Vertex {
+                position: [i as f32 * 2.0, 0.0, 0.0],
+                ..Default::default()
+            })
+            .collect(),
+    );
+
+    let model = BaseModel::<Vertex, DynamicAllocHandle<Vertex>>::simple(geometry);
+
+    model.translate(glam::Vec3::Y).unwrap();
+    let dormant = model.bounds().unwrap();
+    assert_eq!(dormant.min, glam::Vec3::new(0.0, 1.0, 0.0));
+    assert_eq!(dormant.max, glam::Vec3::new(2.0, 1.0, 0.0));
+
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+    Model::wake(&model, allocator.allocate("model", 2)).unwrap();
+    assert_eq!(model.bounds(), Some(dormant));
+
+    model
+        .rotate(
+            glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+            None,
+        )
+        .unwrap();
+    model.translate(glam::Vec3::X).unwrap();
+
+    let awake = model.bounds().unwrap();
+    assert!(awake.min.abs_diff_eq(glam::Vec3::new(0.0, 0.0, 0.0), 1e-5));
+    assert!(awake.max.abs_diff_eq(glam::Vec3::new(0.0, 2.0, 0.0), 1e-5));
+
+    let empty = BaseModel::<Vertex, DynamicAllocHandle<Vertex>>::simple(SimpleGeometry::empty());
+    assert!(empty.bounds().is_none());
+}
+
+#[test]
+fn try_translate_skips_locked_state() {
+    let model = BaseModel::<Vertex, DynamicAllocHandle<Vertex>>::simple(geometry(3));
+
+    {
+        let _loader = model.state().unwrap().write();
+
+        assert!(!model.try_translate(glam::Vec3::X).unwrap());
+        assert!(!model.try_scale(glam::Vec3::splat(2.0), None).unwrap());
+    }
+
+    assert!(model.try_translate(glam::Vec3::X).unwrap());
+    assert_eq!(model.transform().translation, glam::Vec3::X);
+}
+
+#[test]
+fn dead_root_keeps_its_transform() {
+    let tree = Tree::create_root(geometry(3));
+
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+    Model::wake(&tree, allocator.allocate("tree", 3)).unwrap();
+    tree.destroy().unwrap();
+
+    // the transform only changes together with the geometry
+    assert!(tree.translate(glam::Vec3::X).is_err());
+    assert!(tree.rotate(glam::Quat::from_rotation_z(1.0), None).is_err());
+    assert_eq!(tree.transform(), Transform::default());
+}
+
+#[test]
+fn indexed_model_rejects_single_handle() {
+    let geometry = IndexedGeometry::init(vec![Vertex::default(); 3], vec![0, 1, 2]);
+    let model = BaseModel::<Vertex, DynamicAllocHandle<Vertex>>::indexed(geometry);
+
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+
+    assert!(Model::wake(&model, allocator.allocate("indexed", 3)).is_err());
+    assert!(matches!(
+        &*model.state().unwrap().read(),
+        ModelState::DormantIndexed(_)
+    ));
+}
+
+#[test]
+fn indexed_model_wakes_with_both_handles() {
+    let indexed = IndexedGeometry::init(vec![Vertex::default(); 3], vec![0, 1, 2]);
+    let model = BaseModel::<Vertex, DynamicAllocHandle<Vertex>>::indexed(indexed.clone());
+    let tree = Tree::create_root(indexed);
+
+    let mut vertices = BufferDynamicAllocator::<Vertex>::default();
+    let mut indices = BufferDynamicAllocator::<Vertex>::default();
+
+    // handles of different allocations are rejected
+    assert!(IndexedModel::wake(
+        &model,
+        vertices.allocate("indexed", 3),
+        indices.allocate("other", 3)
+    )
+    .is_err());
+
+    IndexedModel::wake(
+        &model,
+        vertices.allocate("model", 3),
+        indices.allocate("model", 3),
+    )
+    .unwrap();
+    assert!(model.state().unwrap().read().is_alive());
+
+    model.translate(glam::Vec3::X).unwrap();
+    assert_eq!(model.transform().translation, glam::Vec3::X);
+
+    IndexedModel::wake(
+        &tree,
+        vertices.allocate("tree", 3),
+        indices.allocate("tree", 3),
+    )
+    .unwrap();
+    assert!(tree.state().unwrap().read().is_alive());
+
+    let simple = BaseModel::<Vertex, DynamicAllocHandle<Vertex>>::simple(geometry(3));
+    assert!(IndexedModel::wake(
+        &simple,
+        vertices.allocate("simple", 3),
+        indices.allocate("simple", 3)
+    )
+    .is_err());
+}
+
+#[test]
+fn childless_root_is_drawn_whole() {
+    let tree = Tree::create_root(geometry(4));
+
+    let visible = |tree: &Tree| {
+        tree.visible_locations()
+            .iter()
+            .map(|location| (location.offset, location.size))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(visible(&tree), vec![(0, 4)]);
+
+    let mut allocator = BufferDynamicAllocator::<Vertex>::default();
+    allocator.allocate("padding", 2);
+    Model::wake(&tree, allocator.allocate("tree", 4)).unwrap();
+
+    // locations are relative to the allocation
+    assert_eq!(visible(&tree), vec![(0, 4)]);
+
+    tree.set_visible_recursive(false);
+    assert!(visible(&tree).is_empty());
+}